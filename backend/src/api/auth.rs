@@ -0,0 +1,217 @@
+use std::future::{Ready, ready};
+use std::sync::Arc;
+
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, dev::Payload, get, http::header, post, web,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    api::{error::ApiError, middleware::API_KEY_HEADER},
+    config::{ApiKeyTier, CONFIG},
+    core::auth::{AuthService, User},
+    state::AppState,
+};
+
+/// Minimum accepted password length
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserResponse {
+    pub username: String,
+    /// Unix timestamp (seconds) of the registration
+    pub created_at: u64,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            username: user.username,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenResponse {
+    pub token: String,
+    pub token_type: String,
+    /// Unix timestamp (seconds) after which the token is rejected
+    pub expires_at: u64,
+}
+
+/// The user authenticated by the `Authorization: Bearer <jwt>` header.
+///
+/// Add it as a handler argument to require authentication, requests without a valid token
+/// are rejected with 401 before reaching the handler.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub username: String,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(app_state) = req.app_data::<web::Data<AppState>>() else {
+            return ready(Err(ApiError::unauthorized("Authentication unavailable")));
+        };
+
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        let Some(token) = token else {
+            return ready(Err(ApiError::unauthorized("Missing bearer token")));
+        };
+
+        ready(
+            app_state
+                .auth
+                .validate_token(token)
+                .map(|claims| AuthenticatedUser {
+                    username: claims.sub,
+                })
+                .map_err(|e| ApiError::unauthorized(e.to_string())),
+        )
+    }
+}
+
+/// A request carrying an `admin` tier API key in the `X-Api-Key` header.
+///
+/// Add it as a handler argument to restrict an endpoint to the operators, requests without
+/// an admin key are rejected with 401 or 403 before reaching the handler.
+#[derive(Debug, Clone, Copy)]
+pub struct AdminKey;
+
+impl FromRequest for AdminKey {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(api_key) = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return ready(Err(ApiError::unauthorized("Missing admin API key")));
+        };
+
+        ready(match CONFIG.get().api_keys.0.get(api_key) {
+            Some(ApiKeyTier::Admin) => Ok(AdminKey),
+            Some(_) => Err(ApiError::forbidden("An admin API key is required")),
+            None => Err(ApiError::unauthorized("Invalid API key")),
+        })
+    }
+}
+
+#[utoipa::path(
+    request_body = Credentials,
+    responses(
+        (status = 201, description = "User registered", body = UserResponse),
+        (status = 400, description = "Invalid username or password", body = ApiError),
+        (status = 401, description = "Registration closed and missing or invalid API key", body = ApiError),
+        (status = 403, description = "Registration closed and not an admin API key", body = ApiError),
+        (status = 409, description = "Username already taken", body = ApiError),
+    ),
+    security((), ("admin_key" = []))
+)]
+#[post("/auth/register")]
+async fn post_register_service(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<Credentials>,
+) -> Result<HttpResponse, ApiError> {
+    // Without open registration only the operators create users
+    if !CONFIG.get().open_registration {
+        AdminKey::extract(&req).await?;
+    }
+
+    let Credentials { username, password } = body.into_inner();
+    let username = username.trim().to_string();
+
+    if username.is_empty() {
+        return Err(ApiError::bad_request("Username must not be empty"));
+    }
+    if password.len() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::bad_request(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    // Password hashing is CPU heavy, keep it off the async workers
+    let auth: Arc<AuthService> = Arc::clone(&app_state.auth);
+    match web::block(move || auth.register(&username, &password)).await {
+        Ok(Ok(user)) => {
+            info!("Registered user: {}", user.username);
+            Ok(HttpResponse::Created().json(UserResponse::from(user)))
+        }
+        Ok(Err(e)) => Err(ApiError::conflict(e.to_string())),
+        Err(e) => {
+            error!("Failed to register user: {}", e);
+            Err(ApiError::internal("Failed to register user"))
+        }
+    }
+}
+
+#[utoipa::path(
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "Token issued", body = TokenResponse),
+        (status = 401, description = "Invalid credentials", body = ApiError),
+    )
+)]
+#[post("/auth/login")]
+async fn post_login_service(
+    app_state: web::Data<AppState>,
+    body: web::Json<Credentials>,
+) -> Result<HttpResponse, ApiError> {
+    let Credentials { username, password } = body.into_inner();
+    let username = username.trim().to_string();
+
+    let auth: Arc<AuthService> = Arc::clone(&app_state.auth);
+    match web::block(move || auth.login(&username, &password)).await {
+        Ok(Ok((token, claims))) => Ok(HttpResponse::Ok().json(TokenResponse {
+            token,
+            token_type: "Bearer".to_string(),
+            expires_at: claims.exp,
+        })),
+        Ok(Err(e)) => Err(ApiError::unauthorized(e.to_string())),
+        Err(e) => {
+            error!("Failed to log user in: {}", e);
+            Err(ApiError::internal("Failed to log in"))
+        }
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Authenticated user", body = UserResponse),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "User no longer exists", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[get("/auth/me")]
+async fn get_me_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    // A valid token can outlive its user, as users are not persisted across restarts
+    match app_state.auth.user(&user.username) {
+        Some(user) => Ok(HttpResponse::Ok().json(UserResponse::from(user))),
+        None => Err(ApiError::not_found("User not found")),
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use actix_web::{HttpResponse, get};
+use serde::Serialize;
+use utoipa::{
+    ToSchema,
+    openapi::{Ref, RefOr, schema::Schema},
+};
+
+use crate::types::{CandleFeedMessage, CandleFeedRequest, PoolEvent};
+
+/// Who sends the messages of an event schema
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventDirection {
+    /// Sent by the server to its subscribers
+    Server,
+    /// Sent by a client over a WebSocket
+    Client,
+}
+
+/// JSON schema of the messages of a stream
+///
+/// A breaking change of the messages ships as a new `.v2` schema next to the `.v1` one,
+/// adding an optional field or a message `type` does not.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct EventSchema {
+    /// Versioned name, e.g. `pool_event.v1`
+    pub name: String,
+    pub direction: EventDirection,
+    /// Paths under `/api/v1` the messages are exchanged on
+    pub channels: Vec<String>,
+    pub description: String,
+    /// Schema of a message, the `$ref`s point to `components.schemas` of the catalog
+    #[schema(value_type = Object)]
+    pub schema: RefOr<Schema>,
+}
+
+/// Schemas referenced by the event schemas
+#[derive(Clone, Serialize, ToSchema)]
+pub struct EventSchemaComponents {
+    #[schema(value_type = Object)]
+    pub schemas: BTreeMap<String, RefOr<Schema>>,
+}
+
+/// Every event schema, self-contained so it can be fed to a code generator as is
+#[derive(Clone, Serialize, ToSchema)]
+pub struct EventSchemaCatalog {
+    pub events: Vec<EventSchema>,
+    pub components: EventSchemaComponents,
+}
+
+impl EventSchemaCatalog {
+    fn new() -> Self {
+        let mut schemas = BTreeMap::new();
+        let events = vec![
+            event_schema::<PoolEvent>(
+                &mut schemas,
+                "pool_event.v1",
+                EventDirection::Server,
+                &["/ws/pools", "/pool/{address}/stream"],
+                "Change of the tracked pools, discriminated by `type`. The SSE stream only sends the `updated` messages of its pool",
+            ),
+            event_schema::<CandleFeedMessage>(
+                &mut schemas,
+                "candle_feed_message.v1",
+                EventDirection::Server,
+                &["/ws/candles"],
+                "Candles of the subscribed pools and request errors, discriminated by `type`",
+            ),
+            event_schema::<CandleFeedRequest>(
+                &mut schemas,
+                "candle_feed_request.v1",
+                EventDirection::Client,
+                &["/ws/candles"],
+                "Subscription change of a candle feed client, discriminated by `op`",
+            ),
+        ];
+
+        Self {
+            events,
+            components: EventSchemaComponents { schemas },
+        }
+    }
+}
+
+/// Event schema of the `T` messages, adding `T` and the schemas it references to
+/// `schemas`
+fn event_schema<T: ToSchema>(
+    schemas: &mut BTreeMap<String, RefOr<Schema>>,
+    name: &str,
+    direction: EventDirection,
+    channels: &[&str],
+    description: &str,
+) -> EventSchema {
+    let mut referenced = Vec::new();
+    T::schemas(&mut referenced);
+    schemas.extend(referenced);
+    schemas.insert(T::name().into_owned(), T::schema());
+
+    EventSchema {
+        name: name.to_string(),
+        direction,
+        channels: channels.iter().map(|channel| channel.to_string()).collect(),
+        description: description.to_string(),
+        schema: RefOr::Ref(Ref::from_schema_name(T::name())),
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Versioned JSON schemas of the messages of the WebSocket and SSE streams", body = EventSchemaCatalog),
+    )
+)]
+#[get("/events/schema")]
+async fn get_events_schema_service() -> HttpResponse {
+    HttpResponse::Ok().json(EventSchemaCatalog::new())
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_cors::Cors;
+use actix_web::{
+    Error, HttpRequest, HttpResponse, ResponseError,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method,
+        header::{self, HeaderValue},
+    },
+    middleware::Next,
+    web,
+};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    api::error::ApiError,
+    config::{ApiKeyTier, CONFIG, CorsConfig},
+    state::AppState,
+};
+
+/// Header identifying API clients, used as the rate limiting key when it is configured
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Header carrying the ID of a request, taken from the client when valid
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest client provided request ID accepted
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    /// ID of the request handled by the current task
+    static REQUEST_ID: String;
+}
+
+/// ID of the request handled by the current task, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// Random UUID v4
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Build the CORS middleware from the configuration
+pub fn cors(config: &CorsConfig) -> Cors {
+    let is_any = |items: &[String]| items.is_empty() || items.iter().any(|item| item == "*");
+
+    let mut cors = Cors::default();
+
+    cors = if is_any(&config.allowed_origins) {
+        cors.allow_any_origin()
+    } else {
+        config
+            .allowed_origins
+            .iter()
+            .fold(cors, |cors, origin| cors.allowed_origin(origin))
+    };
+
+    cors = if is_any(&config.allowed_methods) {
+        cors.allow_any_method()
+    } else {
+        cors.allowed_methods(config.allowed_methods.iter().map(String::as_str))
+    };
+
+    cors = if is_any(&config.allowed_headers) {
+        cors.allow_any_header()
+    } else {
+        cors.allowed_headers(config.allowed_headers.iter().map(String::as_str))
+    };
+
+    cors.expose_headers([REQUEST_ID_HEADER])
+}
+
+/// Assign an ID to every request, available to the handlers through `current_request_id`
+/// and in the tracing span of the request, returned in the `X-Request-Id` header, and log
+/// an access line for every request
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map_or_else(uuid_v4, str::to_string);
+
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let span = info_span!("request", request_id = %id);
+    let start_time = Instant::now();
+
+    let mut res = REQUEST_ID
+        .scope(id.clone(), next.call(req).instrument(span.clone()))
+        .await?;
+
+    span.in_scope(|| {
+        info!(
+            "{} {} {} {:.1}ms",
+            method,
+            path,
+            res.status().as_u16(),
+            start_time.elapsed().as_secs_f64() * 1000.0
+        )
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut()
+            .insert(header::HeaderName::from_static("x-request-id"), value);
+    }
+
+    Ok(res)
+}
+
+/// Record the count and latency of every request in the metrics registry
+pub async fn track_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let metrics = req
+        .app_data::<web::Data<AppState>>()
+        .map(|app_state| Arc::clone(&app_state.metrics));
+    let method = req.method().to_string();
+    let start_time = Instant::now();
+
+    let res = next.call(req).await?;
+
+    if let Some(metrics) = metrics {
+        // Use the route pattern rather than the raw path to keep the labels cardinality bounded
+        let path = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.record_http_request(&method, &path, res.status().as_u16(), start_time.elapsed());
+    }
+
+    Ok(res)
+}
+
+/// Endpoints a sandbox API key may call besides the `GET` ones
+///
+/// `DELETE /positions/{id}` is only allowed with `dry_run=true`, and
+/// `POST /pool/{address}/strategy/dry-run` never executes anything.
+const SANDBOX_POST_PATHS: [&str; 4] = [
+    "/api/v1/analytics/query",
+    "/api/v1/invest",
+    "/api/v1/auth/register",
+    "/api/v1/auth/login",
+];
+
+/// Whether a request only reads or simulates, without sending a transaction or changing
+/// the server state
+fn is_sandbox_allowed(req: &ServiceRequest) -> bool {
+    match *req.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => true,
+        Method::POST => {
+            SANDBOX_POST_PATHS.contains(&req.path())
+                || (req.path().starts_with("/api/v1/pool/")
+                    && req.path().ends_with("/strategy/dry-run"))
+        }
+        Method::DELETE => {
+            req.path().starts_with("/api/v1/positions/")
+                && web::Query::<HashMap<String, String>>::from_query(req.query_string())
+                    .is_ok_and(|query| query.get("dry_run").is_some_and(|value| value == "true"))
+        }
+        _ => false,
+    }
+}
+
+/// Reject requests with an unknown API key with 401, and requests of sandbox keys to
+/// endpoints that could execute live actions with 403
+///
+/// Once `API_KEYS` is set, requests without an API key are limited to the sandbox
+/// endpoints too, and rejected with 401 elsewhere.
+pub async fn api_key_tier(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let config = CONFIG.get();
+    let api_key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    // Without configured keys any key is accepted, it only identifies the client
+    let tier = match api_key.filter(|_| !config.api_keys.0.is_empty()) {
+        None => None,
+        Some(api_key) => match config.api_keys.0.get(api_key) {
+            Some(tier) => Some(*tier),
+            None => {
+                warn!("Rejected unknown API key on {}", req.path());
+                let response = ApiError::unauthorized("Invalid API key").error_response();
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+        },
+    };
+
+    if tier.is_none() && !config.api_keys.0.is_empty() && !is_sandbox_allowed(&req) {
+        debug!("Keyless request refused {} {}", req.method(), req.path());
+        let response =
+            ApiError::unauthorized("An API key is required for this endpoint").error_response();
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    if tier == Some(ApiKeyTier::Sandbox) && !is_sandbox_allowed(&req) {
+        debug!("Sandbox API key refused {} {}", req.method(), req.path());
+        let response =
+            ApiError::forbidden("Sandbox API keys can only call read and simulation endpoints")
+                .error_response();
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Reject requests of clients exceeding their rate limit with 429 Too Many Requests
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(rate_limiter) = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|app_state| app_state.rate_limiter.clone())
+    else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let client = rate_limit_client(req.request());
+    match rate_limiter.check(&client) {
+        Ok(()) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(retry_after) => {
+            debug!("Rate limited {} on {}", client, req.path());
+            let response = too_many_requests("Too many requests", retry_after);
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+/// Key of the rate limit buckets of a request
+///
+/// Only configured keys get their own bucket, a client rotating unknown keys is limited
+/// by its address. The peer address is used rather than forwarded headers, those can be
+/// spoofed by clients.
+pub fn rate_limit_client(req: &HttpRequest) -> String {
+    let api_keys = &CONFIG.get().api_keys.0;
+    match req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|api_key| api_keys.contains_key(*api_key))
+    {
+        Some(api_key) => format!("key:{}", api_key),
+        None => format!(
+            "ip:{}",
+            req.peer_addr()
+                .map(|addr| addr.ip().to_string())
+                .unwrap_or_default()
+        ),
+    }
+}
+
+/// 429 Too Many Requests response, with the `Retry-After` header
+pub fn too_many_requests(message: &str, retry_after: Duration) -> HttpResponse {
+    let mut response = ApiError::too_many_requests(message).error_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+    );
+    response
+}
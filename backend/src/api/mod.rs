@@ -1,6 +1,363 @@
-use actix_web::{HttpResponse, Responder, get, post, web};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use crate::{state::AppState, types::Pool};
+use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, patch, post, put, web};
+use alloy::{
+    primitives::Address,
+    providers::{Provider, WalletProvider},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::{
+    IntoParams, Modify, OpenApi, ToSchema,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+use utoipa_actix_web::service_config::ServiceConfig;
+
+pub mod auth;
+pub mod error;
+pub mod events;
+pub mod middleware;
+pub mod positions;
+pub mod sse;
+pub mod ws;
+
+use crate::{
+    api::{
+        auth::{AdminKey, AuthenticatedUser},
+        error::ApiError,
+        middleware::API_KEY_HEADER,
+    },
+    config::{
+        CONFIG, DEFAULT_CANDLE_INTERVAL_SECS, DEFAULT_CANDLE_LIMIT, DEFAULT_PAGE_LIMIT,
+        DEFAULT_SWAP_LOOKBACK_SECS, DEFAULT_TICK_BITMAP_WORDS, DEFAULT_VOLATILITY_PERIODS,
+        DEFAULT_VOLATILITY_WINDOWS_SECS, HEALTH_CHECK_TIMEOUT_SECS, INCIDENT_WINDOW_SECS,
+        MAX_CANDLE_LIMIT, MAX_PAGE_LIMIT, MAX_SWAP_LOOKBACK_SECS, MAX_TICK_BITMAP_WORDS,
+        MIN_CANDLE_INTERVAL_SECS, PoolStrategy,
+    },
+    core::{
+        self,
+        analytics::{AnalyticsQuery, Candle, PoolAnalytics, VolatilityStats},
+        annotations::{Annotation, AnnotationUpdate},
+        anomaly::PoolAnomaly,
+        as_of::PoolAsOf,
+        block_time::{BlockDeadline, BlockTimeEstimate},
+        executions::PoolProfitability,
+        gas::GasPercentiles,
+        invest::{InvestPlan, InvestRequest},
+        liquidity::LiquidityDistribution,
+        pool_verification::{PoolVerification, PoolVerificationStatus},
+        quotes::Quotes,
+        range_tuning::TunedRangeWidth,
+        snapshots::PositionSnapshot,
+        strategy::StrategyDryRun,
+        swaps::{FeeAprEstimate, TickCrossings},
+        tx::FailSafeState,
+    },
+    state::AppState,
+    types::{
+        ChartQuote, ChartSide, DexType, Paginated, Pool, PoolPriceState, PoolRefresh,
+        PoolSortField, RegisterPoolRequest, SortOrder, TokenPools,
+    },
+    utils::{amm_math::MAX_RANGE_WIDTH, log_sampling::LOG_SAMPLER, time::unix_timestamp},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "YieldAI API",
+        description = "Liquidity pool tracking and management for Uniswap V3 and PancakeSwap V3"
+    ),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+/// Register the endpoints served outside of `/api/v1`, where the probes and the
+/// Prometheus scrapers expect them
+pub fn configure_root(cfg: &mut ServiceConfig) {
+    cfg.service(get_health_service)
+        .service(get_readiness_service)
+        .service(get_metrics_service);
+}
+
+/// Register the v1 handlers, mounted under the `/api/v1` scope
+///
+/// Breaking changes go into a new `configure_v2` mounted under `/api/v2`, so v1
+/// clients keep working.
+pub fn configure_v1(cfg: &mut ServiceConfig) {
+    cfg.app_data(
+        web::JsonConfig::default()
+            .error_handler(|e, _| ApiError::bad_request(e.to_string()).into()),
+    )
+    .app_data(
+        web::QueryConfig::default()
+            .error_handler(|e, _| ApiError::bad_request(e.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default()
+            .error_handler(|e, _| ApiError::bad_request(e.to_string()).into()),
+    )
+    .service(get_index_service)
+    .service(get_readiness_service)
+    .service(get_status_service)
+    .service(get_anomalies_service)
+    .service(post_analytics_query_service)
+    .service(get_pools_service)
+    .service(get_pools_by_token_service)
+    .service(post_pools_service)
+    .service(delete_pool_service)
+    .service(patch_pool_service)
+    .service(get_pool_service)
+    .service(get_pool_chart_service)
+    .service(get_pool_volatility_service)
+    .service(get_pool_apr_service)
+    .service(get_pool_profitability_service)
+    .service(get_pool_tick_crossings_service)
+    .service(get_pool_as_of_service)
+    .service(get_quotes_service)
+    .service(post_invest_service)
+    .service(get_pool_liquidity_service)
+    .service(get_pool_range_width_service)
+    .service(get_pool_strategy_service)
+    .service(post_pool_strategy_dry_run_service)
+    .service(post_pool_refresh_service)
+    .service(get_admin_config_service)
+    .service(post_admin_config_reload_service)
+    .service(post_admin_snapshots_backfill_service)
+    .service(get_admin_allowlist_service)
+    .service(get_admin_tx_failsafe_service)
+    .service(post_admin_tx_failsafe_reset_service)
+    .service(get_admin_pool_verification_service)
+    .service(get_admin_prometheus_rules_service)
+    .service(get_admin_log_sampling_service)
+    .service(put_admin_log_sampling_service)
+    .service(auth::post_register_service)
+    .service(auth::post_login_service)
+    .service(auth::get_me_service)
+    .service(positions::get_positions_service)
+    .service(positions::get_wallet_positions_service)
+    .service(positions::post_positions_service)
+    .service(positions::post_position_rebalance_service)
+    .service(positions::post_position_collect_service)
+    .service(positions::get_position_nft_service)
+    .service(positions::delete_position_service)
+    .service(positions::patch_position_service)
+    .service(positions::get_position_snapshots_service)
+    .service(get_block_time_service)
+    .service(get_gas_service)
+    .service(events::get_events_schema_service)
+    .service(sse::get_pool_stream_service)
+    .service(ws::get_pools_ws_service)
+    .service(ws::get_candles_ws_service);
+}
+
+/// Register the bearer JWT security scheme used by the authenticated endpoints
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "admin_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PoolQuery {
+    /// Re-fetch the pool details from the blockchain before returning them
+    pub refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BlockTimeQuery {
+    /// Also convert this duration (in seconds) into block and timestamp deadlines
+    pub within_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BlockTimeResponse {
+    #[serde(flatten)]
+    pub estimate: BlockTimeEstimate,
+    pub deadline: Option<BlockDeadline>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GasResponse {
+    /// Current gas price (wei)
+    #[schema(value_type = String)]
+    #[serde(with = "crate::types::u128_string")]
+    pub gas_price: u128,
+    /// Percentiles over the sampling window, absent until a first sample is taken
+    pub percentiles: Option<GasPercentiles>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyCheck {
+    pub name: String,
+    pub status: DependencyStatus,
+    pub latency_ms: u128,
+    /// What was observed when up, the error when down
+    pub details: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub checks: Vec<DependencyCheck>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceStatus {
+    Operational,
+    Degraded,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    DependencyDown,
+    PoolAnomaly,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolRefreshStatus {
+    pub address: String,
+    /// Unix timestamp (seconds) of the last refresh
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatusResponse {
+    pub status: ServiceStatus,
+    pub version: String,
+    pub git_sha: String,
+    /// Unix timestamp (seconds) of the build
+    pub build_time: u64,
+    /// Unix timestamp (seconds) of the process start
+    pub started_at: u64,
+    pub uptime_secs: u64,
+    pub pools: Vec<PoolRefreshStatus>,
+    pub dependencies: Vec<DependencyCheck>,
+    pub incidents: Vec<Incident>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AnomaliesQuery {
+    /// Only return the anomalies of this pool
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChartQuery {
+    /// Token whose price is charted, defaults to token0
+    #[param(inline)]
+    pub side: Option<ChartSide>,
+    /// Currency of the prices, defaults to the other token of the pool
+    #[param(inline)]
+    pub quote: Option<ChartQuote>,
+    /// Duration of a candle in seconds
+    pub interval_secs: Option<u64>,
+    /// Maximum number of candles, the most recent ones are returned
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AprQuery {
+    /// Width of the range in ticks, rounded up to the tick spacing. Defaults to the tuned width of the pool
+    pub width: Option<i32>,
+    /// Duration in seconds of the swap history the estimate is based on, 1 day at most and by default
+    pub lookback_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ProfitabilityQuery {
+    /// Unix timestamp (seconds) of the start of the period, defaults to the first execution
+    pub from: Option<u64>,
+    /// Unix timestamp (seconds) of the end of the period, defaults to now
+    pub to: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TickCrossingsQuery {
+    /// Width of the candidate range in ticks, rounded up to the tick spacing
+    pub width: i32,
+    /// Duration in seconds of the swap history to replay, 1 day at most and by default
+    pub lookback_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct QuotesQuery {
+    /// Amount to sell, in the smallest unit of the token
+    pub amount: String,
+    /// Address of the token to sell
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AsOfQuery {
+    /// Unix timestamp (seconds) to rebuild the state at
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LiquidityQuery {
+    /// Number of tick bitmap words (256 tick spacings each) to read on each side of the current tick
+    pub words: Option<i16>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct VolatilityQuery {
+    /// Token whose price is analysed, defaults to token0
+    #[param(inline)]
+    pub side: Option<ChartSide>,
+    /// Durations in seconds of the candles to compute the statistics on, comma separated
+    pub windows: Option<String>,
+    /// Number of most recent candles used per window
+    pub periods: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PoolsQuery {
+    /// Page number, starting at 1
+    pub page: Option<usize>,
+    /// Number of pools per page
+    pub limit: Option<usize>,
+    /// Only return pools of this dex
+    #[param(inline)]
+    pub dex_type: Option<DexType>,
+    /// Only return pools containing this token (symbol or address, case insensitive)
+    pub token: Option<String>,
+    #[param(inline)]
+    pub sort_by: Option<PoolSortField>,
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+}
 
 #[utoipa::path(
         responses(
@@ -24,15 +381,1407 @@ async fn get_health_service() -> impl Responder {
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Pools", body = Vec<Pool>),
+        (status = 200, description = "All dependencies are up", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency is down", body = ReadinessResponse),
+    )
+)]
+#[get("/health/ready")]
+async fn get_readiness_service(app_state: web::Data<AppState>) -> impl Responder {
+    let checks = check_dependencies(&app_state).await;
+    let ready = checks
+        .iter()
+        .all(|check| check.status == DependencyStatus::Up);
+    let response = ReadinessResponse { ready, checks };
+
+    if ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+/// Probe every external dependency concurrently
+async fn check_dependencies(app_state: &AppState) -> Vec<DependencyCheck> {
+    let provider = &app_state.evm_provider;
+    let metrics = &app_state.metrics;
+    let address = CONFIG.get().contract_address;
+
+    let (rpc, contract) = tokio::join!(
+        probe("rpc", async {
+            let block = metrics
+                .track_rpc("eth_blockNumber", provider.get_block_number())
+                .await?;
+            Ok(format!("latest block {}", block))
+        }),
+        probe("yield_contract", async {
+            let code = metrics
+                .track_rpc("eth_getCode", provider.get_code_at(address))
+                .await?;
+            if code.is_empty() {
+                anyhow::bail!("no contract deployed at {}", address);
+            }
+            Ok(format!("{} bytes of code at {}", code.len(), address))
+        }),
+    );
+
+    vec![rpc, contract]
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Service status summary", body = StatusResponse),
+    )
+)]
+#[get("/status")]
+async fn get_status_service(app_state: web::Data<AppState>) -> impl Responder {
+    let dependencies = check_dependencies(&app_state).await;
+    let now = unix_timestamp();
+
+    let mut incidents: Vec<Incident> = dependencies
+        .iter()
+        .filter(|check| check.status == DependencyStatus::Down)
+        .map(|check| Incident {
+            kind: IncidentKind::DependencyDown,
+            message: format!("{} is down: {}", check.name, check.details),
+        })
+        .collect();
+
+    let mut anomalies_per_pool: Vec<(String, usize)> = Vec::new();
+    for anomaly in app_state.anomalies.recent(None) {
+        if now.saturating_sub(anomaly.detected_at) > INCIDENT_WINDOW_SECS {
+            // Anomalies are sorted newest first
+            break;
+        }
+        match anomalies_per_pool
+            .iter_mut()
+            .find(|(address, _)| *address == anomaly.address)
+        {
+            Some((_, count)) => *count += 1,
+            None => anomalies_per_pool.push((anomaly.address, 1)),
+        }
+    }
+    incidents.extend(
+        anomalies_per_pool
+            .into_iter()
+            .map(|(address, count)| Incident {
+                kind: IncidentKind::PoolAnomaly,
+                message: format!(
+                    "{} anomalies detected on pool {} in the last {}s",
+                    count, address, INCIDENT_WINDOW_SECS
+                ),
+            }),
+    );
+
+    let mut pools: Vec<PoolRefreshStatus> = app_state
+        .pools
+        .iter()
+        .map(|entry| PoolRefreshStatus {
+            address: entry.key().clone(),
+            updated_at: entry.updated_at,
+        })
+        .collect();
+    pools.sort_by(|a, b| a.address.cmp(&b.address));
+
+    HttpResponse::Ok().json(StatusResponse {
+        status: if incidents.is_empty() {
+            ServiceStatus::Operational
+        } else {
+            ServiceStatus::Degraded
+        },
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_sha: env!("GIT_SHA").to_string(),
+        build_time: env!("BUILD_TIME").parse().unwrap_or_default(),
+        started_at: app_state.started_at,
+        uptime_secs: now.saturating_sub(app_state.started_at),
+        pools,
+        dependencies,
+        incidents,
+    })
+}
+
+/// Run a dependency probe with a timeout and report its status and latency
+async fn probe(name: &str, check: impl Future<Output = anyhow::Result<String>>) -> DependencyCheck {
+    let start_time = Instant::now();
+    let result = tokio::time::timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS), check).await;
+    let latency_ms = start_time.elapsed().as_millis();
+
+    let (status, details) = match result {
+        Ok(Ok(details)) => (DependencyStatus::Up, details),
+        Ok(Err(e)) => (DependencyStatus::Down, e.to_string()),
+        Err(_) => (
+            DependencyStatus::Down,
+            format!("timed out after {}s", HEALTH_CHECK_TIMEOUT_SECS),
+        ),
+    };
+
+    if status == DependencyStatus::Down {
+        warn!("Readiness probe {} failed: {}", name, details);
+    }
+
+    DependencyCheck {
+        name: name.to_string(),
+        status,
+        latency_ms,
+        details,
+    }
+}
+
+#[utoipa::path(
+    params(AnomaliesQuery),
+    responses(
+        (status = 200, description = "Most recent anomalies detected between pool snapshots, newest first", body = Vec<PoolAnomaly>),
+    )
+)]
+#[get("/anomalies")]
+async fn get_anomalies_service(
+    app_state: web::Data<AppState>,
+    query: web::Query<AnomaliesQuery>,
+) -> impl Responder {
+    let address = query.address.as_ref().map(|address| address.to_lowercase());
+    HttpResponse::Ok().json(app_state.anomalies.recent(address.as_deref()))
+}
+
+#[utoipa::path(
+    request_body = AnalyticsQuery,
+    responses(
+        (status = 200, description = "Requested metrics of every pool, computed from the recorded price samples", body = Vec<PoolAnalytics>),
+        (status = 400, description = "Empty query", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[post("/analytics/query")]
+async fn post_analytics_query_service(
+    app_state: web::Data<AppState>,
+    body: web::Json<AnalyticsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let AnalyticsQuery { pools, metrics } = body.into_inner();
+
+    if pools.is_empty() || metrics.is_empty() {
+        return Err(ApiError::bad_request(
+            "At least one pool and one metric are required",
+        ));
+    }
+
+    let addresses: Vec<String> = pools.iter().map(|address| address.to_lowercase()).collect();
+    if let Some(address) = addresses
+        .iter()
+        .find(|address| !app_state.pools.contains_key(*address))
+    {
+        return Err(ApiError::not_found(format!("Pool not found: {}", address)));
+    }
+
+    let now = unix_timestamp();
+    let analytics: Vec<PoolAnalytics> = addresses
+        .iter()
+        .map(|address| app_state.price_history.compute(address, &metrics, now))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(analytics))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text exposition format", body = String, content_type = "text/plain"),
+    )
+)]
+#[get("/metrics")]
+async fn get_metrics_service(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(app_state.metrics.render())
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Prometheus alerting rules file matching the application thresholds", body = String, content_type = "application/yaml"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/alerts/prometheus-rules")]
+async fn get_admin_prometheus_rules_service(_admin: AdminKey) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/yaml")
+        .body(core::alerts::render_prometheus_rules(
+            &core::alerts::alert_rules(),
+        ))
+}
+
+#[utoipa::path(
+    params(PoolsQuery),
+    responses(
+        (status = 200, description = "Pools", body = Paginated<Pool>),
     )
 )]
 #[get("/pools")]
-async fn get_pools_service(app_state: web::Data<AppState>) -> impl Responder {
-    let pools_map = &app_state.pools;
-    let pools: Vec<Pool> = pools_map
+async fn get_pools_service(
+    app_state: web::Data<AppState>,
+    query: web::Query<PoolsQuery>,
+) -> impl Responder {
+    let query = query.into_inner();
+
+    let mut pools: Vec<Pool> = app_state
+        .pools
         .iter()
         .map(|entry| entry.value().clone())
+        .filter(|pool| {
+            query
+                .dex_type
+                .as_ref()
+                .is_none_or(|dex_type| &pool.dex_type == dex_type)
+        })
+        .filter(|pool| {
+            query
+                .token
+                .as_ref()
+                .is_none_or(|token| pool.token0.matches(token) || pool.token1.matches(token))
+        })
+        .collect();
+
+    // Sort by address by default so pages are stable across requests
+    let order = query.order.unwrap_or_default();
+    pools.sort_by(|a, b| {
+        let ordering = match query.sort_by {
+            Some(PoolSortField::Fee) => a.fee.total_cmp(&b.fee),
+            Some(PoolSortField::Price0) => a.price0.total_cmp(&b.price0),
+            Some(PoolSortField::Price1) => a.price1.total_cmp(&b.price1),
+            None => a.address.cmp(&b.address),
+        };
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let total = pools.len();
+
+    let items = pools
+        .into_iter()
+        .skip((page - 1).saturating_mul(limit))
+        .take(limit)
+        .map(|mut pool| {
+            pool.annotation = app_state.annotations.pool(&pool.address.to_lowercase());
+            pool
+        })
         .collect();
-    HttpResponse::Ok().json(pools)
+
+    HttpResponse::Ok().json(Paginated {
+        items,
+        total,
+        page,
+        limit,
+    })
+}
+
+#[utoipa::path(
+    params(
+        ("symbol_or_address" = String, Path, description = "Token symbol or address, case insensitive"),
+    ),
+    responses(
+        (status = 200, description = "Tracked pools per matching token, several tokens can share a symbol", body = Vec<TokenPools>),
+        (status = 404, description = "No tracked pool contains this token", body = ApiError),
+    )
+)]
+#[get("/pools/by-token/{symbol_or_address}")]
+async fn get_pools_by_token_service(
+    app_state: web::Data<AppState>,
+    symbol_or_address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let symbol_or_address = symbol_or_address.into_inner();
+
+    // Group by token address, as different tokens can use the same symbol
+    let mut by_token: Vec<TokenPools> = Vec::new();
+    for entry in app_state.pools.iter() {
+        let pool = entry.value();
+        for token in [&pool.token0, &pool.token1] {
+            if !token.matches(&symbol_or_address) {
+                continue;
+            }
+            match by_token
+                .iter_mut()
+                .find(|group| group.token.address.eq_ignore_ascii_case(&token.address))
+            {
+                Some(group) => group.pools.push(pool.clone()),
+                None => by_token.push(TokenPools {
+                    token: token.clone(),
+                    pools: vec![pool.clone()],
+                }),
+            }
+        }
+    }
+
+    if by_token.is_empty() {
+        return Err(ApiError::not_found("No tracked pool contains this token"));
+    }
+
+    for group in &mut by_token {
+        group.pools.sort_by(|a, b| a.address.cmp(&b.address));
+    }
+
+    Ok(HttpResponse::Ok().json(by_token))
+}
+
+#[utoipa::path(
+    request_body = RegisterPoolRequest,
+    responses(
+        (status = 201, description = "Pool registered", body = Pool),
+        (status = 400, description = "Invalid pool address", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 409, description = "Pool already tracked", body = ApiError),
+        (status = 502, description = "Failed to fetch the pool details", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/pools")]
+async fn post_pools_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<RegisterPoolRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let RegisterPoolRequest { address, dex_type } = body.into_inner();
+
+    if Address::from_str(&address).is_err() {
+        return Err(ApiError::bad_request("Invalid pool address"));
+    }
+
+    if app_state.pools.contains_key(&address) {
+        return Err(ApiError::conflict("Pool already tracked"));
+    }
+
+    match core::pools::fetch_pool_blockchain_details(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        &address,
+        &dex_type,
+    )
+    .await
+    {
+        // Another request may have registered the same pool while we were fetching it
+        Ok(pool) => {
+            if app_state.track_pool(address.clone(), pool.clone()) {
+                info!("{} registered pool {}", user.username, address);
+                Ok(HttpResponse::Created().json(pool))
+            } else {
+                Err(ApiError::conflict("Pool already tracked"))
+            }
+        }
+        Err(e) => {
+            error!("Failed to fetch details of pool {}: {}", address, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch pool details: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "Pool removed", body = Pool),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[delete("/pools/{address}")]
+async fn delete_pool_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    match app_state.untrack_pool(&address) {
+        Some(pool) => {
+            info!("{} removed pool {}", user.username, address);
+            Ok(HttpResponse::Ok().json(pool))
+        }
+        None => Err(ApiError::not_found("Pool not found")),
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "Strategy of the `[pools.strategy]` table of the pool", body = PoolStrategy),
+        (status = 404, description = "Pool not found or without strategy", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/strategy")]
+async fn get_pool_strategy_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    if !app_state.pools.contains_key(&address) {
+        return Err(ApiError::not_found("Pool not found"));
+    }
+
+    match app_state.strategies.get(&address) {
+        Some(strategy) => Ok(HttpResponse::Ok().json(strategy.value())),
+        None => Err(ApiError::not_found("No strategy configured for this pool")),
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "What the strategy would do right now with each position of the pool held by the signer wallet, nothing is executed", body = StrategyDryRun),
+        (status = 404, description = "Pool not found or without strategy", body = ApiError),
+        (status = 502, description = "Failed to fetch the positions", body = ApiError),
+    )
+)]
+#[post("/pool/{address}/strategy/dry-run")]
+async fn post_pool_strategy_dry_run_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    let Some(pool) = app_state
+        .pools
+        .get(&address)
+        .map(|entry| entry.value().clone())
+    else {
+        return Err(ApiError::not_found("Pool not found"));
+    };
+    let Some(strategy) = app_state
+        .strategies
+        .get(&address)
+        .map(|entry| entry.value().clone())
+    else {
+        return Err(ApiError::not_found("No strategy configured for this pool"));
+    };
+
+    // Only the positions of the signer can be rebalanced
+    let signer = app_state.evm_provider.default_signer_address().to_string();
+    let positions: Vec<_> = match core::positions::fetch_positions(&app_state).await {
+        Ok(positions) => positions
+            .into_iter()
+            .filter(|position| {
+                position
+                    .pool
+                    .as_deref()
+                    .is_some_and(|pool| pool.eq_ignore_ascii_case(&address))
+                    && position.owner.eq_ignore_ascii_case(&signer)
+            })
+            .collect(),
+        Err(e) => {
+            error!("Failed to fetch positions: {:#}", e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch positions: {:#}",
+                e
+            )));
+        }
+    };
+    let gas_price =
+        match core::gas::fetch_gas_price(&app_state.evm_provider, &app_state.metrics).await {
+            Ok(gas_price) => Some(gas_price),
+            Err(e) => {
+                warn!("Failed to fetch the gas price: {:#}", e);
+                None
+            }
+        };
+
+    match core::strategy::dry_run(&pool, &strategy, &positions, gas_price) {
+        Ok(dry_run) => Ok(HttpResponse::Ok().json(dry_run)),
+        Err(e) => Err(ApiError::internal(format!(
+            "Failed to run the strategy: {:#}",
+            e
+        ))),
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    request_body = AnnotationUpdate,
+    responses(
+        (status = 200, description = "Labels and note of the pool after the update", body = Annotation),
+        (status = 400, description = "Too many or too long labels, or note too long", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 500, description = "Failed to persist the annotation", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/pools/{address}")]
+async fn patch_pool_service(
+    app_state: web::Data<AppState>,
+    _user: AuthenticatedUser,
+    address: web::Path<String>,
+    body: web::Json<AnnotationUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+    let update = body.into_inner();
+
+    if !app_state.pools.contains_key(&address) {
+        return Err(ApiError::not_found("Pool not found"));
+    }
+    if let Err(e) = update.validate() {
+        return Err(ApiError::bad_request(format!("{:#}", e)));
+    }
+
+    match app_state.annotations.update_pool(&address, update) {
+        Ok(annotation) => Ok(HttpResponse::Ok().json(annotation.unwrap_or_default())),
+        Err(e) => Err(ApiError::internal(format!(
+            "Failed to save the annotation: {:#}",
+            e
+        ))),
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        ChartQuery,
+    ),
+    responses(
+        (status = 200, description = "Price candles built from the recorded pool samples, oldest first", body = Vec<Candle>),
+        (status = 400, description = "Unsupported quote or invalid interval", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/chart")]
+async fn get_pool_chart_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<ChartQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    if !app_state.pools.contains_key(&address) {
+        return Err(ApiError::not_found("Pool not found"));
+    }
+
+    // There is no USD price source, only the pool price itself
+    if matches!(query.quote, Some(ChartQuote::Usd)) {
+        return Err(ApiError::bad_request("USD quotes are not supported"));
+    }
+
+    let interval_secs = query.interval_secs.unwrap_or(DEFAULT_CANDLE_INTERVAL_SECS);
+    if interval_secs < MIN_CANDLE_INTERVAL_SECS {
+        return Err(ApiError::bad_request(format!(
+            "interval_secs must be at least {}",
+            MIN_CANDLE_INTERVAL_SECS
+        )));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_CANDLE_LIMIT)
+        .clamp(1, MAX_CANDLE_LIMIT);
+
+    Ok(HttpResponse::Ok().json(app_state.price_history.candles(
+        &address,
+        query.side.unwrap_or_default(),
+        interval_secs,
+        limit,
+    )))
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        AprQuery,
+    ),
+    responses(
+        (status = 200, description = "Fee APR estimated from the recent swaps of the pool", body = FeeAprEstimate),
+        (status = 400, description = "Invalid width or lookback", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 429, description = "Too many swap history requests", body = ApiError),
+        (status = 502, description = "Failed to fetch the swaps", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/apr")]
+async fn get_pool_apr_service(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<AprQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    let Some(pool) = app_state.pools.get(&address).map(|pool| pool.clone()) else {
+        return Err(ApiError::not_found("Pool not found"));
+    };
+
+    let client = middleware::rate_limit_client(&req);
+    if let Err(retry_after) = app_state.swap_history_limiter.check(&client) {
+        return Ok(middleware::too_many_requests(
+            "Too many swap history requests",
+            retry_after,
+        ));
+    }
+
+    let width = query
+        .width
+        .unwrap_or_else(|| app_state.range_tuner.width(&address, &pool));
+    if !(1..=MAX_RANGE_WIDTH).contains(&width) {
+        return Err(ApiError::bad_request(format!(
+            "Width must be between 1 and {} ticks",
+            MAX_RANGE_WIDTH
+        )));
+    }
+    let lookback_secs = query.lookback_secs.unwrap_or(DEFAULT_SWAP_LOOKBACK_SECS);
+    if lookback_secs == 0 || lookback_secs > MAX_SWAP_LOOKBACK_SECS {
+        return Err(ApiError::bad_request(format!(
+            "Lookback must be between 1 and {} seconds",
+            MAX_SWAP_LOOKBACK_SECS
+        )));
+    }
+
+    match core::swaps::fetch_swaps(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        &pool,
+        lookback_secs,
+    )
+    .await
+    {
+        Ok(window) => Ok(HttpResponse::Ok().json(FeeAprEstimate::new(&pool, &window, width))),
+        Err(e) => {
+            error!("Failed to fetch swaps of pool {}: {}", address, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch swaps: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        ProfitabilityQuery,
+    ),
+    responses(
+        (status = 200, description = "Fee income, impermanent loss, slippage and gas of the executions on the pool positions over the period", body = PoolProfitability),
+        (status = 400, description = "Period ending before it starts", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 502, description = "Failed to fetch the open positions", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/profitability")]
+async fn get_pool_profitability_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<ProfitabilityQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    let Some(pool) = app_state.pools.get(&address).map(|pool| pool.clone()) else {
+        return Err(ApiError::not_found("Pool not found"));
+    };
+
+    let now = unix_timestamp();
+    let executions = app_state.executions.for_pool(&address);
+    let from = query
+        .from
+        .or_else(|| executions.first().map(|execution| execution.timestamp))
+        .unwrap_or(now);
+    let to = query.to.unwrap_or(now);
+    if to < from {
+        return Err(ApiError::bad_request("The period must end after it starts"));
+    }
+
+    // The uncollected fees are only known now, they belong to periods ending now
+    let open_positions = if to >= now {
+        match core::positions::fetch_positions(&app_state).await {
+            Ok(positions) => positions
+                .into_iter()
+                .filter(|position| {
+                    position
+                        .pool
+                        .as_ref()
+                        .is_some_and(|pool| pool.eq_ignore_ascii_case(&address))
+                })
+                .collect(),
+            Err(e) => {
+                error!("Failed to fetch positions: {}", e);
+                return Err(ApiError::bad_gateway(format!(
+                    "Failed to fetch positions: {}",
+                    e
+                )));
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    Ok(HttpResponse::Ok().json(PoolProfitability::new(
+        &pool,
+        &executions,
+        &open_positions,
+        CONFIG.get().toml.addresses.wrapped_native,
+        from,
+        to,
+    )))
+}
+
+#[utoipa::path(
+    params(QuotesQuery),
+    responses(
+        (status = 200, description = "Output and price impact of selling the amount in each tracked pool of the token, grouped by bought token with the best output first, and the pools that couldn't be read. The swap is computed within the current liquidity of each pool", body = Quotes),
+        (status = 400, description = "Invalid amount or token", body = ApiError),
+        (status = 502, description = "Failed to read the pools", body = ApiError),
+    )
+)]
+#[get("/quotes")]
+async fn get_quotes_service(
+    app_state: web::Data<AppState>,
+    query: web::Query<QuotesQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token) = Address::from_str(&query.token) else {
+        return Err(ApiError::bad_request("Invalid token address"));
+    };
+    let Some(amount) = query
+        .amount
+        .parse::<u128>()
+        .ok()
+        .filter(|amount| *amount > 0)
+    else {
+        return Err(ApiError::bad_request("Amount must be a positive integer"));
+    };
+
+    match core::quotes::quote_all(&app_state, token, amount).await {
+        Ok(quotes) => Ok(HttpResponse::Ok().json(quotes)),
+        Err(e) => {
+            error!("Failed to quote {} of {}: {:#}", amount, token, e);
+            Err(ApiError::bad_gateway(format!("Failed to quote: {:#}", e)))
+        }
+    }
+}
+
+#[utoipa::path(
+    request_body = InvestRequest,
+    responses(
+        (status = 200, description = "Plan investing the amount in the tracked pool where the swap into the other token has the lowest price impact. Nothing is executed: the signer wallet gives the `approvals`, does the `swap`, then sends the `mint` request to `POST /positions` as the confirm step", body = InvestPlan),
+        (status = 400, description = "Invalid amount, token or slippage", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "No tracked pool of the token matches the request", body = ApiError),
+        (status = 502, description = "Failed to read the pools", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/invest")]
+async fn post_invest_service(
+    app_state: web::Data<AppState>,
+    _user: AuthenticatedUser,
+    body: web::Json<InvestRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let request = body.into_inner();
+    let Ok(token) = Address::from_str(&request.token) else {
+        return Err(ApiError::bad_request("Invalid token address"));
+    };
+    let Some(amount) = request
+        .amount
+        .parse::<u128>()
+        .ok()
+        .filter(|amount| *amount > 1)
+    else {
+        return Err(ApiError::bad_request(
+            "Amount must be an integer greater than 1",
+        ));
+    };
+    if request
+        .max_slippage_percent
+        .is_some_and(|percent| !(0.0..100.0).contains(&percent))
+    {
+        return Err(ApiError::bad_request(
+            "max_slippage_percent must be at least 0 and below 100",
+        ));
+    }
+
+    match core::invest::plan(&app_state, &request, token, amount).await {
+        Ok(Some(plan)) => Ok(HttpResponse::Ok().json(plan)),
+        Ok(None) => Err(ApiError::not_found(
+            "No tracked pool with liquidity matches the request",
+        )),
+        Err(e) => {
+            error!("Failed to plan investing {} of {}: {:#}", amount, token, e);
+            Err(ApiError::bad_gateway(format!("Failed to plan: {:#}", e)))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        AsOfQuery,
+    ),
+    responses(
+        (status = 200, description = "State of the pool and of its managed positions at a past timestamp, from the price history, position snapshots and executions", body = PoolAsOf),
+        (status = 400, description = "Timestamp in the future", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/as-of")]
+async fn get_pool_as_of_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<AsOfQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    if !app_state.pools.contains_key(&address) {
+        return Err(ApiError::not_found("Pool not found"));
+    }
+    if query.timestamp > unix_timestamp() {
+        return Err(ApiError::bad_request("Timestamp must not be in the future"));
+    }
+
+    Ok(HttpResponse::Ok().json(core::as_of::pool_as_of(
+        &app_state,
+        &address,
+        query.timestamp,
+    )))
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        TickCrossingsQuery,
+    ),
+    responses(
+        (status = 200, description = "How often a range of the given width went out of range over the recent swaps", body = TickCrossings),
+        (status = 400, description = "Invalid width or lookback", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 429, description = "Too many swap history requests", body = ApiError),
+        (status = 502, description = "Failed to fetch the swaps", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/tick-crossings")]
+async fn get_pool_tick_crossings_service(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<TickCrossingsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    let Some(pool) = app_state.pools.get(&address).map(|pool| pool.clone()) else {
+        return Err(ApiError::not_found("Pool not found"));
+    };
+
+    let client = middleware::rate_limit_client(&req);
+    if let Err(retry_after) = app_state.swap_history_limiter.check(&client) {
+        return Ok(middleware::too_many_requests(
+            "Too many swap history requests",
+            retry_after,
+        ));
+    }
+
+    if !(1..=MAX_RANGE_WIDTH).contains(&query.width) {
+        return Err(ApiError::bad_request(format!(
+            "Width must be between 1 and {} ticks",
+            MAX_RANGE_WIDTH
+        )));
+    }
+    let lookback_secs = query.lookback_secs.unwrap_or(DEFAULT_SWAP_LOOKBACK_SECS);
+    if lookback_secs == 0 || lookback_secs > MAX_SWAP_LOOKBACK_SECS {
+        return Err(ApiError::bad_request(format!(
+            "Lookback must be between 1 and {} seconds",
+            MAX_SWAP_LOOKBACK_SECS
+        )));
+    }
+
+    match core::swaps::fetch_swaps(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        &pool,
+        lookback_secs,
+    )
+    .await
+    {
+        Ok(window) => Ok(HttpResponse::Ok().json(TickCrossings::new(&pool, &window, query.width))),
+        Err(e) => {
+            error!("Failed to fetch swaps of pool {}: {}", address, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch swaps: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "Default range width of the pool and its adjustment history", body = TunedRangeWidth),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/range-width")]
+async fn get_pool_range_width_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    match app_state.pools.get(&address) {
+        Some(pool) => {
+            Ok(HttpResponse::Ok().json(app_state.range_tuner.tuned_width(&address, &pool)))
+        }
+        None => Err(ApiError::not_found("Pool not found")),
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        LiquidityQuery,
+    ),
+    responses(
+        (status = 200, description = "Liquidity histogram around the current tick, from the initialized ticks of the pool", body = LiquidityDistribution),
+        (status = 400, description = "Invalid number of words", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 502, description = "Failed to read the ticks", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/liquidity")]
+async fn get_pool_liquidity_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<LiquidityQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    let Some(pool) = app_state.pools.get(&address).map(|pool| pool.clone()) else {
+        return Err(ApiError::not_found("Pool not found"));
+    };
+
+    let words = query.words.unwrap_or(DEFAULT_TICK_BITMAP_WORDS);
+    if !(0..=MAX_TICK_BITMAP_WORDS).contains(&words) {
+        return Err(ApiError::bad_request(format!(
+            "Words must be between 0 and {}",
+            MAX_TICK_BITMAP_WORDS
+        )));
+    }
+
+    match core::liquidity::fetch_liquidity_distribution(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        &pool,
+        words,
+    )
+    .await
+    {
+        Ok(distribution) => Ok(HttpResponse::Ok().json(distribution)),
+        Err(e) => {
+            error!("Failed to read the ticks of pool {}: {}", address, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to read ticks: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        VolatilityQuery,
+    ),
+    responses(
+        (status = 200, description = "Volatility statistics per candle duration, computed from the recorded pool samples", body = Vec<VolatilityStats>),
+        (status = 400, description = "Invalid windows", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/volatility")]
+async fn get_pool_volatility_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<VolatilityQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    if !app_state.pools.contains_key(&address) {
+        return Err(ApiError::not_found("Pool not found"));
+    }
+
+    let windows: Vec<u64> = match &query.windows {
+        Some(windows) => match windows
+            .split(',')
+            .map(|window| window.trim().parse::<u64>())
+            .collect::<Result<_, _>>()
+        {
+            Ok(windows) => windows,
+            Err(_) => return Err(ApiError::bad_request("Invalid windows")),
+        },
+        None => DEFAULT_VOLATILITY_WINDOWS_SECS.to_vec(),
+    };
+    if windows
+        .iter()
+        .any(|window| *window < MIN_CANDLE_INTERVAL_SECS)
+    {
+        return Err(ApiError::bad_request(format!(
+            "Windows must be at least {} seconds",
+            MIN_CANDLE_INTERVAL_SECS
+        )));
+    }
+    let periods = query
+        .periods
+        .unwrap_or(DEFAULT_VOLATILITY_PERIODS)
+        .clamp(2, MAX_CANDLE_LIMIT);
+    let side = query.side.unwrap_or_default();
+
+    let stats: Vec<VolatilityStats> = windows
+        .into_iter()
+        .map(|interval_secs| {
+            let candles = app_state
+                .price_history
+                .candles(&address, side, interval_secs, periods);
+            VolatilityStats::from_candles(&candles, interval_secs)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+        PoolQuery,
+    ),
+    responses(
+        (status = 200, description = "Pool", body = Pool),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 500, description = "Failed to refresh the pool", body = ApiError),
+    )
+)]
+#[get("/pool/{address}")]
+async fn get_pool_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<PoolQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    if !query.refresh.unwrap_or(false) {
+        return match app_state.pools.get(&address) {
+            Some(entry) => Ok(HttpResponse::Ok().json(entry.value())),
+            None => Err(ApiError::not_found("Pool not found")),
+        };
+    }
+
+    match core::pools::refresh_pool(&app_state, &address).await {
+        Ok(Some((_, pool))) => Ok(HttpResponse::Ok().json(pool)),
+        Ok(None) => Err(ApiError::not_found("Pool not found")),
+        Err(e) => {
+            error!("Failed to refresh pool {}: {}", address, e);
+            Err(ApiError::internal(format!("Failed to refresh pool: {}", e)))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "Pool state before and after the resync", body = PoolRefresh),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Pool not found", body = ApiError),
+        (status = 500, description = "Failed to refresh the pool", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/pool/{address}/refresh")]
+async fn post_pool_refresh_service(
+    app_state: web::Data<AppState>,
+    _user: AuthenticatedUser,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    match core::pools::refresh_pool(&app_state, &address).await {
+        Ok(Some((old_pool, new_pool))) => Ok(HttpResponse::Ok().json(PoolRefresh {
+            address,
+            previous: PoolPriceState::from(&old_pool),
+            current: PoolPriceState::from(&new_pool),
+        })),
+        Ok(None) => Err(ApiError::not_found("Pool not found")),
+        Err(e) => {
+            error!("Failed to refresh pool {}: {}", address, e);
+            Err(ApiError::internal(format!("Failed to refresh pool: {}", e)))
+        }
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Contracts the signer is allowed to send transactions to", body = Vec<String>),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+        (status = 500, description = "Invalid allowlist configuration", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/allowlist")]
+async fn get_admin_allowlist_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> Result<HttpResponse, ApiError> {
+    match app_state.tx_manager.allowlist() {
+        Ok(allowlist) => {
+            let mut addresses: Vec<String> = allowlist
+                .iter()
+                .map(|address| address.to_checksum(None))
+                .collect();
+            addresses.sort();
+            Ok(HttpResponse::Ok().json(addresses))
+        }
+        Err(e) => Err(ApiError::internal(format!("{:#}", e))),
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "State of the fail-safe switching the signer to read-only after repeated transaction failures", body = FailSafeState),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/tx/failsafe")]
+async fn get_admin_tx_failsafe_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> impl Responder {
+    HttpResponse::Ok().json(app_state.tx_manager.failsafe())
+}
+
+#[utoipa::path(
+    responses(
+        (status = 204, description = "Fail-safe reset, transactions are enabled again"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+        (status = 500, description = "Failed to persist the reset, the fail-safe stays tripped", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[post("/admin/tx/failsafe/reset")]
+async fn post_admin_tx_failsafe_reset_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> Result<HttpResponse, ApiError> {
+    match app_state.tx_manager.reset_failsafe() {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => {
+            error!("Failed to reset the transaction fail-safe: {:#}", e);
+            Err(ApiError::internal(format!(
+                "Failed to reset the fail-safe: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "On-chain verification that each configured pool is a pool of its declared dex_type", body = Vec<PoolVerification>),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+        (status = 502, description = "Failed to fetch the DEX factories", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/pools/verification")]
+async fn get_admin_pool_verification_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> Result<HttpResponse, ApiError> {
+    let config = CONFIG.get();
+
+    match core::pool_verification::verify_pools(&app_state, &config.toml.pools).await {
+        Ok(verifications) => {
+            for verification in &verifications {
+                if verification.status != PoolVerificationStatus::Verified {
+                    warn!(
+                        "Pool {} failed verification: {:?}",
+                        verification.address, verification.status
+                    );
+                }
+            }
+            Ok(HttpResponse::Ok().json(verifications))
+        }
+        Err(e) => Err(ApiError::bad_gateway(format!(
+            "Failed to fetch the DEX factories: {:#}",
+            e
+        ))),
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Effective configuration of the running instance, with the profile and reloads applied. Secrets are masked, the RPC urls are reduced to their origin and the API keys to a count per tier", body = Object),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/config")]
+async fn get_admin_config_service(_admin: AdminKey) -> impl Responder {
+    HttpResponse::Ok().json(CONFIG.get().as_ref())
+}
+
+#[utoipa::path(
+    responses(
+        (status = 204, description = "Config reloaded, the pools added to or removed from the toml file are tracked or untracked"),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+        (status = 500, description = "Failed to reload the config, the current one is kept", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[post("/admin/config/reload")]
+async fn post_admin_config_reload_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> Result<HttpResponse, ApiError> {
+    match core::config_watch::reload_config(&app_state).await {
+        Ok(()) => Ok(HttpResponse::NoContent().finish()),
+        Err(e) => {
+            error!("Failed to reload config: {:#}", e);
+            Err(ApiError::internal(format!(
+                "Failed to reload config: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Snapshots taken of the managed positions that had none, opened before the snapshots were recorded", body = Vec<PositionSnapshot>),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+        (status = 502, description = "Failed to snapshot the positions", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[post("/admin/snapshots/backfill")]
+async fn post_admin_snapshots_backfill_service(
+    app_state: web::Data<AppState>,
+    _admin: AdminKey,
+) -> Result<HttpResponse, ApiError> {
+    match core::positions::backfill_snapshots(&app_state).await {
+        Ok(snapshots) => {
+            info!("Backfilled the snapshots of {} positions", snapshots.len());
+            Ok(HttpResponse::Ok().json(snapshots))
+        }
+        Err(e) => {
+            error!("Failed to backfill position snapshots: {:#}", e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to backfill position snapshots: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Sampling rate (keep 1 in N events) of each module, warnings and errors are always kept", body = BTreeMap<String, u64>),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[get("/admin/log-sampling")]
+async fn get_admin_log_sampling_service(_admin: AdminKey) -> impl Responder {
+    HttpResponse::Ok().json(LOG_SAMPLER.rates())
+}
+
+#[utoipa::path(
+    request_body = BTreeMap<String, u64>,
+    responses(
+        (status = 200, description = "Sampling rates replaced, until the next config reload", body = BTreeMap<String, u64>),
+        (status = 400, description = "Rates must be at least 1", body = ApiError),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+        (status = 403, description = "Not an admin API key", body = ApiError),
+    ),
+    security(("admin_key" = []))
+)]
+#[put("/admin/log-sampling")]
+async fn put_admin_log_sampling_service(
+    _admin: AdminKey,
+    body: web::Json<BTreeMap<String, u64>>,
+) -> Result<HttpResponse, ApiError> {
+    let rates = body.into_inner();
+    if rates.values().any(|rate| *rate == 0) {
+        return Err(ApiError::bad_request("Rates must be at least 1"));
+    }
+
+    info!("Log sampling rates set to {:?}", rates);
+    LOG_SAMPLER.set_rates(rates);
+    Ok(HttpResponse::Ok().json(LOG_SAMPLER.rates()))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Current gas price and its percentiles over the sampling window", body = GasResponse),
+        (status = 502, description = "Failed to fetch the gas price", body = ApiError),
+    )
+)]
+#[get("/chain/gas")]
+async fn get_gas_service(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    match core::gas::fetch_gas_price(&app_state.evm_provider, &app_state.metrics).await {
+        Ok(gas_price) => Ok(HttpResponse::Ok().json(GasResponse {
+            gas_price,
+            percentiles: app_state.gas.percentiles(),
+        })),
+        Err(e) => {
+            error!("Failed to fetch gas price: {}", e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch gas price: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(BlockTimeQuery),
+    responses(
+        (status = 200, description = "Current block time estimate", body = BlockTimeResponse),
+        (status = 502, description = "Failed to read the chain headers", body = ApiError),
+    )
+)]
+#[get("/chain/block-time")]
+async fn get_block_time_service(
+    app_state: web::Data<AppState>,
+    query: web::Query<BlockTimeQuery>,
+) -> Result<HttpResponse, ApiError> {
+    match core::block_time::estimate_block_time(&app_state.evm_provider, &app_state.metrics).await {
+        Ok(estimate) => {
+            let deadline = query.within_secs.map(|secs| estimate.deadline(secs));
+            Ok(HttpResponse::Ok().json(BlockTimeResponse { estimate, deadline }))
+        }
+        Err(e) => {
+            error!("Failed to estimate block time: {}", e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to estimate block time: {}",
+                e
+            )))
+        }
+    }
 }
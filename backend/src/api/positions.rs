@@ -0,0 +1,533 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::{HttpResponse, delete, get, patch, post, web};
+use alloy::{
+    primitives::{Address, U256},
+    providers::WalletProvider,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+use utoipa::IntoParams;
+
+use crate::{
+    api::{auth::AuthenticatedUser, error::ApiError},
+    config::{CONFIG, MAX_GAS_WAIT_SECS},
+    core::{
+        self,
+        annotations::{Annotation, AnnotationUpdate},
+        snapshots::PositionSnapshot,
+        wallets::WalletAnalysis,
+    },
+    state::AppState,
+    types::{
+        ClosedPosition, CollectedFees, MintPositionRequest, MintedPosition, NftMetadata, Position,
+        RebalancePositionRequest, RebalancedPosition,
+    },
+};
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "LP positions held by the Yield contract and the signer wallet", body = Vec<Position>),
+        (status = 502, description = "Failed to fetch the positions", body = ApiError),
+    )
+)]
+#[get("/positions")]
+async fn get_positions_service(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    match core::positions::fetch_positions(&app_state).await {
+        Ok(positions) => Ok(HttpResponse::Ok().json(positions)),
+        Err(e) => {
+            error!("Failed to fetch positions: {}", e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch positions: {}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Wallet address"),
+    ),
+    responses(
+        (status = 200, description = "LP positions of a wallet the backend doesn't manage, classified with onboarding recommendations", body = WalletAnalysis),
+        (status = 400, description = "Invalid wallet address", body = ApiError),
+        (status = 502, description = "Failed to fetch the positions", body = ApiError),
+    )
+)]
+#[get("/wallets/{address}/positions")]
+async fn get_wallet_positions_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(owner) = Address::from_str(&address) else {
+        return Err(ApiError::bad_request("Invalid wallet address"));
+    };
+
+    match core::wallets::analyze_wallet(&app_state, owner).await {
+        Ok(analysis) => Ok(HttpResponse::Ok().json(analysis)),
+        Err(e) => {
+            error!("Failed to fetch the positions of wallet {}: {:#}", owner, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to fetch positions: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    request_body = MintPositionRequest,
+    responses(
+        (status = 201, description = "Position minted", body = MintedPosition),
+        (status = 400, description = "Invalid tick range or amounts", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Pool not tracked", body = ApiError),
+        (status = 502, description = "Failed to mint the position", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/positions")]
+async fn post_positions_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<MintPositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let request = body.into_inner();
+
+    let Some(pool) = app_state
+        .pools
+        .get(&request.pool)
+        .map(|entry| entry.value().clone())
+    else {
+        return Err(ApiError::not_found("Pool not tracked"));
+    };
+
+    // The signer holds the position, the Yield contract only moves it from the signer
+    let signer = app_state.evm_provider.default_signer_address();
+    let params = match core::positions::build_mint_params(&pool, &request, signer) {
+        Ok(params) => params,
+        Err(e) => return Err(ApiError::bad_request(format!("{:#}", e))),
+    };
+
+    match core::positions::mint_position(&app_state, &pool, params).await {
+        Ok(minted) => {
+            info!(
+                "{} minted position {} on pool {}",
+                user.username, minted.token_id, pool.address
+            );
+            Ok(HttpResponse::Created().json(minted))
+        }
+        Err(e) => {
+            error!("Failed to mint position on pool {}: {:#}", pool.address, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to mint position: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("id" = String, Path, description = "Token ID of the position"),
+    ),
+    request_body = RebalancePositionRequest,
+    responses(
+        (status = 200, description = "Position closed and reopened in the new range", body = RebalancedPosition),
+        (status = 400, description = "Invalid token ID, tick range or swap", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Position not found", body = ApiError),
+        (status = 409, description = "Position not held by the signer wallet or pool not tracked", body = ApiError),
+        (status = 502, description = "Failed to rebalance the position", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/positions/{id}/rebalance")]
+async fn post_position_rebalance_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<String>,
+    body: web::Json<RebalancePositionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token_id) = U256::from_str_radix(&id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+
+    let position = match core::positions::fetch_position(&app_state, token_id).await {
+        Ok(Some(position)) => position,
+        Ok(None) => return Err(ApiError::not_found("Position not found")),
+        Err(e) => {
+            error!("Failed to fetch position {}: {}", token_id, e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch position: {}",
+                e
+            )));
+        }
+    };
+
+    // The Yield contract moves the position out of the wallet of the caller
+    let signer = app_state.evm_provider.default_signer_address();
+    if !position.owner.eq_ignore_ascii_case(&signer.to_string()) {
+        return Err(ApiError::conflict(
+            "Only the positions held by the signer wallet can be rebalanced",
+        ));
+    }
+
+    // The new range is validated against the tracked pool state
+    let Some(pool) = position
+        .pool
+        .as_ref()
+        .and_then(|address| app_state.pools.get(&address.to_lowercase()))
+        .map(|entry| entry.value().clone())
+    else {
+        return Err(ApiError::conflict(
+            "The pool of the position is not tracked",
+        ));
+    };
+
+    let params = match core::positions::build_rebalance_params(&position, &pool, &body) {
+        Ok(params) => params,
+        Err(e) => return Err(ApiError::bad_request(format!("{:#}", e))),
+    };
+
+    match core::positions::rebalance_position(&app_state, &position, params).await {
+        Ok(rebalanced) => {
+            info!(
+                "{} rebalanced position {} into {}",
+                user.username, rebalanced.old_token_id, rebalanced.new_token_id
+            );
+            Ok(HttpResponse::Ok().json(rebalanced))
+        }
+        Err(e) => {
+            error!("Failed to rebalance position {}: {:#}", token_id, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to rebalance position: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CollectQuery {
+    /// Wait up to this many seconds for the gas price to drop below its daily median
+    pub max_gas_wait_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    params(
+        ("id" = String, Path, description = "Token ID of the position"),
+        CollectQuery,
+    ),
+    responses(
+        (status = 200, description = "Fees collected to the signer wallet", body = CollectedFees),
+        (status = 400, description = "Invalid token ID", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Position not found", body = ApiError),
+        (status = 409, description = "Position not held by the signer wallet", body = ApiError),
+        (status = 502, description = "Failed to collect the fees", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[post("/positions/{id}/collect")]
+async fn post_position_collect_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<String>,
+    query: web::Query<CollectQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token_id) = U256::from_str_radix(&id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+
+    let position = match core::positions::fetch_position(&app_state, token_id).await {
+        Ok(Some(position)) => position,
+        Ok(None) => return Err(ApiError::not_found("Position not found")),
+        Err(e) => {
+            error!("Failed to fetch position {}: {}", token_id, e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch position: {}",
+                e
+            )));
+        }
+    };
+
+    // The signer collects through the position manager directly, checked before waiting
+    // for the gas. The Yield contract collects the fees of its positions on rebalance or
+    // removal.
+    let signer = app_state.evm_provider.default_signer_address();
+    if !position.owner.eq_ignore_ascii_case(&signer.to_string()) {
+        return Err(ApiError::conflict(
+            "Only the fees of the positions held by the signer wallet can be collected",
+        ));
+    }
+
+    // Collecting is not urgent, it can wait for cheaper gas
+    if let Some(max_wait_secs) = query.max_gas_wait_secs {
+        let max_delay = Duration::from_secs(max_wait_secs.min(MAX_GAS_WAIT_SECS));
+        if let Err(e) = core::gas::wait_for_cheap_gas(&app_state, max_delay).await {
+            error!("Failed to check the gas price: {}", e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to check the gas price: {}",
+                e
+            )));
+        }
+    }
+
+    match core::positions::collect_fees(&app_state, &position).await {
+        Ok(collected) => {
+            info!(
+                "{} collected the fees of position {}",
+                user.username, collected.token_id
+            );
+            Ok(HttpResponse::Ok().json(collected))
+        }
+        Err(e) => {
+            error!("Failed to collect fees of position {}: {:#}", token_id, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to collect fees: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct NftQuery {
+    /// Return the decoded SVG image itself instead of the metadata
+    pub image: Option<bool>,
+}
+
+#[utoipa::path(
+    params(
+        ("token_id" = String, Path, description = "Token ID of the position"),
+        NftQuery,
+    ),
+    responses(
+        (status = 200, description = "Position NFT metadata, or its SVG image with `?image=true`", body = NftMetadata),
+        (status = 400, description = "Invalid token ID", body = ApiError),
+        (status = 404, description = "Position or SVG image not found", body = ApiError),
+        (status = 502, description = "Failed to fetch the metadata", body = ApiError),
+    )
+)]
+#[get("/positions/{token_id}/nft")]
+async fn get_position_nft_service(
+    app_state: web::Data<AppState>,
+    token_id: web::Path<String>,
+    query: web::Query<NftQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token_id) = U256::from_str_radix(&token_id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+
+    let metadata = match core::positions::fetch_nft_metadata(&app_state, token_id).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return Err(ApiError::not_found("Position not found")),
+        Err(e) => {
+            error!(
+                "Failed to fetch NFT metadata of position {}: {:#}",
+                token_id, e
+            );
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch NFT metadata: {:#}",
+                e
+            )));
+        }
+    };
+
+    if query.image.unwrap_or(false) {
+        return match metadata.svg {
+            Some(svg) => Ok(HttpResponse::Ok().content_type("image/svg+xml").body(svg)),
+            None => Err(ApiError::not_found("The NFT image is not an SVG data URI")),
+        };
+    }
+
+    Ok(HttpResponse::Ok().json(metadata))
+}
+
+#[utoipa::path(
+    params(
+        ("id" = String, Path, description = "Token ID of the position"),
+    ),
+    responses(
+        (status = 200, description = "Snapshots of the position taken before and after each execution, oldest first", body = Vec<PositionSnapshot>),
+        (status = 400, description = "Invalid token ID", body = ApiError),
+    )
+)]
+#[get("/positions/{id}/snapshots")]
+async fn get_position_snapshots_service(
+    app_state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    // Closed positions keep their snapshots, the position is not looked up on-chain
+    let Ok(token_id) = U256::from_str_radix(&id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+
+    Ok(HttpResponse::Ok().json(app_state.snapshots.for_position(&token_id.to_string())))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ClosePositionQuery {
+    /// Only simulate the close and return the amounts it would withdraw, defaults to
+    /// `app.dry_run` of the config
+    pub dry_run: Option<bool>,
+    /// Minimum amount of token0 to withdraw, in its smallest unit, defaults to 0
+    pub amount0_min: Option<String>,
+    /// Minimum amount of token1 to withdraw, in its smallest unit, defaults to 0
+    pub amount1_min: Option<String>,
+}
+
+#[utoipa::path(
+    params(
+        ("id" = String, Path, description = "Token ID of the position"),
+        ClosePositionQuery,
+    ),
+    responses(
+        (status = 200, description = "Position closed, or the simulated result with `?dry_run=true`", body = ClosedPosition),
+        (status = 400, description = "Invalid token ID or amounts", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Position not found", body = ApiError),
+        (status = 409, description = "Position not held by the signer wallet, or a dry run before the Yield contract is approved on the position manager", body = ApiError),
+        (status = 502, description = "Failed to close the position", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[delete("/positions/{id}")]
+async fn delete_position_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<String>,
+    query: web::Query<ClosePositionQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token_id) = U256::from_str_radix(&id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+    let amount0_min =
+        match core::positions::parse_optional_amount(query.amount0_min.as_deref(), "amount0_min") {
+            Ok(amount) => amount,
+            Err(e) => return Err(ApiError::bad_request(format!("{:#}", e))),
+        };
+    let amount1_min =
+        match core::positions::parse_optional_amount(query.amount1_min.as_deref(), "amount1_min") {
+            Ok(amount) => amount,
+            Err(e) => return Err(ApiError::bad_request(format!("{:#}", e))),
+        };
+    let dry_run = query.dry_run.unwrap_or(CONFIG.get().toml.app.dry_run);
+
+    let position = match core::positions::fetch_position(&app_state, token_id).await {
+        Ok(Some(position)) => position,
+        Ok(None) => return Err(ApiError::not_found("Position not found")),
+        Err(e) => {
+            error!("Failed to fetch position {}: {}", token_id, e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch position: {}",
+                e
+            )));
+        }
+    };
+
+    // The Yield contract moves the position out of the wallet of the caller
+    let signer = app_state.evm_provider.default_signer_address();
+    if !position.owner.eq_ignore_ascii_case(&signer.to_string()) {
+        return Err(ApiError::conflict(
+            "Only the positions held by the signer wallet can be closed",
+        ));
+    }
+
+    // The simulation reverts until the Yield contract may move the position
+    if dry_run {
+        match core::positions::positions_approved(&app_state, &position.dex_type).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(ApiError::conflict(
+                    "The Yield contract is not approved on the position manager yet, the first close or rebalance approves it",
+                ));
+            }
+            Err(e) => {
+                error!("Failed to check the position manager approval: {:#}", e);
+                return Err(ApiError::bad_gateway(format!(
+                    "Failed to check the position manager approval: {:#}",
+                    e
+                )));
+            }
+        }
+    }
+
+    match core::positions::close_position(&app_state, &position, amount0_min, amount1_min, dry_run)
+        .await
+    {
+        Ok(closed) => {
+            if !dry_run {
+                info!("{} closed position {}", user.username, closed.token_id);
+            }
+            Ok(HttpResponse::Ok().json(closed))
+        }
+        Err(e) => {
+            error!("Failed to close position {}: {:#}", token_id, e);
+            Err(ApiError::bad_gateway(format!(
+                "Failed to close position: {:#}",
+                e
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    params(
+        ("id" = String, Path, description = "Token ID of the position NFT"),
+    ),
+    request_body = AnnotationUpdate,
+    responses(
+        (status = 200, description = "Labels and note of the position after the update", body = Annotation),
+        (status = 400, description = "Invalid token ID, too many or too long labels, or note too long", body = ApiError),
+        (status = 401, description = "Missing or invalid token", body = ApiError),
+        (status = 404, description = "Position not found", body = ApiError),
+        (status = 500, description = "Failed to persist the annotation", body = ApiError),
+        (status = 502, description = "Failed to fetch the position", body = ApiError),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[patch("/positions/{id}")]
+async fn patch_position_service(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<String>,
+    body: web::Json<AnnotationUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    let Ok(token_id) = U256::from_str_radix(&id, 10) else {
+        return Err(ApiError::bad_request("Invalid token ID"));
+    };
+    let update = body.into_inner();
+    if let Err(e) = update.validate() {
+        return Err(ApiError::bad_request(format!("{:#}", e)));
+    }
+
+    match core::positions::fetch_position(&app_state, token_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ApiError::not_found("Position not found")),
+        Err(e) => {
+            error!("Failed to fetch position {}: {}", token_id, e);
+            return Err(ApiError::bad_gateway(format!(
+                "Failed to fetch position: {}",
+                e
+            )));
+        }
+    }
+
+    match app_state
+        .annotations
+        .update_position(&token_id.to_string(), update)
+    {
+        Ok(annotation) => {
+            info!("{} annotated position {}", user.username, token_id);
+            Ok(HttpResponse::Ok().json(annotation.unwrap_or_default()))
+        }
+        Err(e) => Err(ApiError::internal(format!(
+            "Failed to save the annotation: {:#}",
+            e
+        ))),
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::{HttpResponse, get, http::header, web, web::Bytes};
+use futures::stream::{self, StreamExt};
+use tokio::sync::broadcast;
+
+use crate::{
+    api::error::ApiError,
+    state::AppState,
+    types::{PoolEvent, PoolPriceState},
+};
+
+#[utoipa::path(
+    params(
+        ("address" = String, Path, description = "Pool address"),
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events stream of `updated` PoolEvent messages, starting with the current state", body = PoolEvent, content_type = "text/event-stream"),
+        (status = 404, description = "Pool not found", body = ApiError),
+    )
+)]
+#[get("/pool/{address}/stream")]
+async fn get_pool_stream_service(
+    app_state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let address = address.into_inner().to_lowercase();
+
+    // Subscribe before reading the current state so no update can be missed in between
+    let events = app_state.pool_events.subscribe();
+    let current = match app_state.pools.get(&address) {
+        Some(entry) => PoolEvent::Updated {
+            address: address.clone(),
+            state: PoolPriceState::from(entry.value()),
+        },
+        None => return Err(ApiError::not_found("Pool not found")),
+    };
+
+    let updates = stream::unfold((events, address), |(mut events, address)| async move {
+        loop {
+            match events.recv().await {
+                Ok(event @ PoolEvent::Updated { .. }) if event_address(&event) == address => {
+                    return Some((event, (events, address)));
+                }
+                // The stream ends once the pool stops being tracked
+                Ok(PoolEvent::Removed { address: removed }) if removed == address => return None,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let body = stream::once(async move { current })
+        .chain(updates)
+        .map(|event| Ok::<Bytes, actix_web::Error>(to_sse_message(&event)));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+        .streaming(body))
+}
+
+fn event_address(event: &PoolEvent) -> &str {
+    match event {
+        PoolEvent::Added { pool } => &pool.address,
+        PoolEvent::Updated { address, .. } | PoolEvent::Removed { address } => address,
+        PoolEvent::Anomaly { anomaly } => &anomaly.address,
+    }
+}
+
+/// Format a pool event as a single SSE message
+fn to_sse_message(event: &PoolEvent) -> Bytes {
+    let json = serde_json::to_string(event).expect("PoolEvent is always serializable");
+    Bytes::from(format!("data: {}\n\n", json))
+}
@@ -0,0 +1,364 @@
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{self, Codec, Frame, Message};
+use actix_web::{
+    Error, HttpRequest, HttpResponse,
+    body::{BodyStream, MessageBody},
+    get, rt, web,
+    web::Bytes,
+    web::BytesMut,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, warn};
+
+use crate::{
+    config::{
+        DEFAULT_CANDLE_INTERVAL_SECS, DEFAULT_CANDLE_LIMIT, MAX_CANDLE_LIMIT,
+        MAX_CANDLE_SUBSCRIPTIONS, MIN_CANDLE_INTERVAL_SECS,
+    },
+    core::analytics::Candle,
+    state::AppState,
+    types::{CandleFeedMessage, CandleFeedRequest, ChartSide, PoolEvent},
+};
+
+/// Number of outgoing messages buffered per WebSocket client
+const OUTGOING_BUFFER: usize = 32;
+
+#[utoipa::path(
+    responses(
+        (status = 101, description = "WebSocket streaming a PoolEvent JSON message for every pool change", body = PoolEvent),
+        (status = 400, description = "Not a WebSocket handshake", body = String),
+    )
+)]
+#[get("/ws/pools")]
+async fn get_pools_ws_service(
+    req: HttpRequest,
+    payload: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let response = ws::handshake(req.head())?;
+
+    // Subscribe before taking the snapshot so no change can be missed in between
+    let events = app_state.pool_events.subscribe();
+    let snapshot: Vec<PoolEvent> = app_state
+        .pools
+        .iter()
+        .map(|entry| PoolEvent::Added {
+            pool: entry.value().clone(),
+        })
+        .collect();
+
+    let (tx, rx) = mpsc::channel(OUTGOING_BUFFER);
+
+    rt::spawn(run_session(payload, events, snapshot, tx));
+
+    stream_messages(response, rx)
+}
+
+#[utoipa::path(
+    request_body = CandleFeedRequest,
+    responses(
+        (status = 101, description = "WebSocket feed of the locally built candles: send `subscribe` messages to receive a snapshot of a pool candles then every change of its latest candle", body = CandleFeedMessage),
+        (status = 400, description = "Not a WebSocket handshake", body = String),
+    )
+)]
+#[get("/ws/candles")]
+async fn get_candles_ws_service(
+    req: HttpRequest,
+    payload: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let response = ws::handshake(req.head())?;
+
+    let (tx, rx) = mpsc::channel(OUTGOING_BUFFER);
+
+    rt::spawn(run_candle_session(payload, app_state, tx));
+
+    stream_messages(response, rx)
+}
+
+/// Complete the handshake response with the stream of the messages sent to the client
+fn stream_messages(
+    mut response: actix_http::ResponseBuilder,
+    rx: mpsc::Receiver<Message>,
+) -> Result<HttpResponse, Error> {
+    let body = futures::stream::unfold((rx, Codec::new()), |(mut rx, mut codec)| async move {
+        let message = rx.recv().await?;
+        let mut buffer = BytesMut::new();
+        match codec.encode(message, &mut buffer) {
+            Ok(()) => Some((Ok::<Bytes, Error>(buffer.freeze()), (rx, codec))),
+            Err(e) => {
+                warn!("Failed to encode WebSocket message: {}", e);
+                None
+            }
+        }
+    });
+
+    Ok(response
+        .message_body(MessageBody::boxed(BodyStream::new(Box::pin(body))))?
+        .into())
+}
+
+/// Decode the frames received so far, answering the control frames of the client
+///
+/// # Returns:
+/// * The text messages received, or `None` once the session must end
+async fn read_frames(
+    codec: &mut Codec,
+    buffer: &mut BytesMut,
+    tx: &mpsc::Sender<Message>,
+) -> Option<Vec<String>> {
+    let mut texts = Vec::new();
+
+    loop {
+        match codec.decode(buffer) {
+            Ok(Some(Frame::Ping(bytes))) => {
+                tx.send(Message::Pong(bytes)).await.ok()?;
+            }
+            Ok(Some(Frame::Close(reason))) => {
+                let _ = tx.send(Message::Close(reason)).await;
+                return None;
+            }
+            Ok(Some(Frame::Text(bytes))) => match String::from_utf8(bytes.to_vec()) {
+                Ok(text) => texts.push(text),
+                Err(_) => {
+                    debug!("WebSocket text frame is not valid UTF-8");
+                    return None;
+                }
+            },
+            // Binary and continuation frames are not part of any protocol here
+            Ok(Some(_)) => {}
+            Ok(None) => return Some(texts),
+            Err(e) => {
+                debug!("WebSocket protocol error: {}", e);
+                return None;
+            }
+        }
+    }
+}
+
+/// Drive one WebSocket client: answer its control frames and forward pool events to it.
+///
+/// The client first receives an `added` event for every currently tracked pool, then the
+/// live changes. The session ends when either side closes the connection.
+async fn run_session(
+    mut payload: web::Payload,
+    mut events: broadcast::Receiver<PoolEvent>,
+    snapshot: Vec<PoolEvent>,
+    tx: mpsc::Sender<Message>,
+) {
+    for event in snapshot {
+        if send_json(&tx, &event).await.is_err() {
+            return;
+        }
+    }
+
+    let mut codec = Codec::new();
+    let mut buffer = BytesMut::new();
+
+    loop {
+        tokio::select! {
+            chunk = payload.next() => {
+                let Some(Ok(chunk)) = chunk else {
+                    return;
+                };
+                buffer.extend_from_slice(&chunk);
+
+                // This stream is server to client only, text messages are ignored
+                if read_frames(&mut codec, &mut buffer, &tx).await.is_none() {
+                    return;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if send_json(&tx, &event).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket client lagging behind, skipped {} pool events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+/// Candles of a pool a client subscribed to
+struct CandleSubscription {
+    pool: String,
+    side: ChartSide,
+    interval_secs: u64,
+    /// Latest candle sent to the client
+    last: Option<Candle>,
+}
+
+impl CandleSubscription {
+    fn matches(&self, pool: &str, side: ChartSide, interval_secs: u64) -> bool {
+        self.pool == pool && self.side == side && self.interval_secs == interval_secs
+    }
+}
+
+/// Drive one client of the candle feed: apply its subscriptions and send the changes of
+/// the candles it subscribed to
+///
+/// Only the latest candles are followed, samples backfilled into older candles are not
+/// sent again, a new subscription gets them in its snapshot.
+async fn run_candle_session(
+    mut payload: web::Payload,
+    app_state: web::Data<AppState>,
+    tx: mpsc::Sender<Message>,
+) {
+    let mut updates = app_state.price_history.subscribe();
+    let mut subscriptions: Vec<CandleSubscription> = Vec::new();
+
+    let mut codec = Codec::new();
+    let mut buffer = BytesMut::new();
+
+    loop {
+        tokio::select! {
+            chunk = payload.next() => {
+                let Some(Ok(chunk)) = chunk else {
+                    return;
+                };
+                buffer.extend_from_slice(&chunk);
+
+                let Some(texts) = read_frames(&mut codec, &mut buffer, &tx).await else {
+                    return;
+                };
+                for text in texts {
+                    let message = match serde_json::from_str(&text) {
+                        Ok(request) => handle_candle_request(&app_state, &mut subscriptions, request),
+                        Err(e) => CandleFeedMessage::Error {
+                            message: format!("Invalid request: {}", e),
+                        },
+                    };
+                    if send_json(&tx, &message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            update = updates.recv() => match update {
+                Ok(address) => {
+                    for subscription in subscriptions.iter_mut().filter(|s| s.pool == address) {
+                        for candle in app_state.price_history.candles(
+                            &subscription.pool,
+                            subscription.side,
+                            subscription.interval_secs,
+                            2,
+                        ) {
+                            let is_new = subscription.last.as_ref().is_none_or(|last| {
+                                candle.open_time > last.open_time
+                                    || (candle.open_time == last.open_time && candle != *last)
+                            });
+                            if !is_new {
+                                continue;
+                            }
+
+                            subscription.last = Some(candle.clone());
+                            let message = CandleFeedMessage::Candle {
+                                pool: subscription.pool.clone(),
+                                side: subscription.side,
+                                interval_secs: subscription.interval_secs,
+                                candle,
+                            };
+                            if send_json(&tx, &message).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Candle feed client lagging behind, skipped {} price updates", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+/// Apply a subscription change of a candle feed client
+///
+/// # Returns:
+/// * The message answering the request
+fn handle_candle_request(
+    app_state: &AppState,
+    subscriptions: &mut Vec<CandleSubscription>,
+    request: CandleFeedRequest,
+) -> CandleFeedMessage {
+    match request {
+        CandleFeedRequest::Subscribe {
+            pool,
+            side,
+            interval_secs,
+            limit,
+        } => {
+            let pool = pool.to_lowercase();
+            let interval_secs = interval_secs.unwrap_or(DEFAULT_CANDLE_INTERVAL_SECS);
+            if interval_secs < MIN_CANDLE_INTERVAL_SECS {
+                return CandleFeedMessage::Error {
+                    message: format!(
+                        "interval_secs must be at least {}",
+                        MIN_CANDLE_INTERVAL_SECS
+                    ),
+                };
+            }
+            if !app_state.pools.contains_key(&pool) {
+                return CandleFeedMessage::Error {
+                    message: format!("Pool {} is not tracked", pool),
+                };
+            }
+
+            subscriptions.retain(|s| !s.matches(&pool, side, interval_secs));
+            if subscriptions.len() >= MAX_CANDLE_SUBSCRIPTIONS {
+                return CandleFeedMessage::Error {
+                    message: format!("At most {} subscriptions", MAX_CANDLE_SUBSCRIPTIONS),
+                };
+            }
+
+            let limit = limit
+                .unwrap_or(DEFAULT_CANDLE_LIMIT)
+                .clamp(1, MAX_CANDLE_LIMIT);
+            let candles = app_state
+                .price_history
+                .candles(&pool, side, interval_secs, limit);
+            subscriptions.push(CandleSubscription {
+                pool: pool.clone(),
+                side,
+                interval_secs,
+                last: candles.last().cloned(),
+            });
+
+            CandleFeedMessage::Snapshot {
+                pool,
+                side,
+                interval_secs,
+                candles,
+            }
+        }
+        CandleFeedRequest::Unsubscribe {
+            pool,
+            side,
+            interval_secs,
+        } => {
+            let pool = pool.to_lowercase();
+            let interval_secs = interval_secs.unwrap_or(DEFAULT_CANDLE_INTERVAL_SECS);
+            subscriptions.retain(|s| !s.matches(&pool, side, interval_secs));
+
+            CandleFeedMessage::Unsubscribed {
+                pool,
+                side,
+                interval_secs,
+            }
+        }
+    }
+}
+
+async fn send_json(
+    tx: &mpsc::Sender<Message>,
+    message: &impl Serialize,
+) -> Result<(), mpsc::error::SendError<Message>> {
+    let json = serde_json::to_string(message).expect("WebSocket messages are always serializable");
+    tx.send(Message::Text(json.into())).await
+}
@@ -0,0 +1,68 @@
+use alloy::primitives::{Address, address};
+
+use super::registry::{AddressRegistry, DexAddresses};
+
+/// Same address on every chain
+const MULTICALL3: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
+/// PancakeSwap V3 is deployed at the same addresses on every mainnet
+const PANCAKESWAP_V3: DexAddresses = DexAddresses {
+    factory: Some(address!("0x0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865")),
+    position_manager: Some(address!("0x46A15B0b27311cedF172AB29E4f4766fbE7F4364")),
+    router: Some(address!("0x1b81D678ffb9C0263b24A97847620C99d213eB14")),
+};
+
+/// Built-in address registry of a supported chain, `None` for any other chain
+///
+/// The addresses of the `[addresses]` table are used over these ones, see
+/// `AddressRegistry::fill_from`.
+pub fn known_addresses(chain_id: u64) -> Option<AddressRegistry> {
+    let registry = match chain_id {
+        // Ethereum
+        1 => AddressRegistry {
+            wrapped_native: Some(address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")),
+            multicall: Some(MULTICALL3),
+            uniswap_v3: DexAddresses {
+                factory: Some(address!("0x1F98431c8aD98523631AE4a59f267346ea31F984")),
+                position_manager: Some(address!("0xC36442b4a4522E871399CD717aBDD847Ab11FE88")),
+                router: Some(address!("0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45")),
+            },
+            pancakeswap_v3: PANCAKESWAP_V3,
+        },
+        // BNB Smart Chain
+        56 => AddressRegistry {
+            wrapped_native: Some(address!("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c")),
+            multicall: Some(MULTICALL3),
+            uniswap_v3: DexAddresses {
+                factory: Some(address!("0xdB1d10011AD0Ff90774D0C6Bb92e5C5c8b4461F7")),
+                position_manager: Some(address!("0x7b8A01B39D58278b5DE7e48c8449c9f4F5170613")),
+                router: Some(address!("0xB971eF87ede563556b2ED4b1C0b0019111Dd85d2")),
+            },
+            pancakeswap_v3: PANCAKESWAP_V3,
+        },
+        // BNB Smart Chain testnet, Uniswap V3 isn't deployed there
+        97 => AddressRegistry {
+            wrapped_native: Some(address!("0xae13d989daC2f0dEbFf460aC112a837C89BAa7cd")),
+            multicall: Some(MULTICALL3),
+            uniswap_v3: DexAddresses::default(),
+            pancakeswap_v3: DexAddresses {
+                position_manager: Some(address!("0x427bF5b37357632377eCbEC9de3626C71A5396c1")),
+                ..PANCAKESWAP_V3
+            },
+        },
+        // Base
+        8453 => AddressRegistry {
+            wrapped_native: Some(address!("0x4200000000000000000000000000000000000006")),
+            multicall: Some(MULTICALL3),
+            uniswap_v3: DexAddresses {
+                factory: Some(address!("0x33128a8fC17869897dcE68Ed026d694621f6FDfD")),
+                position_manager: Some(address!("0x03a520b32C04BF3bEEf7BEb72E919cf822Ed34f1")),
+                router: Some(address!("0x2626664c2603336E57B271c5C0b26F421741e481")),
+            },
+            pancakeswap_v3: PANCAKESWAP_V3,
+        },
+        _ => return None,
+    };
+
+    Some(registry)
+}
@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, bail};
+use once_cell::sync::Lazy;
+
+const USAGE: &str = "Usage: yieldai [OPTIONS]
+
+Options:
+  --config <PATH>      Toml config file, overrides --chain and CONFIG_PATH
+  --chain <NAME>       Load the src/config/<NAME>.toml config file, overrides CONFIG_PATH
+  --port <PORT>        Port of the HTTP server, overrides PORT
+  --read-only[=BOOL]   Never send a transaction, overrides READ_ONLY
+  --log-level <LEVEL>  Log level of the yieldai logs or filter directives, overrides app.log_level
+  -h, --help           Print this help";
+
+/// Command line arguments, they take precedence over the environment
+pub static CLI_ARGS: Lazy<CliArgs> = Lazy::new(|| match CliArgs::parse(std::env::args().skip(1)) {
+    Ok(args) => args,
+    Err(e) => {
+        eprintln!("{:#}\n\n{}", e, USAGE);
+        std::process::exit(2);
+    }
+});
+
+#[derive(Debug, Default, Clone)]
+pub struct CliArgs {
+    pub config: Option<String>,
+    pub chain: Option<String>,
+    pub port: Option<u16>,
+    pub read_only: bool,
+    pub log_level: Option<String>,
+}
+
+impl CliArgs {
+    /// Parse `--name value`, `--name=value`, `--flag` and `--flag=true|false` arguments
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli_args = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .with_context(|| format!("{} requires a value", name))
+            };
+
+            match name.as_str() {
+                "--config" => cli_args.config = Some(value()?),
+                "--chain" => cli_args.chain = Some(value()?),
+                "--port" => {
+                    cli_args.port = Some(value()?.parse().context("--port must be a valid u16")?)
+                }
+                "--read-only" => {
+                    cli_args.read_only = match inline_value.as_deref() {
+                        None | Some("true" | "1") => true,
+                        Some("false" | "0") => false,
+                        Some(value) => bail!("--read-only must be true or false, not {}", value),
+                    }
+                }
+                "--log-level" => cli_args.log_level = Some(value()?),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => bail!("Unknown argument: {}", name),
+            }
+        }
+
+        Ok(cli_args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs> {
+        CliArgs::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_separate_and_inline_values() {
+        let args = parse(&["--chain", "base", "--port=9000"]).unwrap();
+        assert_eq!(args.chain.as_deref(), Some("base"));
+        assert_eq!(args.port, Some(9000));
+    }
+
+    #[test]
+    fn parses_the_read_only_flag() {
+        assert!(!parse(&[]).unwrap().read_only);
+        assert!(parse(&["--read-only"]).unwrap().read_only);
+        assert!(parse(&["--read-only=true"]).unwrap().read_only);
+        assert!(!parse(&["--read-only=false"]).unwrap().read_only);
+        assert!(!parse(&["--read-only=0"]).unwrap().read_only);
+        assert!(parse(&["--read-only=yes"]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_values_and_unknown_arguments() {
+        assert!(parse(&["--port"]).is_err());
+        assert!(parse(&["--port=http"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
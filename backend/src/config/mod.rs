@@ -1,69 +1,1321 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 
+use actix_web::http::{Method, header::HeaderName};
+use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use anyhow::{Context, Result, anyhow};
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use reqwest::Url;
+use serde::{Deserialize, Serialize, Serializer};
+use tracing::warn;
+use utoipa::ToSchema;
 
-use crate::types::{DexType, lowercase_address};
+use crate::{
+    types::{DexType, address_key, checksummed_address, parse_checksummed_address},
+    utils::secret::Secret,
+};
 
-#[derive(Debug, Deserialize, Clone)]
+pub mod chains;
+pub mod cli;
+pub mod profile;
+pub mod registry;
+pub mod remote;
+pub mod vault;
+
+use cli::CLI_ARGS;
+use profile::AppEnv;
+use registry::AddressRegistry;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TomlConfig {
     pub chain: ChainConfig,
+    /// Well-known contracts of the chain
+    #[serde(default)]
+    pub addresses: AddressRegistry,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    #[serde(default)]
+    pub app: AppSettings,
+    #[serde(default)]
+    pub price_guard: PriceGuardConfig,
     pub pools: Vec<PoolConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct ChainConfig {
+    /// Serialized as its origin only, the providers often put their API key in the path
+    #[serde(serialize_with = "serialize_url_origin")]
     pub rpc_url: String,
+    /// Endpoints the requests fail over to when `rpc_url` stops answering, in order
+    #[serde(default, serialize_with = "serialize_url_origins")]
+    pub fallback_rpc_urls: Vec<String>,
+    /// WebSocket endpoint the `Swap` logs of the tracked pools are subscribed on, to
+    /// update their tick within a block. `rpc_url` is used when it is a ws(s) url itself
+    #[serde(default, serialize_with = "serialize_optional_url_origin")]
+    pub ws_url: Option<String>,
     pub chain_id: u64,
+    /// Contracts the signer may send transactions to, besides the Yield contract
+    #[serde(default)]
+    pub allowed_contracts: Vec<Address>,
+    /// Extra headers sent with every RPC request to `rpc_url` and the fallback urls, e.g.
+    /// the token of a private node
+    #[serde(default)]
+    pub rpc_headers: RpcHeaders,
+    pub rpc_auth: Option<RpcAuth>,
+    /// How the fees of the signer transactions are priced
+    #[serde(default)]
+    pub gas: GasConfig,
+}
+
+/// Prints the urls as their origin only, like `Serialize`
+impl fmt::Debug for ChainConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainConfig")
+            .field("rpc_url", &url_origin(&self.rpc_url))
+            .field(
+                "fallback_rpc_urls",
+                &self
+                    .fallback_rpc_urls
+                    .iter()
+                    .map(|url| url_origin(url))
+                    .collect::<Vec<_>>(),
+            )
+            .field("ws_url", &self.ws_url.as_deref().map(url_origin))
+            .field("chain_id", &self.chain_id)
+            .field("allowed_contracts", &self.allowed_contracts)
+            .field("rpc_headers", &self.rpc_headers)
+            .field("rpc_auth", &self.rpc_auth)
+            .field("gas", &self.gas)
+            .finish()
+    }
+}
+
+/// Defaults that usually differ between the environment profiles
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Log level or filter directives used without `--log-level`, read at startup
+    pub log_level: Option<String>,
+    /// Only simulate the position closes that don't set `dry_run`
+    pub dry_run: bool,
+}
+
+/// Price checks run before every execution, aborting it when the prices diverge
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PriceGuardConfig {
+    pub enabled: bool,
+    /// Window of the TWAP the spot price is compared to
+    pub twap_secs: u32,
+    /// Maximum divergence between the spot price and the TWAP, in percent
+    pub max_twap_deviation_percent: f64,
+    /// Maximum divergence between the tracked price the execution is built from and the
+    /// spot price, in percent
+    pub max_stale_deviation_percent: f64,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl Default for PriceGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            twap_secs: DEFAULT_PRICE_GUARD_TWAP_SECS,
+            max_twap_deviation_percent: DEFAULT_PRICE_GUARD_MAX_TWAP_DEVIATION_PERCENT,
+            max_stale_deviation_percent: DEFAULT_PRICE_GUARD_MAX_STALE_DEVIATION_PERCENT,
+        }
+    }
+}
+
+/// Concurrency and timeouts, to tune per environment
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Maximum number of RPC calls a batch fetch runs concurrently
+    pub max_concurrency: usize,
+    /// Maximum time an RPC request can take, read at startup
+    pub rpc_timeout_secs: u64,
+    /// Maximum time a request to another HTTP server can take, e.g. an NFT metadata server
+    pub http_timeout_secs: u64,
+    /// Seconds between two checks for a new block, whose logs refresh the hot pools
+    pub hot_poll_interval_secs: u64,
+    /// Maximum age of the state of a warm pool, and of a hot pool without any log
+    pub warm_refresh_interval_secs: u64,
+    /// Maximum age of the state of a cold pool
+    pub cold_refresh_interval_secs: u64,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            rpc_timeout_secs: DEFAULT_RPC_TIMEOUT_SECS,
+            http_timeout_secs: DEFAULT_HTTP_TIMEOUT_SECS,
+            hot_poll_interval_secs: DEFAULT_HOT_POLL_INTERVAL_SECS,
+            warm_refresh_interval_secs: DEFAULT_WARM_REFRESH_INTERVAL_SECS,
+            cold_refresh_interval_secs: DEFAULT_COLD_REFRESH_INTERVAL_SECS,
+        }
+    }
+}
+
+/// Fee fields set on the transactions, some chains don't support EIP-1559
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GasStrategy {
+    /// `gasPrice` only
+    Legacy,
+    /// `maxFeePerGas` and `maxPriorityFeePerGas`
+    #[default]
+    Eip1559,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GasConfig {
+    #[serde(default)]
+    pub strategy: GasStrategy,
+    /// Applied to the fees estimated by the node, above 1 to get included faster
+    #[serde(default = "default_gas_multiplier")]
+    pub multiplier: f64,
+    /// Cap (wei) of the gas price of legacy transactions and of the max fee per gas of
+    /// EIP-1559 ones
+    pub max_fee_per_gas: Option<u128>,
+    /// Cap (wei) of the priority fee of EIP-1559 transactions
+    pub max_priority_fee_per_gas: Option<u128>,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            strategy: GasStrategy::default(),
+            multiplier: default_gas_multiplier(),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+        }
+    }
+}
+
+fn default_gas_multiplier() -> f64 {
+    1.0
+}
+
+fn url_origin(url: &str) -> String {
+    match Url::parse(url).map(|url| url.origin()) {
+        Ok(origin) if origin.is_tuple() => origin.ascii_serialization(),
+        _ => "***".to_string(),
+    }
+}
+
+fn serialize_url_origin<S: Serializer>(url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&url_origin(url))
+}
+
+fn serialize_optional_url_origin<S: Serializer>(
+    url: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match url {
+        Some(url) => serialize_url_origin(url, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_url_origins<S: Serializer>(urls: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(urls.iter().map(|url| url_origin(url)))
+}
+
+/// RPC request headers, their values are kept out of the logs since they often hold tokens
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RpcHeaders(pub BTreeMap<String, Secret<String>>);
+
+/// Authentication of the RPC requests
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RpcAuth {
+    Bearer {
+        token: Secret<String>,
+    },
+    Basic {
+        username: String,
+        password: Option<Secret<String>>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PoolConfig {
-    #[serde(deserialize_with = "lowercase_address")]
-    pub address: String,
+    #[serde(deserialize_with = "checksummed_address")]
+    pub address: Address,
     pub dex_type: DexType,
+    /// How the positions of the pool are managed, none when the pool is only tracked
+    pub strategy: Option<PoolStrategy>,
+    /// How often the pool is refreshed, automatic when unset: hot when positions are
+    /// held in it, cold otherwise
+    pub refresh_tier: Option<RefreshTier>,
+}
+
+/// How often a tracked pool is refreshed from the blockchain
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshTier {
+    /// On every block with a log of the pool
+    Hot,
+    /// Every `runtime.warm_refresh_interval_secs`
+    Warm,
+    /// Every `runtime.cold_refresh_interval_secs`
+    Cold,
+}
+
+impl PoolConfig {
+    /// Key the pool is tracked under in the app state
+    pub fn key(&self) -> String {
+        address_key(&self.address)
+    }
+}
+
+/// Position management parameters of a pool, the `[pools.strategy]` table
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, ToSchema)]
+pub struct PoolStrategy {
+    /// Width of the position range, in percent of the price at the range center
+    pub range_width_percent: f64,
+    /// Price move away from the range center that triggers a rebalance, in percent of
+    /// the half range width: 100 rebalances as soon as the price leaves the range
+    pub rebalance_threshold_percent: f64,
+    /// Highest gas price (wei) a rebalance may be sent at, no limit when unset
+    pub max_gas_price: Option<u128>,
+    /// Rebalance automatically, otherwise rebalances are only suggested
+    #[serde(default)]
+    pub auto_rebalance: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimitConfig {
+    pub requests_per_second: f64,
+    /// Number of requests a client can make in a row before being throttled
+    pub burst: u32,
+}
+
+/// Capabilities granted to an API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyTier {
+    /// Every endpoint, the `/admin` ones included
+    Admin,
+    /// Every endpoint but the `/admin` ones
+    Full,
+    /// Read and simulation endpoints only, never anything sending a transaction or
+    /// changing the server state
+    Sandbox,
+}
+
+impl FromStr for ApiKeyTier {
+    type Err = anyhow::Error;
+
+    fn from_str(tier: &str) -> Result<Self> {
+        match tier {
+            "admin" => Ok(ApiKeyTier::Admin),
+            "full" => Ok(ApiKeyTier::Full),
+            "sandbox" => Ok(ApiKeyTier::Sandbox),
+            _ => anyhow::bail!("Unknown API key tier: {}", tier),
+        }
+    }
+}
+
+/// Tier of each known API key, the keys are kept out of the logs
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys(pub BTreeMap<Secret<String>, ApiKeyTier>);
+
+/// Serialized as the number of keys of each tier, the keys themselves are secrets
+impl Serialize for ApiKeys {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tiers: BTreeMap<ApiKeyTier, usize> = BTreeMap::new();
+        for tier in self.0.values() {
+            *tiers.entry(*tier).or_default() += 1;
+        }
+        serializer.collect_map(tiers)
+    }
+}
+
+/// Allowed CORS origins, methods and headers, `["*"]` allows any
+#[derive(Debug, Clone, Serialize)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Serialized with its secrets masked, see `GET /admin/config`
+#[derive(Debug, Clone, Serialize)]
 pub struct Config {
-    pub contract_address: String,
-    pub private_key: String,
+    /// Environment whose profile is layered over the toml config, from `APP_ENV`
+    pub app_env: Option<AppEnv>,
+    pub contract_address: Address,
+    pub private_key: Secret<String>,
+    /// Address the HTTP server binds, `0.0.0.0` to listen on every interface
+    pub host: String,
     pub port: u16,
+    /// Number of HTTP worker threads, the number of physical CPU cores when unset
+    pub workers: Option<usize>,
+    /// Never send a transaction, the simulations are still allowed
+    pub read_only: bool,
+    /// Secret used to sign the JWTs, a random one is generated at startup when unset
+    pub jwt_secret: Option<Secret<String>>,
+    /// Anyone can register a user, otherwise registering requires an admin API key
+    pub open_registration: bool,
+    /// Per client rate limit, disabled when `RATE_LIMIT_RPS` is 0
+    pub rate_limit: Option<RateLimitConfig>,
+    pub cors: CorsConfig,
+    /// API keys accepted in the `X-Api-Key` header, any key is accepted when empty
+    pub api_keys: ApiKeys,
+    /// Keep 1 in N log events of each module, warnings and errors are always kept
+    pub log_sampling: BTreeMap<String, u64>,
+    /// Seconds between two checks of `CONFIG_URL` for a new ETag, never checked when unset
+    pub config_refresh_secs: Option<u64>,
     pub toml: TomlConfig,
 }
 
 impl Config {
+    /// Load the configuration, exiting with every problem found when it is invalid
     pub fn load() -> Self {
-        let contract_address =
-            std::env::var("CONTRACT_ADDRESS").expect("CONTRACT_ADDRESS must be set");
-        let private_key = std::env::var("PRIVATE_KEY").expect("PRIVATE_KEY must be set");
-        let port: u16 = std::env::var("PORT")
-            .unwrap_or_else(|_| "8080".to_string())
-            .parse()
-            .expect("PORT must be a valid u16 number");
+        match Self::try_load() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Failed to load config: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-        let path = "src/config/bnb.toml";
+    /// Load the configuration from the environment and the toml file
+    ///
+    /// The invalid environment variables are reported together with the problems of the
+    /// loaded values.
+    pub fn try_load() -> Result<Self> {
+        let mut problems = Vec::new();
 
-        // Read the toml configuration
-        let data = fs::read_to_string(path).expect("Unable to read config file");
+        let contract_address = or_problem(
+            &mut problems,
+            std::env::var("CONTRACT_ADDRESS")
+                .context("CONTRACT_ADDRESS must be set")
+                .and_then(|address| {
+                    parse_checksummed_address(&address)
+                        .context("CONTRACT_ADDRESS must be a valid address")
+                }),
+            Address::ZERO,
+        );
+        let private_key = or_problem(
+            &mut problems,
+            if vault::is_enabled() {
+                vault::secret("PRIVATE_KEY").context("PRIVATE_KEY is missing from the Vault secret")
+            } else {
+                std::env::var("PRIVATE_KEY")
+                    .map(Secret::new)
+                    .context("PRIVATE_KEY must be set")
+            },
+            Secret::new(String::new()),
+        );
+        let port: u16 = match CLI_ARGS.port {
+            Some(port) => port,
+            None => or_problem(
+                &mut problems,
+                std::env::var("PORT")
+                    .unwrap_or_else(|_| "8080".to_string())
+                    .parse()
+                    .context("PORT must be a valid u16 number"),
+                8080,
+            ),
+        };
+        let host = std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+        let workers = match std::env::var("WORKERS") {
+            Ok(workers) => or_problem(
+                &mut problems,
+                workers
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|workers| *workers > 0)
+                    .map(Some)
+                    .context("WORKERS must be a positive number"),
+                None,
+            ),
+            Err(_) => None,
+        };
+        let read_only = CLI_ARGS.read_only
+            || std::env::var("READ_ONLY").is_ok_and(|value| value == "true" || value == "1");
+        let jwt_secret = vault::secret("JWT_SECRET")
+            .or_else(|| std::env::var("JWT_SECRET").ok().map(Secret::new));
+        let open_registration =
+            std::env::var("OPEN_REGISTRATION").is_ok_and(|value| value == "true" || value == "1");
+        let requests_per_second: f64 = or_problem(
+            &mut problems,
+            std::env::var("RATE_LIMIT_RPS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .context("RATE_LIMIT_RPS must be a number"),
+            0.0,
+        );
+        let burst: u32 = or_problem(
+            &mut problems,
+            std::env::var("RATE_LIMIT_BURST")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .context("RATE_LIMIT_BURST must be a valid u32 number"),
+            1,
+        );
+        let rate_limit = (requests_per_second > 0.0).then_some(RateLimitConfig {
+            requests_per_second,
+            burst: burst.max(1),
+        });
 
-        let config: TomlConfig = toml::from_str(&data).expect("Unable to parse config file");
+        let cors = CorsConfig {
+            allowed_origins: env_list("CORS_ALLOWED_ORIGINS"),
+            allowed_methods: env_list("CORS_ALLOWED_METHODS"),
+            allowed_headers: env_list("CORS_ALLOWED_HEADERS"),
+        };
+        // `key:tier` pairs, the tier defaults to full
+        let api_keys = ApiKeys(or_problem(
+            &mut problems,
+            std::env::var("API_KEYS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| match entry.split_once(':') {
+                    Some((key, tier)) => Ok((Secret::new(key.to_string()), tier.parse()?)),
+                    None => Ok((Secret::new(entry.to_string()), ApiKeyTier::Full)),
+                })
+                .collect::<Result<_>>()
+                .context("API_KEYS must be a comma separated list of key:tier"),
+            Default::default(),
+        ));
 
-        Self {
+        // `module=N` pairs, e.g. `yieldai::core::gas=10`
+        let log_sampling = or_problem(
+            &mut problems,
+            std::env::var("LOG_SAMPLING")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| {
+                    let (module, rate) = entry
+                        .split_once('=')
+                        .with_context(|| format!("Invalid LOG_SAMPLING entry: {}", entry))?;
+                    let rate: u64 = rate
+                        .parse()
+                        .ok()
+                        .filter(|rate| *rate > 0)
+                        .with_context(|| format!("Invalid LOG_SAMPLING rate: {}", entry))?;
+                    Ok((module.to_string(), rate))
+                })
+                .collect::<Result<_>>(),
+            BTreeMap::new(),
+        );
+
+        let config_refresh_secs = match std::env::var("CONFIG_URL_REFRESH_SECS") {
+            Ok(secs) => or_problem(
+                &mut problems,
+                secs.parse::<u64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .map(Some)
+                    .context("CONFIG_URL_REFRESH_SECS must be a positive number"),
+                None,
+            ),
+            Err(_) => None,
+        };
+
+        let app_env = match std::env::var("APP_ENV") {
+            Ok(app_env) => or_problem(&mut problems, app_env.parse::<AppEnv>().map(Some), None),
+            Err(_) => None,
+        };
+
+        // The values can't be checked without the toml config
+        let config = match read_toml_config(app_env) {
+            Ok(config) => config,
+            Err(e) => {
+                problems.push(format!("{:#}", e));
+                return Err(problems_error(&problems));
+            }
+        };
+
+        let config = Self {
+            app_env,
             contract_address,
             private_key,
+            host,
             port,
+            workers,
+            read_only,
+            jwt_secret,
+            open_registration,
+            rate_limit,
+            cors,
+            api_keys,
+            log_sampling,
+            config_refresh_secs,
             toml: config,
+        };
+        config.validate(problems)?;
+        Ok(config)
+    }
+
+    /// Check the loaded values, reporting every problem at once along with the
+    /// `problems` already found
+    fn validate(&self, mut problems: Vec<String>) -> Result<()> {
+        if self.host.trim().is_empty() {
+            problems.push("HOST must not be empty".to_string());
+        }
+        // A missing key is already reported
+        if !self.private_key.expose().is_empty()
+            && PrivateKeySigner::from_str(self.private_key.expose()).is_err()
+        {
+            problems.push("PRIVATE_KEY must be a 32 bytes hex private key".to_string());
+        }
+
+        // Origins as sent by the browsers, `scheme://host[:port]`
+        for origin in &self.cors.allowed_origins {
+            if origin != "*"
+                && !Url::parse(origin)
+                    .is_ok_and(|url| url.origin().ascii_serialization() == *origin)
+            {
+                problems.push(format!("Invalid CORS origin: {}", origin));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if method != "*" && Method::from_bytes(method.as_bytes()).is_err() {
+                problems.push(format!("Invalid CORS method: {}", method));
+            }
+        }
+        for header in &self.cors.allowed_headers {
+            if header != "*" && HeaderName::from_bytes(header.as_bytes()).is_err() {
+                problems.push(format!("Invalid CORS header: {}", header));
+            }
+        }
+
+        let chain = &self.toml.chain;
+        match Url::parse(&chain.rpc_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https" | "ws" | "wss") => {}
+            Ok(url) => problems.push(format!(
+                "chain.rpc_url has an unsupported scheme: {}",
+                url.scheme()
+            )),
+            Err(e) => problems.push(format!("chain.rpc_url is not a valid url: {}", e)),
+        }
+        for (index, rpc_url) in chain.fallback_rpc_urls.iter().enumerate() {
+            match Url::parse(rpc_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                Ok(_) => problems.push(format!(
+                    "chain.fallback_rpc_urls[{}] must be an http(s) url",
+                    index
+                )),
+                Err(e) => problems.push(format!(
+                    "chain.fallback_rpc_urls[{}] is not a valid url: {}",
+                    index, e
+                )),
+            }
+        }
+        // The failover transport is built on HTTP clients
+        if let Some(ws_url) = &chain.ws_url {
+            match Url::parse(ws_url) {
+                Ok(url) if matches!(url.scheme(), "ws" | "wss") => {}
+                Ok(_) => problems.push("chain.ws_url must be a ws(s) url".to_string()),
+                Err(e) => problems.push(format!("chain.ws_url is not a valid url: {}", e)),
+            }
+        }
+        if !chain.fallback_rpc_urls.is_empty()
+            && Url::parse(&chain.rpc_url).is_ok_and(|url| !matches!(url.scheme(), "http" | "https"))
+        {
+            problems.push(
+                "chain.rpc_url must be an http(s) url when fallback urls are set".to_string(),
+            );
+        }
+        if chain.chain_id == 0 {
+            problems.push("chain.chain_id must not be 0".to_string());
+        }
+        if !(chain.gas.multiplier.is_finite() && chain.gas.multiplier > 0.0) {
+            problems.push("chain.gas.multiplier must be a positive number".to_string());
+        }
+
+        problems.extend(self.toml.addresses.problems());
+
+        let runtime = &self.toml.runtime;
+        if runtime.max_concurrency == 0 {
+            problems.push("runtime.max_concurrency must be at least 1".to_string());
+        }
+        if runtime.rpc_timeout_secs == 0 {
+            problems.push("runtime.rpc_timeout_secs must be at least 1".to_string());
+        }
+        if runtime.http_timeout_secs == 0 {
+            problems.push("runtime.http_timeout_secs must be at least 1".to_string());
+        }
+        for (name, secs) in [
+            ("hot_poll_interval_secs", runtime.hot_poll_interval_secs),
+            (
+                "warm_refresh_interval_secs",
+                runtime.warm_refresh_interval_secs,
+            ),
+            (
+                "cold_refresh_interval_secs",
+                runtime.cold_refresh_interval_secs,
+            ),
+        ] {
+            if secs == 0 {
+                problems.push(format!("runtime.{} must be at least 1", name));
+            }
+        }
+
+        let price_guard = &self.toml.price_guard;
+        if price_guard.twap_secs == 0 {
+            problems.push("price_guard.twap_secs must be at least 1".to_string());
+        }
+        if !(price_guard.max_twap_deviation_percent.is_finite()
+            && price_guard.max_twap_deviation_percent > 0.0)
+        {
+            problems.push("price_guard.max_twap_deviation_percent must be positive".to_string());
         }
+        if !(price_guard.max_stale_deviation_percent.is_finite()
+            && price_guard.max_stale_deviation_percent > 0.0)
+        {
+            problems.push("price_guard.max_stale_deviation_percent must be positive".to_string());
+        }
+
+        let mut seen = HashSet::new();
+        for pool in &self.toml.pools {
+            if !seen.insert(pool.key()) {
+                problems.push(format!("Pool {} is listed more than once", pool.address));
+            }
+
+            if let Some(strategy) = &pool.strategy {
+                if !(strategy.range_width_percent > 0.0 && strategy.range_width_percent < 200.0) {
+                    problems.push(format!(
+                        "Pool {} strategy.range_width_percent must be between 0 and 200",
+                        pool.address
+                    ));
+                }
+                if !(strategy.rebalance_threshold_percent.is_finite()
+                    && strategy.rebalance_threshold_percent > 0.0)
+                {
+                    problems.push(format!(
+                        "Pool {} strategy.rebalance_threshold_percent must be a positive number",
+                        pool.address
+                    ));
+                }
+            }
+        }
+
+        report(problems)
+    }
+
+    /// Keep the fields of `previous` that are only read at startup, like the RPC
+    /// connection and the signer, so a reload never leaves them out of sync
+    ///
+    /// # Returns:
+    /// * The names of the restart-only fields whose change was ignored
+    fn keep_restart_only(&mut self, previous: &Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+
+        if self.private_key != previous.private_key {
+            self.private_key = previous.private_key.clone();
+            ignored.push("PRIVATE_KEY");
+        }
+        // Allowlisted and trusted as the spender of the signer approvals, like
+        // `allowed_contracts`
+        if self.contract_address != previous.contract_address {
+            self.contract_address = previous.contract_address;
+            ignored.push("CONTRACT_ADDRESS");
+        }
+        if self.host != previous.host {
+            self.host = previous.host.clone();
+            ignored.push("HOST");
+        }
+        if self.port != previous.port {
+            self.port = previous.port;
+            ignored.push("PORT");
+        }
+        if self.workers != previous.workers {
+            self.workers = previous.workers;
+            ignored.push("WORKERS");
+        }
+        if self.jwt_secret != previous.jwt_secret {
+            self.jwt_secret = previous.jwt_secret.clone();
+            ignored.push("JWT_SECRET");
+        }
+        // The refresh loop is started with its interval
+        if self.config_refresh_secs != previous.config_refresh_secs {
+            self.config_refresh_secs = previous.config_refresh_secs;
+            ignored.push("CONFIG_URL_REFRESH_SECS");
+        }
+        // The RPC client is built at startup
+        let runtime = &mut self.toml.runtime;
+        if runtime.rpc_timeout_secs != previous.toml.runtime.rpc_timeout_secs {
+            runtime.rpc_timeout_secs = previous.toml.runtime.rpc_timeout_secs;
+            ignored.push("runtime.rpc_timeout_secs");
+        }
+        let chain = &mut self.toml.chain;
+        let previous_chain = &previous.toml.chain;
+        if chain.chain_id != previous_chain.chain_id {
+            chain.chain_id = previous_chain.chain_id;
+            ignored.push("chain.chain_id");
+        }
+        if chain.rpc_url != previous_chain.rpc_url {
+            chain.rpc_url = previous_chain.rpc_url.clone();
+            ignored.push("chain.rpc_url");
+        }
+        if chain.fallback_rpc_urls != previous_chain.fallback_rpc_urls {
+            chain.fallback_rpc_urls = previous_chain.fallback_rpc_urls.clone();
+            ignored.push("chain.fallback_rpc_urls");
+        }
+        if chain.ws_url != previous_chain.ws_url {
+            chain.ws_url = previous_chain.ws_url.clone();
+            ignored.push("chain.ws_url");
+        }
+        // The signer must not be able to reach new contracts without a restart
+        if chain.allowed_contracts != previous_chain.allowed_contracts {
+            chain.allowed_contracts = previous_chain.allowed_contracts.clone();
+            ignored.push("chain.allowed_contracts");
+        }
+        // The headers and the authentication hold secrets, they are always restored
+        chain.rpc_headers = previous_chain.rpc_headers.clone();
+        chain.rpc_auth = previous_chain.rpc_auth.clone();
+
+        ignored
+    }
+}
+
+/// Toml config file given by `--config`, `--chain` or `CONFIG_PATH`, defaulting to
+/// `TOML_CONFIG_PATH` when it exists
+///
+/// # Returns:
+/// * `None` when the config is fetched from `CONFIG_URL` or the embedded default config
+///   is used
+pub fn toml_config_path() -> Option<String> {
+    if let Some(path) = &CLI_ARGS.config {
+        return Some(path.clone());
+    }
+    if let Some(chain) = &CLI_ARGS.chain {
+        return Some(format!("src/config/{}.toml", chain));
+    }
+    if remote::config_url().is_some() {
+        return None;
+    }
+    if let Ok(path) = std::env::var("CONFIG_PATH") {
+        return Some(path);
+    }
+    Path::new(TOML_CONFIG_PATH)
+        .exists()
+        .then(|| TOML_CONFIG_PATH.to_string())
+}
+
+/// Toml config of `toml_config_path`, `CONFIG_URL` or the embedded one, with the profile
+/// of `app_env` applied and the known addresses of the chain filled in
+fn read_toml_config(app_env: Option<AppEnv>) -> Result<TomlConfig> {
+    let data = match toml_config_path() {
+        Some(path) => fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read config file {}", path))?,
+        None if remote::config_url().is_some() => {
+            remote::body().context("The config of CONFIG_URL was not fetched")?
+        }
+        None => EMBEDDED_TOML_CONFIG.to_string(),
+    };
+
+    let profile = match app_env {
+        Some(app_env) => profile::read_profile(app_env)?,
+        None => None,
+    };
+
+    let mut config: TomlConfig = match profile {
+        Some(profile) => {
+            let mut table: toml::Table =
+                toml::from_str(&data).context("Unable to parse config file")?;
+            let profile: toml::Table =
+                toml::from_str(&profile).context("Unable to parse profile file")?;
+            profile::merge(&mut table, profile);
+            toml::Value::Table(table)
+                .try_into()
+                .context("Invalid config once the profile is applied")?
+        }
+        None => toml::from_str(&data).context("Unable to parse config file")?,
+    };
+    if let Some(known) = chains::known_addresses(config.chain.chain_id) {
+        config.addresses.fill_from(known);
+    }
+    Ok(config)
+}
+
+/// Value of `result`, or `default` with the error added to `problems`
+fn or_problem<T>(problems: &mut Vec<String>, result: Result<T>, default: T) -> T {
+    result.unwrap_or_else(|e| {
+        problems.push(format!("{:#}", e));
+        default
+    })
+}
+
+/// Fail with every problem found, if any
+fn report(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(problems_error(&problems))
+}
+
+fn problems_error(problems: &[String]) -> anyhow::Error {
+    anyhow!(
+        "{} configuration problem(s):\n  - {}",
+        problems.len(),
+        problems.join("\n  - ")
+    )
+}
+
+/// Read a comma separated list from the environment, defaulting to `*`
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_else(|_| "*".to_string())
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Runtime mutable holder of the current configuration.
+///
+/// Readers get a cheap `Arc` snapshot that stays consistent for as long as they hold it,
+/// while writers swap in a whole new `Config`, so nobody ever sees a half-applied change.
+#[derive(Debug)]
+pub struct ConfigService {
+    current: RwLock<Arc<Config>>,
+}
+
+impl ConfigService {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// Get a snapshot of the current configuration
+    pub fn get(&self) -> Arc<Config> {
+        Arc::clone(&self.current.read().expect("Config lock poisoned"))
+    }
+
+    /// Replace the current configuration, returning the previous one
+    pub fn replace(&self, config: Config) -> Arc<Config> {
+        let mut current = self.current.write().expect("Config lock poisoned");
+        std::mem::replace(&mut *current, Arc::new(config))
+    }
+
+    /// Reload the configuration from the environment and the toml file.
+    /// The current configuration is kept if the new one fails to load.
+    ///
+    /// Changes of the restart-only fields are ignored with a warning.
+    pub fn reload(&self) -> Result<Arc<Config>> {
+        let mut config = Config::try_load()?;
+        let ignored = config.keep_restart_only(&self.get());
+        if !ignored.is_empty() {
+            warn!(
+                "Ignored changes of {}, they only apply after a restart",
+                ignored.join(", ")
+            );
+        }
+        self.replace(config);
+        Ok(self.get())
     }
 }
 
 // Define a globally accessible static Config instance
-pub static CONFIG: Lazy<Config> = Lazy::new(Config::load);
+pub static CONFIG: Lazy<ConfigService> = Lazy::new(|| ConfigService::new(Config::load()));
 
 // CONSTANTS
+/// Default toml configuration file, relative to the `backend` directory
+pub const TOML_CONFIG_PATH: &str = "src/config/bnb.toml";
+
+/// Config used when no toml file is given and the binary doesn't run from the `backend`
+/// directory
+pub const EMBEDDED_TOML_CONFIG: &str = include_str!("bnb.toml");
+
 pub const FEE_FACTOR: f64 = 10_000.0;
 
-/// Maximum number of concurrent tasks allowed
+/// Default maximum number of concurrent tasks, `runtime.max_concurrency`
 /// This prevents overwhelming
-pub const MAX_ALLOWED_THREADS: usize = 8;
\ No newline at end of file
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// Default maximum time an RPC request can take, `runtime.rpc_timeout_secs`
+pub const DEFAULT_RPC_TIMEOUT_SECS: u64 = 10;
+
+/// Default maximum time a request to another HTTP server can take,
+/// `runtime.http_timeout_secs`
+pub const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 10;
+
+/// Default seconds between two checks for a new block, `runtime.hot_poll_interval_secs`
+pub const DEFAULT_HOT_POLL_INTERVAL_SECS: u64 = 3;
+
+/// Default maximum age of the state of a warm pool, `runtime.warm_refresh_interval_secs`
+pub const DEFAULT_WARM_REFRESH_INTERVAL_SECS: u64 = 30;
+
+/// Default maximum age of the state of a cold pool, `runtime.cold_refresh_interval_secs`
+pub const DEFAULT_COLD_REFRESH_INTERVAL_SECS: u64 = 600;
+
+/// Default window of the TWAP compared to the spot price, `price_guard.twap_secs`
+pub const DEFAULT_PRICE_GUARD_TWAP_SECS: u32 = 300;
+
+/// Default maximum divergence between the spot price and the TWAP, in percent,
+/// `price_guard.max_twap_deviation_percent`
+pub const DEFAULT_PRICE_GUARD_MAX_TWAP_DEVIATION_PERCENT: f64 = 5.0;
+
+/// Default maximum divergence between the tracked and spot prices, in percent,
+/// `price_guard.max_stale_deviation_percent`
+pub const DEFAULT_PRICE_GUARD_MAX_STALE_DEVIATION_PERCENT: f64 = 1.0;
+
+/// Number of recent blocks the block time is averaged over
+pub const BLOCK_TIME_SAMPLE_BLOCKS: u64 = 200;
+
+/// Interval between two sweeps of the idle rate limited clients
+pub const RATE_LIMIT_SWEEP_SECS: u64 = 60;
+
+/// Idle time after which a rate limited client is forgotten
+pub const RATE_LIMIT_IDLE_SECS: u64 = 10 * 60;
+
+/// Maximum time a readiness probe of a dependency can take before it is reported as down
+pub const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Requests timing out in a row after which the RPC endpoint is replaced by the next one
+pub const RPC_FAILOVER_MAX_TIMEOUTS: u32 = 3;
+
+/// Number of snapshots of a pool needed before its tick jumps are checked for anomalies
+pub const ANOMALY_MIN_SAMPLES: u32 = 10;
+
+/// Liquidity drop ratio between two snapshots above which an anomaly is flagged
+pub const ANOMALY_LIQUIDITY_DROP_RATIO: f64 = 0.5;
+
+/// Number of recent anomalies kept in memory
+pub const MAX_STORED_ANOMALIES: usize = 1_000;
+
+/// Validity of the deadline set on the submitted transactions
+pub const TX_DEADLINE_SECS: u64 = 5 * 60;
+
+/// Lifetime of the cached position NFT metadata
+pub const NFT_METADATA_CACHE_SECS: u64 = 60 * 60;
+
+/// Largest NFT metadata JSON downloaded from a token URI
+pub const MAX_NFT_METADATA_BYTES: usize = 256 * 1024;
+
+/// Interval between two gas price samples
+pub const GAS_SAMPLE_INTERVAL_SECS: u64 = 5 * 60;
+
+/// Gas price percentiles are computed over this trailing window
+pub const GAS_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Interval between two gas price checks while waiting for cheap gas
+pub const GAS_WAIT_POLL_SECS: u64 = 30;
+
+/// Longest an action may wait for cheap gas
+pub const MAX_GAS_WAIT_SECS: u64 = 15 * 60;
+
+/// Number of price samples kept per pool for the analytics
+pub const MAX_PRICE_HISTORY_SAMPLES: usize = 10_000;
+
+/// Duration of the chart candles when none is requested
+pub const DEFAULT_CANDLE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Shortest accepted chart candle duration
+pub const MIN_CANDLE_INTERVAL_SECS: u64 = 60;
+
+/// Number of chart candles returned when no limit is requested
+pub const DEFAULT_CANDLE_LIMIT: usize = 100;
+
+/// Maximum number of chart candles returned at once
+pub const MAX_CANDLE_LIMIT: usize = 1_000;
+
+/// Candle durations the volatility statistics are computed on when none is requested
+pub const DEFAULT_VOLATILITY_WINDOWS_SECS: [u64; 3] = [60 * 60, 4 * 60 * 60, 24 * 60 * 60];
+
+/// Number of candles the volatility statistics are computed on when none is requested
+pub const DEFAULT_VOLATILITY_PERIODS: usize = 14;
+
+/// Largest block range queried in a single `eth_getLogs` call
+pub const MAX_LOG_BLOCK_RANGE: u64 = 5_000;
+
+/// Duration of the swap history used for the estimates when none is requested
+pub const DEFAULT_SWAP_LOOKBACK_SECS: u64 = 24 * 60 * 60;
+
+/// Longest swap history that can be requested for the estimates
+pub const MAX_SWAP_LOOKBACK_SECS: u64 = 24 * 60 * 60;
+
+/// Most `eth_getLogs` calls a swap history fetch makes, a longer lookback is shortened
+pub const MAX_SWAP_LOG_REQUESTS: u64 = 10;
+
+/// Requests per second a client can make to the endpoints fetching swap history, on top
+/// of `RATE_LIMIT_RPS`
+pub const SWAP_HISTORY_RATE_LIMIT_RPS: f64 = 0.1;
+
+/// Swap history requests a client can make in a row
+pub const SWAP_HISTORY_RATE_LIMIT_BURST: u32 = 3;
+
+/// Number of tick bitmap words read on each side of the current tick when none is requested
+pub const DEFAULT_TICK_BITMAP_WORDS: i16 = 2;
+
+/// Maximum number of tick bitmap words read on each side of the current tick
+pub const MAX_TICK_BITMAP_WORDS: i16 = 10;
+
+/// Default range width, in tick spacings, of a pool that has not been tuned yet
+pub const DEFAULT_RANGE_WIDTH_SPACINGS: i32 = 10;
+
+/// Out of range events per week the range width tuning aims for
+pub const RANGE_TUNING_TARGET_PER_WEEK: f64 = 1.0;
+
+/// Interval between two range width adjustments
+pub const RANGE_TUNING_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+/// Recorded ticks replayed to measure the out of range frequency of a width
+pub const RANGE_TUNING_WINDOW_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Minimum duration of recorded ticks needed to adjust a range width
+pub const RANGE_TUNING_MIN_HISTORY_SECS: u64 = 24 * 60 * 60;
+
+/// Largest factor a range width can be widened or narrowed by in one adjustment
+pub const RANGE_TUNING_MAX_STEP: f64 = 1.25;
+
+/// Number of range width adjustments kept per pool
+pub const MAX_RANGE_TUNING_HISTORY: usize = 100;
+
+/// Window the rates of the generated Prometheus alerting rules are computed over
+pub const ALERT_RATE_WINDOW_SECS: u64 = 5 * 60;
+
+/// Duration a generated Prometheus alert condition must hold before firing
+pub const ALERT_FOR_SECS: u64 = 5 * 60;
+
+/// Failed RPC calls ratio above which an alert fires
+pub const ALERT_RPC_FAILURE_RATIO: f64 = 0.05;
+
+/// 5xx responses ratio above which an alert fires
+pub const ALERT_HTTP_ERROR_RATIO: f64 = 0.05;
+
+/// Failed pool refreshes ratio above which an alert fires
+pub const ALERT_POOL_REFRESH_FAILURE_RATIO: f64 = 0.2;
+
+/// Transaction submissions failing in a row after which the signer switches to read-only
+pub const TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// File the swap ingestion checkpoints are persisted to
+pub const CHECKPOINTS_PATH: &str = "./data/checkpoints.json";
+
+/// File the position and pool annotations are persisted to
+pub const ANNOTATIONS_PATH: &str = "./data/annotations.json";
+
+/// File the executions of the positions are persisted to
+pub const EXECUTIONS_PATH: &str = "./data/executions.json";
+
+/// File the position snapshots are persisted to
+pub const SNAPSHOTS_PATH: &str = "./data/snapshots.json";
+
+/// File the transaction fail-safe state is persisted to, so a trip survives a restart
+pub const TX_FAILSAFE_PATH: &str = "./data/tx_failsafe.json";
+
+/// Maximum number of labels of a position or a pool
+pub const MAX_ANNOTATION_LABELS: usize = 10;
+
+/// Maximum number of characters of a label
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Maximum number of characters of a note
+pub const MAX_NOTE_LENGTH: usize = 1_000;
+
+/// Interval between two swap ingestion runs
+pub const SWAP_INGESTION_INTERVAL_SECS: u64 = 15;
+
+/// Confirmations a block needs before its swaps are ingested
+pub const SWAP_INGESTION_CONFIRMATIONS: u64 = 3;
+
+/// Delay before subscribing to the `Swap` logs on `chain.ws_url` again after the
+/// subscription failed or closed
+pub const SWAP_SUBSCRIPTION_RETRY_SECS: u64 = 5;
+
+/// Most blocks backfilled for a pool after a long downtime, older swaps are skipped
+pub const MAX_BACKFILL_BLOCKS: u64 = 200_000;
+
+/// Interval between two checks of the toml configuration file for changes
+pub const CONFIG_WATCH_INTERVAL_SECS: u64 = 5;
+
+/// Anomalies detected within this window are reported as active incidents
+pub const INCIDENT_WINDOW_SECS: u64 = 60 * 60;
+
+/// Lifetime of the issued JWTs
+pub const JWT_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// PBKDF2 iterations used to hash user passwords
+pub const PASSWORD_HASH_ITERATIONS: u32 = 100_000;
+
+/// Number of pool events buffered for slow subscribers before they start lagging
+pub const POOL_EVENTS_CAPACITY: usize = 1024;
+
+/// Number of price history updates buffered for slow subscribers before they start lagging
+pub const PRICE_HISTORY_UPDATES_CAPACITY: usize = 1024;
+
+/// Maximum number of candle subscriptions of a WebSocket client
+pub const MAX_CANDLE_SUBSCRIPTIONS: usize = 20;
+
+/// Default page size of paginated endpoints
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+
+/// Maximum page size of paginated endpoints
+pub const MAX_PAGE_LIMIT: usize = 200;
+
+/// Default maximum slippage of the swap of an invest plan, in percent
+pub const DEFAULT_INVEST_SLIPPAGE_PERCENT: f64 = 0.5;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Valid config built on the embedded toml config
+    fn config() -> Config {
+        Config {
+            app_env: None,
+            contract_address: Address::ZERO,
+            private_key: Secret::new(
+                "0x0123456789012345678901234567890123456789012345678901234567890123".to_string(),
+            ),
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            workers: None,
+            read_only: false,
+            jwt_secret: None,
+            open_registration: false,
+            rate_limit: None,
+            cors: CorsConfig {
+                allowed_origins: vec!["*".to_string()],
+                allowed_methods: vec!["*".to_string()],
+                allowed_headers: vec!["*".to_string()],
+            },
+            api_keys: ApiKeys::default(),
+            log_sampling: BTreeMap::new(),
+            config_refresh_secs: None,
+            toml: toml::from_str(EMBEDDED_TOML_CONFIG).unwrap(),
+        }
+    }
+
+    fn problems(config: &Config, env_problems: Vec<String>) -> Vec<String> {
+        let Err(e) = config.validate(env_problems) else {
+            return Vec::new();
+        };
+        e.to_string()
+            .lines()
+            .skip(1)
+            .map(|line| line.trim_start_matches("  - ").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn accepts_the_embedded_config() {
+        assert!(config().validate(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let mut config = config();
+        config.host = " ".to_string();
+        config.toml.chain.chain_id = 0;
+        config.toml.runtime.max_concurrency = 0;
+
+        assert_eq!(
+            problems(&config, vec!["PORT must be a valid u16 number".to_string()]),
+            [
+                "PORT must be a valid u16 number",
+                "HOST must not be empty",
+                "chain.chain_id must not be 0",
+                "runtime.max_concurrency must be at least 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_invalid_cors_settings() {
+        let mut config = config();
+        config.cors = CorsConfig {
+            allowed_origins: vec![
+                "https://app.example.com".to_string(),
+                "http://localhost:3000".to_string(),
+                "https://app.example.com/".to_string(),
+                "app.example.com".to_string(),
+            ],
+            allowed_methods: vec!["GET".to_string(), "GE T".to_string()],
+            allowed_headers: vec!["X-Api-Key".to_string(), "X Api Key".to_string()],
+        };
+
+        assert_eq!(
+            problems(&config, Vec::new()),
+            [
+                "Invalid CORS origin: https://app.example.com/",
+                "Invalid CORS origin: app.example.com",
+                "Invalid CORS method: GE T",
+                "Invalid CORS header: X Api Key",
+            ]
+        );
+    }
+
+    #[test]
+    fn debug_prints_only_the_origin_of_the_rpc_urls() {
+        let mut chain = config().toml.chain;
+        chain.rpc_url = "https://rpc.example.com/v2/secret-key".to_string();
+        chain.fallback_rpc_urls = vec!["https://fallback.example.com/secret-key".to_string()];
+        chain.ws_url = Some("wss://ws.example.com/secret-key".to_string());
+
+        let debug = format!("{:?}", chain);
+        assert!(!debug.contains("secret-key"), "{}", debug);
+        assert!(debug.contains("https://rpc.example.com"));
+        assert!(debug.contains("https://fallback.example.com"));
+        assert!(debug.contains("wss://ws.example.com"));
+    }
+
+    #[test]
+    fn keeps_the_signer_contracts_on_reload() {
+        let previous = config();
+        let mut reloaded = config();
+        reloaded.contract_address = Address::repeat_byte(1);
+        reloaded.toml.chain.allowed_contracts = vec![Address::repeat_byte(2)];
+        reloaded.toml.runtime.max_concurrency += 1;
+
+        assert_eq!(
+            reloaded.keep_restart_only(&previous),
+            ["CONTRACT_ADDRESS", "chain.allowed_contracts"]
+        );
+        assert_eq!(reloaded.contract_address, previous.contract_address);
+        assert!(reloaded.toml.chain.allowed_contracts.is_empty());
+        assert_eq!(
+            reloaded.toml.runtime.max_concurrency,
+            previous.toml.runtime.max_concurrency + 1
+        );
+    }
+
+    #[test]
+    fn reports_the_pools_listed_twice() {
+        let mut config = config();
+        let pool = config.toml.pools[0].clone();
+        config.toml.pools.push(pool);
+
+        assert_eq!(
+            problems(&config, Vec::new()),
+            [format!(
+                "Pool {} is listed more than once",
+                config.toml.pools[0].address
+            )]
+        );
+    }
+
+    #[test]
+    fn collects_the_env_errors() {
+        let mut problems = Vec::new();
+
+        let port: u16 = or_problem(
+            &mut problems,
+            "80800".parse().context("PORT must be a valid u16 number"),
+            8080,
+        );
+        let burst: u32 = or_problem(&mut problems, "20".parse().context("unused"), 1);
+
+        assert_eq!((port, burst), (8080, 20));
+        assert_eq!(
+            problems,
+            ["PORT must be a valid u16 number: number too large to fit in target type"]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use toml::{Table, Value};
+
+use super::toml_config_path;
+
+/// Deployment environment selected by `APP_ENV`, its `<name>.toml` profile is layered
+/// over the toml config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppEnv {
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl FromStr for AppEnv {
+    type Err = anyhow::Error;
+
+    fn from_str(env: &str) -> Result<Self> {
+        match env {
+            "dev" => Ok(AppEnv::Dev),
+            "staging" => Ok(AppEnv::Staging),
+            "prod" => Ok(AppEnv::Prod),
+            _ => bail!("Unknown APP_ENV: {}, expected dev, staging or prod", env),
+        }
+    }
+}
+
+impl AppEnv {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEnv::Dev => "dev",
+            AppEnv::Staging => "staging",
+            AppEnv::Prod => "prod",
+        }
+    }
+
+    /// Profile used along the embedded toml config
+    fn embedded(&self) -> &'static str {
+        match self {
+            AppEnv::Dev => include_str!("dev.toml"),
+            AppEnv::Staging => include_str!("staging.toml"),
+            AppEnv::Prod => include_str!("prod.toml"),
+        }
+    }
+}
+
+/// Profile file of `app_env`, next to the toml config file
+///
+/// # Returns:
+/// * `None` when the file doesn't exist, or the embedded config is used
+pub fn profile_config_path(app_env: AppEnv) -> Option<String> {
+    let config_path = toml_config_path()?;
+    let dir = Path::new(&config_path).parent().unwrap_or(Path::new(""));
+    let path = dir.join(format!("{}.toml", app_env.name()));
+    path.exists().then(|| path.to_string_lossy().into_owned())
+}
+
+/// Read the profile of `app_env`, the embedded one when the embedded config is used
+///
+/// # Returns:
+/// * `None` when the toml config file has no profile of this environment next to it
+pub fn read_profile(app_env: AppEnv) -> Result<Option<String>> {
+    if toml_config_path().is_none() {
+        return Ok(Some(app_env.embedded().to_string()));
+    }
+    profile_config_path(app_env)
+        .map(|path| {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Unable to read profile file {}", path))
+        })
+        .transpose()
+}
+
+/// Layer `profile` over `base`: the tables are merged key by key, any other value of the
+/// profile, arrays included, replaces the base one
+pub fn merge(base: &mut Table, profile: Table) {
+    for (key, value) in profile {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(profile)) => merge(base, profile),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
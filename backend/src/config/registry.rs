@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::types::DexType;
+
+/// Well-known contracts of a DEX deployment
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DexAddresses {
+    pub factory: Option<Address>,
+    /// NonfungiblePositionManager
+    pub position_manager: Option<Address>,
+    /// Swap router
+    pub router: Option<Address>,
+}
+
+/// Well-known addresses of the chain, from the `[addresses]` table of the toml config
+///
+/// Every address is optional and defaults to the built-in one of the chain, see
+/// `chains::known_addresses`. The features needing a missing one fall back to reading
+/// it on-chain when they can, or are unavailable.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AddressRegistry {
+    /// ERC-20 wrapper of the native token (WETH, WBNB)
+    pub wrapped_native: Option<Address>,
+    /// Multicall3 contract
+    pub multicall: Option<Address>,
+    #[serde(rename = "UniswapV3", default)]
+    pub uniswap_v3: DexAddresses,
+    #[serde(rename = "PancakeSwapV3", default)]
+    pub pancakeswap_v3: DexAddresses,
+}
+
+impl AddressRegistry {
+    pub fn dex(&self, dex_type: &DexType) -> &DexAddresses {
+        match dex_type {
+            DexType::UniswapV3 => &self.uniswap_v3,
+            DexType::PancakeSwapV3 => &self.pancakeswap_v3,
+        }
+    }
+
+    /// Use the addresses of `known` for the ones that aren't configured
+    pub fn fill_from(&mut self, known: AddressRegistry) {
+        self.wrapped_native = self.wrapped_native.or(known.wrapped_native);
+        self.multicall = self.multicall.or(known.multicall);
+        for (dex, known) in [
+            (&mut self.uniswap_v3, known.uniswap_v3),
+            (&mut self.pancakeswap_v3, known.pancakeswap_v3),
+        ] {
+            dex.factory = dex.factory.or(known.factory);
+            dex.position_manager = dex.position_manager.or(known.position_manager);
+            dex.router = dex.router.or(known.router);
+        }
+    }
+
+    /// Every configured address with its name in the toml config
+    fn entries(&self) -> impl Iterator<Item = (String, Address)> + '_ {
+        let chain = [
+            ("wrapped_native".to_string(), self.wrapped_native),
+            ("multicall".to_string(), self.multicall),
+        ];
+        let dexes = [
+            ("UniswapV3", &self.uniswap_v3),
+            ("PancakeSwapV3", &self.pancakeswap_v3),
+        ]
+        .into_iter()
+        .flat_map(|(name, dex)| {
+            [
+                (format!("{}.factory", name), dex.factory),
+                (format!("{}.position_manager", name), dex.position_manager),
+                (format!("{}.router", name), dex.router),
+            ]
+        });
+
+        chain
+            .into_iter()
+            .chain(dexes)
+            .filter_map(|(name, address)| Some((name, address?)))
+    }
+
+    /// Problems of the registry, reported with the other configuration problems
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut seen: HashMap<Address, String> = HashMap::new();
+
+        for (name, address) in self.entries() {
+            if address.is_zero() {
+                problems.push(format!("addresses.{} must not be the zero address", name));
+            } else if let Some(other) = seen.get(&address) {
+                problems.push(format!(
+                    "addresses.{} is the same address as addresses.{}",
+                    name, other
+                ));
+            } else {
+                seen.insert(address, name);
+            }
+        }
+
+        problems
+    }
+}
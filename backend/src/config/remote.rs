@@ -0,0 +1,114 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::{
+    StatusCode, Url,
+    header::{ETAG, IF_NONE_MATCH},
+};
+
+use super::cli::CLI_ARGS;
+
+/// Toml config fetched from `CONFIG_URL`, kept so `Config::try_load` stays synchronous
+#[derive(Debug, Clone)]
+struct RemoteConfig {
+    url: String,
+    etag: Option<String>,
+    body: String,
+}
+
+static REMOTE_CONFIG: RwLock<Option<RemoteConfig>> = RwLock::new(None);
+
+/// Url of the toml config given by `CONFIG_URL`, unless `--config` or `--chain` is given
+pub fn config_url() -> Option<String> {
+    if CLI_ARGS.config.is_some() || CLI_ARGS.chain.is_some() {
+        return None;
+    }
+    std::env::var("CONFIG_URL").ok()
+}
+
+/// Url without its query and credentials, a presigned url carries its signature in the
+/// query
+pub fn redacted_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            url.set_query(None);
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+/// Fetch the toml config from `CONFIG_URL`, to call once before the config is loaded
+pub async fn init(timeout: Duration) -> Result<()> {
+    let Some(url) = config_url() else {
+        return Ok(());
+    };
+    let parsed = Url::parse(&url).context("CONFIG_URL must be a valid url")?;
+    if parsed.scheme() != "https" {
+        bail!("CONFIG_URL must be an https url");
+    }
+
+    let remote = fetch(&url, None, timeout)
+        .await
+        .with_context(|| format!("Unable to fetch config from {}", redacted_url(&url)))?
+        .context("The config server answered Not Modified without an ETag")?;
+    *REMOTE_CONFIG.write().expect("Remote config lock poisoned") = Some(remote);
+    Ok(())
+}
+
+/// Fetch the toml config again, sending the ETag of the current one
+///
+/// # Returns:
+/// * Whether the config changed
+pub async fn refresh(timeout: Duration) -> Result<bool> {
+    let current = REMOTE_CONFIG
+        .read()
+        .expect("Remote config lock poisoned")
+        .clone()
+        .context("The remote config was never fetched")?;
+
+    let Some(remote) = fetch(&current.url, current.etag.as_deref(), timeout).await? else {
+        return Ok(false);
+    };
+    let changed = remote.body != current.body;
+    *REMOTE_CONFIG.write().expect("Remote config lock poisoned") = Some(remote);
+    Ok(changed)
+}
+
+/// Last fetched toml config, `None` when `CONFIG_URL` isn't used
+pub fn body() -> Option<String> {
+    REMOTE_CONFIG
+        .read()
+        .expect("Remote config lock poisoned")
+        .as_ref()
+        .map(|remote| remote.body.clone())
+}
+
+/// Fetch the config, `None` when it didn't change since `etag`
+async fn fetch(url: &str, etag: Option<&str>, timeout: Duration) -> Result<Option<RemoteConfig>> {
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+
+    Ok(Some(RemoteConfig {
+        url: url.to_string(),
+        etag,
+        body: response.text().await?,
+    }))
+}
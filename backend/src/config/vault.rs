@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+use serde_json::{Value, json};
+
+use crate::utils::secret::Secret;
+
+/// Fields of the Vault secret, fetched once at startup so `Config::try_load` stays
+/// synchronous
+static VAULT_SECRETS: RwLock<Option<HashMap<String, Secret<String>>>> = RwLock::new(None);
+
+/// Whether the secrets are read from Vault, enabled by `VAULT_ADDR`
+pub fn is_enabled() -> bool {
+    std::env::var("VAULT_ADDR").is_ok()
+}
+
+/// Field of the Vault secret, `None` when Vault isn't used or the field is missing
+pub fn secret(name: &str) -> Option<Secret<String>> {
+    VAULT_SECRETS
+        .read()
+        .expect("Vault secrets lock poisoned")
+        .as_ref()?
+        .get(name)
+        .cloned()
+}
+
+/// Read the KV v2 secret `VAULT_SECRET_PATH` of the `VAULT_KV_MOUNT` engine, to call
+/// once before the config is loaded
+///
+/// Authenticates with `VAULT_TOKEN`, or with AppRole when `VAULT_ROLE_ID` and
+/// `VAULT_SECRET_ID` are set instead.
+pub async fn init(timeout: Duration) -> Result<()> {
+    let Ok(address) = std::env::var("VAULT_ADDR") else {
+        return Ok(());
+    };
+    let address = Url::parse(&address).context("VAULT_ADDR must be a valid url")?;
+    let mount = std::env::var("VAULT_KV_MOUNT").unwrap_or_else(|_| "secret".to_string());
+    let path = std::env::var("VAULT_SECRET_PATH").context("VAULT_SECRET_PATH must be set")?;
+    let client = reqwest::Client::builder().timeout(timeout).build()?;
+
+    let token = match std::env::var("VAULT_TOKEN") {
+        Ok(token) => Secret::new(token),
+        Err(_) => approle_login(&client, &address).await?,
+    };
+
+    let url = address.join(&format!(
+        "v1/{}/data/{}",
+        mount.trim_matches('/'),
+        path.trim_matches('/')
+    ))?;
+    let response: Value = client
+        .get(url)
+        .header("X-Vault-Token", token.expose())
+        .send()
+        .await
+        .context("Unable to reach Vault")?
+        .error_for_status()
+        .with_context(|| format!("Unable to read the Vault secret {}/{}", mount, path))?
+        .json()
+        .await?;
+
+    let Some(fields) = response["data"]["data"].as_object() else {
+        bail!("The Vault secret {}/{} is not a KV v2 secret", mount, path);
+    };
+    let secrets = fields
+        .iter()
+        .filter_map(|(name, value)| Some((name.clone(), Secret::new(value.as_str()?.to_string()))))
+        .collect();
+    *VAULT_SECRETS.write().expect("Vault secrets lock poisoned") = Some(secrets);
+    Ok(())
+}
+
+async fn approle_login(client: &reqwest::Client, address: &Url) -> Result<Secret<String>> {
+    let (Ok(role_id), Ok(secret_id)) = (
+        std::env::var("VAULT_ROLE_ID"),
+        std::env::var("VAULT_SECRET_ID"),
+    ) else {
+        bail!("VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID, must be set");
+    };
+
+    let response: Value = client
+        .post(address.join("v1/auth/approle/login")?)
+        .json(&json!({ "role_id": role_id, "secret_id": secret_id }))
+        .send()
+        .await
+        .context("Unable to reach Vault")?
+        .error_for_status()
+        .context("Vault AppRole login failed")?
+        .json()
+        .await?;
+
+    response["auth"]["client_token"]
+        .as_str()
+        .map(|token| Secret::new(token.to_string()))
+        .context("Vault AppRole login returned no token")
+}
@@ -0,0 +1,130 @@
+use std::fmt::Write;
+
+use crate::config::{
+    ALERT_FOR_SECS, ALERT_HTTP_ERROR_RATIO, ALERT_POOL_REFRESH_FAILURE_RATIO,
+    ALERT_RATE_WINDOW_SECS, ALERT_RPC_FAILURE_RATIO, HEALTH_CHECK_TIMEOUT_SECS,
+    TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES,
+};
+
+/// Prometheus alerting rule on the metrics exposed by `/metrics`
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: &'static str,
+    pub expr: String,
+    pub for_secs: u64,
+    pub severity: &'static str,
+    pub summary: String,
+}
+
+/// Alerting rules matching the application thresholds
+pub fn alert_rules() -> Vec<AlertRule> {
+    let window = format!("{}s", ALERT_RATE_WINDOW_SECS);
+    let ratio = |failures: &str, total: &str| {
+        format!(
+            "sum(rate({failures}[{window}])) / sum(rate({total}[{window}]))",
+            failures = failures,
+            total = total,
+            window = window
+        )
+    };
+
+    vec![
+        AlertRule {
+            name: "YieldAiRpcFailureRate",
+            expr: format!(
+                "{} > {}",
+                ratio("rpc_failures_total", "rpc_requests_total"),
+                ALERT_RPC_FAILURE_RATIO
+            ),
+            for_secs: ALERT_FOR_SECS,
+            severity: "warning",
+            summary: format!(
+                "More than {}% of the blockchain RPC calls fail",
+                ALERT_RPC_FAILURE_RATIO * 100.0
+            ),
+        },
+        AlertRule {
+            name: "YieldAiRpcLatencyHigh",
+            expr: format!(
+                "histogram_quantile(0.95, sum by (le) (rate(rpc_request_duration_seconds_bucket[{}]))) > {}",
+                window, HEALTH_CHECK_TIMEOUT_SECS
+            ),
+            for_secs: ALERT_FOR_SECS,
+            severity: "warning",
+            summary: format!(
+                "95th percentile RPC latency above the {}s readiness timeout",
+                HEALTH_CHECK_TIMEOUT_SECS
+            ),
+        },
+        AlertRule {
+            name: "YieldAiHttpServerErrors",
+            expr: format!(
+                "{} > {}",
+                ratio(
+                    "http_requests_total{status=~\"5..\"}",
+                    "http_requests_total"
+                ),
+                ALERT_HTTP_ERROR_RATIO
+            ),
+            for_secs: ALERT_FOR_SECS,
+            severity: "critical",
+            summary: format!(
+                "More than {}% of the API requests fail with a server error",
+                ALERT_HTTP_ERROR_RATIO * 100.0
+            ),
+        },
+        AlertRule {
+            name: "YieldAiPoolRefreshFailures",
+            expr: format!(
+                "{} > {}",
+                ratio(
+                    "pool_refresh_total{result=\"failure\"}",
+                    "pool_refresh_total"
+                ),
+                ALERT_POOL_REFRESH_FAILURE_RATIO
+            ),
+            for_secs: ALERT_FOR_SECS,
+            severity: "warning",
+            summary: format!(
+                "More than {}% of the pool refreshes fail",
+                ALERT_POOL_REFRESH_FAILURE_RATIO * 100.0
+            ),
+        },
+        AlertRule {
+            name: "YieldAiTxFailSafeTripped",
+            expr: format!("increase(tx_failsafe_trips_total[{}]) > 0", window),
+            for_secs: 0,
+            severity: "critical",
+            summary: format!(
+                "{} transactions failed in a row, the signer switched to read-only until the fail-safe is reset",
+                TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES
+            ),
+        },
+        AlertRule {
+            name: "YieldAiPriceGuardTripped",
+            expr: format!("sum(increase(price_guard_trips_total[{}])) > 0", window),
+            for_secs: 0,
+            severity: "critical",
+            summary: "An execution was aborted because the spot price of its pool diverged from the TWAP or the tracked price, possible manipulation or stale data".to_string(),
+        },
+    ]
+}
+
+/// Render alerting rules as a Prometheus rule file
+pub fn render_prometheus_rules(rules: &[AlertRule]) -> String {
+    // JSON strings are valid YAML double quoted scalars, which saves escaping by hand
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+
+    let mut output = String::from("groups:\n  - name: yieldai\n    rules:\n");
+    for rule in rules {
+        let _ = writeln!(output, "      - alert: {}", rule.name);
+        let _ = writeln!(output, "        expr: {}", quote(&rule.expr));
+        let _ = writeln!(output, "        for: {}s", rule.for_secs);
+        let _ = writeln!(output, "        labels:");
+        let _ = writeln!(output, "          severity: {}", rule.severity);
+        let _ = writeln!(output, "        annotations:");
+        let _ = writeln!(output, "          summary: {}", quote(&rule.summary));
+    }
+
+    output
+}
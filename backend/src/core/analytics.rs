@@ -0,0 +1,515 @@
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{MAX_PRICE_HISTORY_SAMPLES, PRICE_HISTORY_UPDATES_CAPACITY},
+    types::{ChartSide, Pool},
+    utils,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct PriceSample {
+    timestamp: u64,
+    tick: i32,
+    price0: f64,
+}
+
+/// OHLC candle of a pool price
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Candle {
+    /// Unix timestamp (seconds) of the start of the candle
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Number of price samples in the candle
+    pub samples: usize,
+}
+
+/// Volatility statistics computed from a series of candles
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VolatilityStats {
+    pub interval_secs: u64,
+    /// Number of candles the statistics were computed from
+    pub candles: usize,
+    /// Standard deviation of the close to close log returns, per candle
+    pub realized_volatility: Option<f64>,
+    /// `realized_volatility` scaled to one year
+    pub annualized_volatility: Option<f64>,
+    /// Average true range, in price units
+    pub atr: Option<f64>,
+    /// `atr` relative to the last close
+    pub atr_ratio: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    /// `(high - low) / low`
+    pub range_ratio: Option<f64>,
+}
+
+impl VolatilityStats {
+    pub fn from_candles(candles: &[Candle], interval_secs: u64) -> Self {
+        let returns: Vec<f64> = candles
+            .windows(2)
+            .filter(|pair| pair[0].close > 0.0 && pair[1].close > 0.0)
+            .map(|pair| (pair[1].close / pair[0].close).ln())
+            .collect();
+        let realized_volatility = standard_deviation(&returns);
+        let periods_per_year = (365 * 24 * 60 * 60) as f64 / interval_secs as f64;
+
+        // The first candle has no previous close, its true range is its high-low range
+        let true_ranges: Vec<f64> = candles
+            .iter()
+            .enumerate()
+            .map(
+                |(index, candle)| match index.checked_sub(1).map(|i| candles[i].close) {
+                    Some(previous_close) => (candle.high - candle.low)
+                        .max((candle.high - previous_close).abs())
+                        .max((candle.low - previous_close).abs()),
+                    None => candle.high - candle.low,
+                },
+            )
+            .collect();
+        let atr = (!true_ranges.is_empty())
+            .then(|| true_ranges.iter().sum::<f64>() / true_ranges.len() as f64);
+        let last_close = candles.last().map(|candle| candle.close);
+
+        let high = candles.iter().map(|candle| candle.high).reduce(f64::max);
+        let low = candles.iter().map(|candle| candle.low).reduce(f64::min);
+
+        Self {
+            interval_secs,
+            candles: candles.len(),
+            realized_volatility,
+            annualized_volatility: realized_volatility
+                .map(|volatility| volatility * periods_per_year.sqrt()),
+            atr,
+            atr_ratio: atr
+                .zip(last_close)
+                .filter(|(_, close)| *close > 0.0)
+                .map(|(atr, close)| atr / close),
+            high,
+            low,
+            range_ratio: high
+                .zip(low)
+                .filter(|(_, low)| *low > 0.0)
+                .map(|(high, low)| (high - low) / low),
+        }
+    }
+}
+
+/// One metric to compute, each over its own trailing window
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "metric", rename_all = "snake_case")]
+pub enum MetricSpec {
+    /// Standard deviation of the price0 log returns between consecutive samples
+    Volatility { window_secs: u64 },
+    /// Relative change of price0 between the first and last sample
+    PriceChange { window_secs: u64 },
+    /// Simple moving average of price0
+    Sma { window_secs: u64 },
+    /// Number of ticks between the lowest and highest tick
+    TickRange { window_secs: u64 },
+}
+
+impl MetricSpec {
+    fn window_secs(&self) -> u64 {
+        match self {
+            MetricSpec::Volatility { window_secs }
+            | MetricSpec::PriceChange { window_secs }
+            | MetricSpec::Sma { window_secs }
+            | MetricSpec::TickRange { window_secs } => *window_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AnalyticsQuery {
+    /// Addresses of the pools to compute the metrics for
+    pub pools: Vec<String>,
+    pub metrics: Vec<MetricSpec>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MetricValue {
+    #[serde(flatten)]
+    pub spec: MetricSpec,
+    /// `None` when the window does not hold enough samples
+    pub value: Option<f64>,
+    /// Number of samples the value was computed from
+    pub samples: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolAnalytics {
+    pub address: String,
+    pub metrics: Vec<MetricValue>,
+}
+
+/// Price samples of each pool, taken every time its state is fetched
+#[derive(Debug)]
+pub struct PriceHistory {
+    samples: DashMap<String, VecDeque<PriceSample>>,
+    /// Broadcasts the address of a pool every time a sample of it is recorded
+    updates: broadcast::Sender<String>,
+}
+
+impl Default for PriceHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriceHistory {
+    pub fn new() -> Self {
+        let (updates, _) = broadcast::channel(PRICE_HISTORY_UPDATES_CAPACITY);
+        Self {
+            samples: DashMap::new(),
+            updates,
+        }
+    }
+
+    /// Get notified of the address of a pool every time a sample of it is recorded
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.updates.subscribe()
+    }
+
+    pub fn record(&self, address: &str, pool: &Pool) {
+        {
+            let mut samples = self.samples.entry(address.to_string()).or_default();
+            samples.push_back(PriceSample {
+                timestamp: pool.updated_at,
+                tick: pool.current_tick,
+                price0: pool.price0,
+            });
+            while samples.len() > MAX_PRICE_HISTORY_SAMPLES {
+                samples.pop_front();
+            }
+        }
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = self.updates.send(address.to_string());
+    }
+
+    /// Record a price sample taken from a swap, kept in timestamp order
+    pub fn record_swap(&self, address: &str, pool: &Pool, timestamp: u64, tick: i32) {
+        let Ok(price0) =
+            utils::amm_math::tick_to_price(tick, pool.token0.decimals, pool.token1.decimals)
+        else {
+            return;
+        };
+
+        {
+            let mut samples = self.samples.entry(address.to_string()).or_default();
+            let index = samples
+                .iter()
+                .rposition(|sample| sample.timestamp <= timestamp)
+                .map_or(0, |index| index + 1);
+            samples.insert(
+                index,
+                PriceSample {
+                    timestamp,
+                    tick,
+                    price0,
+                },
+            );
+            while samples.len() > MAX_PRICE_HISTORY_SAMPLES {
+                samples.pop_front();
+            }
+        }
+        let _ = self.updates.send(address.to_string());
+    }
+
+    /// Forget the history of a pool that is not tracked anymore
+    pub fn forget(&self, address: &str) {
+        self.samples.remove(address);
+    }
+
+    /// `(timestamp, tick)` of the samples of a pool taken since `since`, oldest first
+    pub fn ticks_since(&self, address: &str, since: u64) -> Vec<(u64, i32)> {
+        self.samples
+            .get(address)
+            .iter()
+            .flat_map(|samples| samples.iter())
+            .filter(|sample| sample.timestamp >= since)
+            .map(|sample| (sample.timestamp, sample.tick))
+            .collect()
+    }
+
+    /// `(timestamp, tick, price0)` of the last sample of a pool taken at or before
+    /// `timestamp`
+    pub fn sample_at(&self, address: &str, timestamp: u64) -> Option<(u64, i32, f64)> {
+        let samples = self.samples.get(address)?;
+        samples
+            .iter()
+            .rev()
+            .find(|sample| sample.timestamp <= timestamp)
+            .map(|sample| (sample.timestamp, sample.tick, sample.price0))
+    }
+
+    /// Aggregate the price samples of a pool into candles, oldest first
+    ///
+    /// # Arguments:
+    /// * `side` - Whether the candles show price0 or its inverse, price1
+    /// * `interval_secs` - Duration of a candle
+    /// * `limit` - Maximum number of candles, the most recent ones are kept
+    pub fn candles(
+        &self,
+        address: &str,
+        side: ChartSide,
+        interval_secs: u64,
+        limit: usize,
+    ) -> Vec<Candle> {
+        let Some(samples) = self.samples.get(address) else {
+            return Vec::new();
+        };
+
+        let mut candles: Vec<Candle> = Vec::new();
+        for sample in samples.iter() {
+            let price = match side {
+                ChartSide::Token0 => sample.price0,
+                ChartSide::Token1 if sample.price0 > 0.0 => 1.0 / sample.price0,
+                ChartSide::Token1 => continue,
+            };
+            let open_time = sample.timestamp - sample.timestamp % interval_secs;
+
+            match candles.last_mut() {
+                Some(candle) if candle.open_time == open_time => {
+                    candle.high = candle.high.max(price);
+                    candle.low = candle.low.min(price);
+                    candle.close = price;
+                    candle.samples += 1;
+                }
+                _ => candles.push(Candle {
+                    open_time,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    samples: 1,
+                }),
+            }
+        }
+
+        let skip = candles.len().saturating_sub(limit);
+        candles.split_off(skip)
+    }
+
+    /// Compute all the requested metrics of a pool in one pass over its history
+    pub fn compute(&self, address: &str, metrics: &[MetricSpec], now: u64) -> PoolAnalytics {
+        let samples = self.samples.get(address);
+
+        let metrics = metrics
+            .iter()
+            .map(|spec| {
+                let since = now.saturating_sub(spec.window_secs());
+                let window: Vec<&PriceSample> = samples
+                    .iter()
+                    .flat_map(|samples| samples.iter())
+                    .filter(|sample| sample.timestamp >= since)
+                    .collect();
+
+                let value = match spec {
+                    MetricSpec::Volatility { .. } => volatility(&window),
+                    MetricSpec::PriceChange { .. } => price_change(&window),
+                    MetricSpec::Sma { .. } => sma(&window),
+                    MetricSpec::TickRange { .. } => tick_range(&window),
+                };
+
+                MetricValue {
+                    spec: spec.clone(),
+                    value,
+                    samples: window.len(),
+                }
+            })
+            .collect();
+
+        PoolAnalytics {
+            address: address.to_string(),
+            metrics,
+        }
+    }
+}
+
+fn volatility(window: &[&PriceSample]) -> Option<f64> {
+    let returns: Vec<f64> = window
+        .windows(2)
+        .filter(|pair| pair[0].price0 > 0.0 && pair[1].price0 > 0.0)
+        .map(|pair| (pair[1].price0 / pair[0].price0).ln())
+        .collect();
+    standard_deviation(&returns)
+}
+
+/// Sample standard deviation, `None` with less than two values
+fn standard_deviation(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+fn price_change(window: &[&PriceSample]) -> Option<f64> {
+    let (first, last) = (window.first()?, window.last()?);
+    if window.len() < 2 || first.price0 == 0.0 {
+        return None;
+    }
+    Some((last.price0 - first.price0) / first.price0)
+}
+
+fn sma(window: &[&PriceSample]) -> Option<f64> {
+    if window.is_empty() {
+        return None;
+    }
+    Some(window.iter().map(|sample| sample.price0).sum::<f64>() / window.len() as f64)
+}
+
+fn tick_range(window: &[&PriceSample]) -> Option<f64> {
+    let min = window.iter().map(|sample| sample.tick).min()?;
+    let max = window.iter().map(|sample| sample.tick).max()?;
+    Some(f64::from(max - min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(samples: &[(u64, i32, f64)]) -> PriceHistory {
+        let history = PriceHistory::new();
+        history.samples.insert(
+            "pool".to_string(),
+            samples
+                .iter()
+                .map(|&(timestamp, tick, price0)| PriceSample {
+                    timestamp,
+                    tick,
+                    price0,
+                })
+                .collect(),
+        );
+        history
+    }
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle {
+            open_time: 0,
+            open,
+            high,
+            low,
+            close,
+            samples: 1,
+        }
+    }
+
+    #[test]
+    fn computes_the_sample_standard_deviation() {
+        let deviation = standard_deviation(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert!((deviation - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(standard_deviation(&[1.0]), None);
+        assert_eq!(standard_deviation(&[]), None);
+    }
+
+    #[test]
+    fn aggregates_the_samples_into_candles() {
+        let history = history(&[(0, 0, 1.0), (30, 0, 3.0), (59, 0, 2.0), (60, 0, 4.0)]);
+
+        assert_eq!(
+            history.candles("pool", ChartSide::Token0, 60, 10),
+            [
+                Candle {
+                    open_time: 0,
+                    open: 1.0,
+                    high: 3.0,
+                    low: 1.0,
+                    close: 2.0,
+                    samples: 3,
+                },
+                Candle {
+                    open_time: 60,
+                    open: 4.0,
+                    high: 4.0,
+                    low: 4.0,
+                    close: 4.0,
+                    samples: 1,
+                },
+            ]
+        );
+
+        let latest = history.candles("pool", ChartSide::Token1, 60, 1);
+        assert_eq!(latest.len(), 1);
+        assert_eq!((latest[0].open_time, latest[0].close), (60, 0.25));
+        assert!(
+            history
+                .candles("other", ChartSide::Token0, 60, 10)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn computes_each_metric_over_its_window() {
+        let history = history(&[(0, 100, 1.0), (50, 160, 2.0), (100, 130, 4.0)]);
+        let metrics = [
+            MetricSpec::PriceChange { window_secs: 100 },
+            MetricSpec::PriceChange { window_secs: 60 },
+            MetricSpec::Sma { window_secs: 100 },
+            MetricSpec::TickRange { window_secs: 100 },
+            MetricSpec::Volatility { window_secs: 100 },
+            MetricSpec::Volatility { window_secs: 0 },
+        ];
+
+        let analytics = history.compute("pool", &metrics, 100);
+        let values: Vec<_> = analytics
+            .metrics
+            .iter()
+            .map(|metric| (metric.value, metric.samples))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (Some(3.0), 3),
+                (Some(1.0), 2),
+                (Some(7.0 / 3.0), 3),
+                (Some(60.0), 3),
+                // Both log returns are ln(2)
+                (Some(0.0), 3),
+                (None, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn computes_the_volatility_of_the_candles() {
+        let candles = [
+            candle(1.0, 1.2, 0.9, 1.0),
+            candle(1.0, 2.5, 1.5, 2.0),
+            candle(2.0, 2.2, 0.8, 1.0),
+        ];
+        let stats = VolatilityStats::from_candles(&candles, 24 * 60 * 60);
+
+        assert_eq!(stats.candles, 3);
+        // Log returns of ln(2) and -ln(2)
+        let realized = stats.realized_volatility.unwrap();
+        assert!((realized - 2f64.ln() * 2f64.sqrt()).abs() < 1e-12);
+        assert!((stats.annualized_volatility.unwrap() - realized * 365f64.sqrt()).abs() < 1e-9);
+        // True ranges of 0.3, 1.5 (high to the previous close) and 1.4
+        assert!((stats.atr.unwrap() - 3.2 / 3.0).abs() < 1e-12);
+        assert!((stats.atr_ratio.unwrap() - 3.2 / 3.0).abs() < 1e-12);
+        assert_eq!((stats.high, stats.low), (Some(2.5), Some(0.8)));
+        assert!((stats.range_ratio.unwrap() - 1.7 / 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn has_no_volatility_without_candles() {
+        let stats = VolatilityStats::from_candles(&[], 60);
+        assert_eq!(stats.candles, 0);
+        assert_eq!(stats.realized_volatility, None);
+        assert_eq!(stats.atr, None);
+        assert_eq!(stats.range_ratio, None);
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{ANNOTATIONS_PATH, MAX_ANNOTATION_LABELS, MAX_LABEL_LENGTH, MAX_NOTE_LENGTH},
+    utils::json_file,
+};
+
+/// Operator context attached to a position or a pool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Annotation {
+    pub labels: Vec<String>,
+    pub note: Option<String>,
+}
+
+/// Changes to an annotation, the omitted fields are left untouched
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AnnotationUpdate {
+    /// Replaces all the labels, an empty list removes them
+    pub labels: Option<Vec<String>>,
+    /// Replaces the note, an empty note removes it
+    pub note: Option<String>,
+}
+
+impl AnnotationUpdate {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(labels) = &self.labels {
+            if labels.len() > MAX_ANNOTATION_LABELS {
+                bail!("At most {} labels are allowed", MAX_ANNOTATION_LABELS);
+            }
+            if labels
+                .iter()
+                .any(|label| label.trim().chars().count() > MAX_LABEL_LENGTH)
+            {
+                bail!("Labels must be at most {} characters", MAX_LABEL_LENGTH);
+            }
+        }
+        if let Some(note) = &self.note
+            && note.trim().chars().count() > MAX_NOTE_LENGTH
+        {
+            bail!("The note must be at most {} characters", MAX_NOTE_LENGTH);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredAnnotations {
+    /// Token ID → annotation
+    positions: BTreeMap<String, Annotation>,
+    /// Pool address → annotation
+    pools: BTreeMap<String, Annotation>,
+}
+
+/// Annotations of the positions and pools, persisted to `ANNOTATIONS_PATH` so they
+/// survive restarts
+#[derive(Debug, Default)]
+pub struct Annotations {
+    stored: Mutex<StoredAnnotations>,
+}
+
+impl Annotations {
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            stored: Mutex::new(json_file::load(ANNOTATIONS_PATH)?),
+        })
+    }
+
+    pub fn position(&self, token_id: &str) -> Option<Annotation> {
+        let stored = self.stored.lock().expect("Annotations lock poisoned");
+        stored.positions.get(token_id).cloned()
+    }
+
+    pub fn pool(&self, address: &str) -> Option<Annotation> {
+        let stored = self.stored.lock().expect("Annotations lock poisoned");
+        stored.pools.get(address).cloned()
+    }
+
+    /// Apply a validated update to the annotation of a position and persist it
+    ///
+    /// # Returns:
+    /// * The annotation after the update, `None` once it has no label nor note left
+    pub fn update_position(
+        &self,
+        token_id: &str,
+        update: AnnotationUpdate,
+    ) -> Result<Option<Annotation>> {
+        let mut stored = self.stored.lock().expect("Annotations lock poisoned");
+        let annotation = apply_update(&mut stored.positions, token_id, update);
+        json_file::save(ANNOTATIONS_PATH, &*stored)?;
+        Ok(annotation)
+    }
+
+    /// Apply a validated update to the annotation of a pool and persist it
+    ///
+    /// # Returns:
+    /// * The annotation after the update, `None` once it has no label nor note left
+    pub fn update_pool(
+        &self,
+        address: &str,
+        update: AnnotationUpdate,
+    ) -> Result<Option<Annotation>> {
+        let mut stored = self.stored.lock().expect("Annotations lock poisoned");
+        let annotation = apply_update(&mut stored.pools, address, update);
+        json_file::save(ANNOTATIONS_PATH, &*stored)?;
+        Ok(annotation)
+    }
+}
+
+fn apply_update(
+    annotations: &mut BTreeMap<String, Annotation>,
+    key: &str,
+    update: AnnotationUpdate,
+) -> Option<Annotation> {
+    let mut annotation = annotations.get(key).cloned().unwrap_or_default();
+
+    if let Some(labels) = update.labels {
+        annotation.labels.clear();
+        for label in labels {
+            let label = label.trim();
+            if !label.is_empty() && !annotation.labels.iter().any(|known| known == label) {
+                annotation.labels.push(label.to_string());
+            }
+        }
+    }
+
+    if let Some(note) = update.note {
+        let note = note.trim();
+        annotation.note = (!note.is_empty()).then(|| note.to_string());
+    }
+
+    if annotation == Annotation::default() {
+        annotations.remove(key);
+        return None;
+    }
+
+    annotations.insert(key.to_string(), annotation.clone());
+    Some(annotation)
+}
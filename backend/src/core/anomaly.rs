@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use dashmap::DashMap;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{ANOMALY_LIQUIDITY_DROP_RATIO, ANOMALY_MIN_SAMPLES, MAX_STORED_ANOMALIES},
+    types::Pool,
+    utils::time::unix_timestamp,
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The tick moved more between two snapshots than it ever did before
+    TickJump { jump: u32, historical_max: u32 },
+    /// The in-range liquidity dropped by more than the configured ratio in one interval
+    LiquidityDrop {
+        #[schema(value_type = String)]
+        #[serde(with = "crate::types::u128_string")]
+        previous: u128,
+        #[schema(value_type = String)]
+        #[serde(with = "crate::types::u128_string")]
+        current: u128,
+        drop_ratio: f64,
+    },
+    /// The fee tier of the pool changed
+    FeeChange { previous: f64, current: f64 },
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolAnomaly {
+    pub address: String,
+    #[serde(flatten)]
+    pub kind: AnomalyKind,
+    /// Unix timestamp (seconds) of the detection
+    pub detected_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TickJumpStats {
+    max_jump: u32,
+    samples: u32,
+}
+
+/// Compares consecutive snapshots of each pool and flags suspicious changes,
+/// which can reveal exploits or data issues early
+#[derive(Debug, Default)]
+pub struct AnomalyDetector {
+    tick_jumps: DashMap<String, TickJumpStats>,
+    /// Most recent anomalies, oldest first
+    recent: Mutex<VecDeque<PoolAnomaly>>,
+}
+
+impl AnomalyDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Diff two consecutive snapshots of a pool, recording and returning the anomalies found
+    pub fn inspect(&self, address: &str, previous: &Pool, current: &Pool) -> Vec<PoolAnomaly> {
+        let mut kinds = Vec::new();
+
+        let jump = previous.current_tick.abs_diff(current.current_tick);
+        let mut stats = self.tick_jumps.entry(address.to_string()).or_default();
+        // Jumps are only compared once there is enough history to know what is normal
+        if stats.samples >= ANOMALY_MIN_SAMPLES && jump > stats.max_jump {
+            kinds.push(AnomalyKind::TickJump {
+                jump,
+                historical_max: stats.max_jump,
+            });
+        }
+        stats.max_jump = stats.max_jump.max(jump);
+        stats.samples += 1;
+        drop(stats);
+
+        if previous.liquidity > 0 && current.liquidity < previous.liquidity {
+            let drop_ratio =
+                (previous.liquidity - current.liquidity) as f64 / previous.liquidity as f64;
+            if drop_ratio > ANOMALY_LIQUIDITY_DROP_RATIO {
+                kinds.push(AnomalyKind::LiquidityDrop {
+                    previous: previous.liquidity,
+                    current: current.liquidity,
+                    drop_ratio,
+                });
+            }
+        }
+
+        if previous.fee != current.fee {
+            kinds.push(AnomalyKind::FeeChange {
+                previous: previous.fee,
+                current: current.fee,
+            });
+        }
+
+        let detected_at = unix_timestamp();
+        let anomalies: Vec<PoolAnomaly> = kinds
+            .into_iter()
+            .map(|kind| PoolAnomaly {
+                address: address.to_string(),
+                kind,
+                detected_at,
+            })
+            .collect();
+
+        if !anomalies.is_empty() {
+            let mut recent = self.recent.lock().expect("Anomalies lock poisoned");
+            recent.extend(anomalies.iter().cloned());
+            while recent.len() > MAX_STORED_ANOMALIES {
+                recent.pop_front();
+            }
+        }
+
+        anomalies
+    }
+
+    /// Forget the history of a pool that is not tracked anymore
+    pub fn forget(&self, address: &str) {
+        self.tick_jumps.remove(address);
+    }
+
+    /// Most recent anomalies, newest first, optionally only for one pool
+    pub fn recent(&self, address: Option<&str>) -> Vec<PoolAnomaly> {
+        let recent = self.recent.lock().expect("Anomalies lock poisoned");
+        recent
+            .iter()
+            .rev()
+            .filter(|anomaly| address.is_none_or(|address| anomaly.address == address))
+            .cloned()
+            .collect()
+    }
+}
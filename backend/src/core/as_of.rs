@@ -0,0 +1,59 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    core::{executions::Execution, snapshots::PositionSnapshot},
+    state::AppState,
+};
+
+/// Price of a pool at a point in time
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PricePoint {
+    /// When the price sample was taken, at or before the requested timestamp
+    pub timestamp: u64,
+    pub tick: i32,
+    pub price0: f64,
+}
+
+/// State of a pool and of its managed positions at a past timestamp, rebuilt from what
+/// the backend recorded
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolAsOf {
+    pub address: String,
+    pub timestamp: u64,
+    /// Last price sample of the pool, absent when the timestamp is older than the price
+    /// history kept in memory. The snapshots and executions carry the price they saw too
+    pub price: Option<PricePoint>,
+    /// Last snapshot of each position of the pool, the positions closed by then excluded
+    pub positions: Vec<PositionSnapshot>,
+    /// Last execution on the pool at or before the timestamp
+    pub last_execution: Option<Execution>,
+    /// First execution on the pool after the timestamp
+    pub next_execution: Option<Execution>,
+}
+
+/// Rebuild the state of a pool at `timestamp` from the price history, the position
+/// snapshots and the execution ledger
+pub fn pool_as_of(app_state: &AppState, address: &str, timestamp: u64) -> PoolAsOf {
+    let price =
+        app_state
+            .price_history
+            .sample_at(address, timestamp)
+            .map(|(timestamp, tick, price0)| PricePoint {
+                timestamp,
+                tick,
+                price0,
+            });
+
+    let executions = app_state.executions.for_pool(address);
+    let split = executions.partition_point(|execution| execution.timestamp <= timestamp);
+
+    PoolAsOf {
+        address: address.to_string(),
+        timestamp,
+        price,
+        positions: app_state.snapshots.for_pool_as_of(address, timestamp),
+        last_execution: split.checked_sub(1).map(|index| executions[index].clone()),
+        next_execution: executions.get(split).cloned(),
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use dashmap::{DashMap, Entry};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    config::{JWT_TTL_SECS, PASSWORD_HASH_ITERATIONS},
+    utils::{secret::Secret, time::unix_timestamp},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub username: String,
+    /// `pbkdf2-sha256$<iterations>$<salt>$<hash>`, salt and hash base64 encoded
+    password_hash: Secret<String>,
+    pub created_at: u64,
+}
+
+/// Claims carried by the issued JWTs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Username of the authenticated user
+    pub sub: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+/// User registry and JWT (HS256) issuance/validation
+///
+/// Users are kept in memory, so they have to register again after a restart.
+#[derive(Debug)]
+pub struct AuthService {
+    secret: Secret<Vec<u8>>,
+    users: DashMap<String, User>,
+}
+
+impl AuthService {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret: Secret::new(secret),
+            users: DashMap::new(),
+        }
+    }
+
+    /// Register a new user, failing if the username is already taken
+    pub fn register(&self, username: &str, password: &str) -> Result<User> {
+        match self.users.entry(username.to_string()) {
+            Entry::Occupied(_) => bail!("Username already taken"),
+            Entry::Vacant(entry) => {
+                let user = User {
+                    username: username.to_string(),
+                    password_hash: Secret::new(hash_password(password)),
+                    created_at: unix_timestamp(),
+                };
+                entry.insert(user.clone());
+                Ok(user)
+            }
+        }
+    }
+
+    pub fn user(&self, username: &str) -> Option<User> {
+        self.users.get(username).map(|user| user.clone())
+    }
+
+    /// Check the credentials of a user and issue a token for them
+    ///
+    /// # Returns:
+    /// * `Ok((token, claims))` on valid credentials, an error otherwise
+    pub fn login(&self, username: &str, password: &str) -> Result<(String, Claims)> {
+        let user = self
+            .users
+            .get(username)
+            .ok_or_else(|| anyhow!("Invalid credentials"))?;
+
+        if !verify_password(password, user.password_hash.expose()) {
+            bail!("Invalid credentials");
+        }
+
+        let iat = unix_timestamp();
+        let claims = Claims {
+            sub: user.username.clone(),
+            iat,
+            exp: iat + JWT_TTL_SECS,
+        };
+
+        Ok((self.issue_token(&claims)?, claims))
+    }
+
+    /// Check the signature and expiry of a token and return its claims
+    pub fn validate_token(&self, token: &str) -> Result<Claims> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Malformed token");
+        };
+
+        let decoded_header: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        if decoded_header["alg"] != "HS256" {
+            bail!("Unsupported token algorithm");
+        }
+
+        let mut mac = self.mac();
+        mac.update(format!("{}.{}", header, payload).as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature)?)
+            .map_err(|_| anyhow!("Invalid token signature"))?;
+
+        let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+        if claims.exp <= unix_timestamp() {
+            bail!("Token expired");
+        }
+
+        Ok(claims)
+    }
+
+    fn issue_token(&self, claims: &Claims) -> Result<String> {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+        let signing_input = format!("{}.{}", header_segment(), payload);
+
+        let mut mac = self.mac();
+        mac.update(signing_input.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        Ok(format!("{}.{}", signing_input, signature))
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(self.secret.expose()).expect("HMAC accepts keys of any length")
+    }
+}
+
+/// Generate a random secret, used when no JWT secret is configured
+pub fn random_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    secret
+}
+
+fn header_segment() -> String {
+    URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#)
+}
+
+fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+
+    let hash = pbkdf2_sha256(password.as_bytes(), &salt, PASSWORD_HASH_ITERATIONS);
+
+    format!(
+        "pbkdf2-sha256${}${}${}",
+        PASSWORD_HASH_ITERATIONS,
+        URL_SAFE_NO_PAD.encode(salt),
+        URL_SAFE_NO_PAD.encode(hash)
+    )
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    let parts: Vec<&str> = password_hash.split('$').collect();
+    let ["pbkdf2-sha256", iterations, salt, hash] = parts.as_slice() else {
+        return false;
+    };
+    let (Ok(iterations), Ok(salt), Ok(hash)) = (
+        iterations.parse::<u32>(),
+        URL_SAFE_NO_PAD.decode(salt),
+        URL_SAFE_NO_PAD.decode(hash),
+    ) else {
+        return false;
+    };
+
+    // Compare through the MAC so the check runs in constant time
+    let computed = pbkdf2_sha256(password.as_bytes(), &salt, iterations);
+    let mut mac = HmacSha256::new_from_slice(&salt).expect("HMAC accepts keys of any length");
+    mac.update(&computed);
+    let mut expected = HmacSha256::new_from_slice(&salt).expect("HMAC accepts keys of any length");
+    expected.update(&hash);
+    mac.verify_slice(&expected.finalize().into_bytes()).is_ok()
+}
+
+/// PBKDF2 with HMAC-SHA256, producing a single 32 bytes block (RFC 8018)
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let prf = HmacSha256::new_from_slice(password).expect("HMAC accepts keys of any length");
+
+    let mut mac = prf.clone();
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+    let mut block: [u8; 32] = mac.finalize().into_bytes().into();
+    let mut result = block;
+
+    for _ in 1..iterations {
+        let mut mac = prf.clone();
+        mac.update(&block);
+        block = mac.finalize().into_bytes().into();
+        result
+            .iter_mut()
+            .zip(block.iter())
+            .for_each(|(r, b)| *r ^= b);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::hex;
+
+    use super::*;
+
+    fn service() -> AuthService {
+        AuthService::new(b"test secret".to_vec())
+    }
+
+    fn claims(exp: u64) -> Claims {
+        Claims {
+            sub: "alice".to_string(),
+            iat: 0,
+            exp,
+        }
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let cases: [(&[u8], &[u8], &str); 3] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+        ];
+
+        for (key, data, expected) in cases {
+            let mut mac = HmacSha256::new_from_slice(key).unwrap();
+            mac.update(data);
+            assert_eq!(hex::encode(mac.finalize().into_bytes()), expected);
+        }
+    }
+
+    #[test]
+    fn pbkdf2_sha256_matches_the_rfc_6070_inputs() {
+        // RFC 6070 only lists SHA-1 outputs, these are the SHA-256 ones for its inputs
+        let cases: [(&[u8], &[u8], u32, &str); 4] = [
+            (
+                b"password",
+                b"salt",
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            ),
+            (
+                b"password",
+                b"salt",
+                2,
+                "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43",
+            ),
+            (
+                b"password",
+                b"salt",
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1",
+            ),
+        ];
+
+        for (password, salt, iterations, expected) in cases {
+            assert_eq!(
+                hex::encode(pbkdf2_sha256(password, salt, iterations)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn verifies_only_the_hashed_password() {
+        let hash = format!(
+            "pbkdf2-sha256$2${}${}",
+            URL_SAFE_NO_PAD.encode(b"salt"),
+            URL_SAFE_NO_PAD.encode(pbkdf2_sha256(b"password", b"salt", 2))
+        );
+
+        assert!(verify_password("password", &hash));
+        assert!(!verify_password("Password", &hash));
+        assert!(!verify_password("password", "sha1$2$c2FsdA$AAAA"));
+    }
+
+    #[test]
+    fn validates_its_own_tokens() {
+        let auth = service();
+        let token = auth.issue_token(&claims(unix_timestamp() + 60)).unwrap();
+
+        assert_eq!(auth.validate_token(&token).unwrap().sub, "alice");
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let auth = service();
+        let token = auth.issue_token(&claims(unix_timestamp() - 1)).unwrap();
+
+        assert_eq!(
+            auth.validate_token(&token).unwrap_err().to_string(),
+            "Token expired"
+        );
+    }
+
+    #[test]
+    fn rejects_tokens_signed_with_another_secret() {
+        let other = AuthService::new(b"other secret".to_vec());
+        let token = other.issue_token(&claims(unix_timestamp() + 60)).unwrap();
+
+        assert_eq!(
+            service().validate_token(&token).unwrap_err().to_string(),
+            "Invalid token signature"
+        );
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let auth = service();
+        let token = auth.issue_token(&claims(unix_timestamp() + 60)).unwrap();
+        let [_, payload, signature] = token.split('.').collect::<Vec<_>>()[..] else {
+            panic!("Malformed issued token");
+        };
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none","typ":"JWT"}"#);
+
+        for token in [
+            format!("{}.{}.", header, payload),
+            format!("{}.{}.{}", header, payload, signature),
+        ] {
+            assert_eq!(
+                auth.validate_token(&token).unwrap_err().to_string(),
+                "Unsupported token algorithm"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        assert!(service().validate_token("a.b").is_err());
+        assert!(service().validate_token("a.b.c.d").is_err());
+    }
+}
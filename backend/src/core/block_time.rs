@@ -0,0 +1,93 @@
+use alloy::{eips::BlockNumberOrTag, providers::Provider};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{config::BLOCK_TIME_SAMPLE_BLOCKS, core::metrics::Metrics, types::EvmProvider};
+
+/// Average block time of the chain over the most recent blocks
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BlockTimeEstimate {
+    pub chain_id: u64,
+    pub latest_block: u64,
+    /// Unix timestamp (seconds) of the latest block
+    pub latest_timestamp: u64,
+    /// Number of blocks the average is computed over
+    pub sample_blocks: u64,
+    pub avg_block_time_secs: f64,
+}
+
+/// A user facing duration converted into block based and timestamp based deadlines
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BlockDeadline {
+    pub within_secs: u64,
+    /// Number of blocks expected to be produced within the duration
+    pub blocks: u64,
+    /// Block number after which the deadline is considered passed
+    pub block_number: u64,
+    /// Unix timestamp (seconds) usable as a transaction `deadline` parameter
+    pub timestamp: u64,
+}
+
+impl BlockTimeEstimate {
+    /// Convert a duration ("within 2 minutes") into block and timestamp deadlines
+    pub fn deadline(&self, within_secs: u64) -> BlockDeadline {
+        // Round up so the deadline is never shorter than requested
+        let blocks = (within_secs as f64 / self.avg_block_time_secs).ceil() as u64;
+
+        BlockDeadline {
+            within_secs,
+            blocks,
+            block_number: self.latest_block + blocks,
+            timestamp: self.latest_timestamp + within_secs,
+        }
+    }
+}
+
+/// Estimate the current block time as a rolling average over the last blocks headers
+pub async fn estimate_block_time(
+    evm_provider: &EvmProvider,
+    metrics: &Metrics,
+) -> Result<BlockTimeEstimate> {
+    let latest = metrics
+        .track_rpc(
+            "eth_getBlockByNumber",
+            evm_provider.get_block_by_number(BlockNumberOrTag::Latest),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Latest block not found"))?;
+
+    let latest_block = latest.header.number;
+    let sample_blocks = BLOCK_TIME_SAMPLE_BLOCKS.min(latest_block);
+    if sample_blocks == 0 {
+        return Err(anyhow!("Not enough blocks to estimate the block time"));
+    }
+
+    let oldest = metrics
+        .track_rpc(
+            "eth_getBlockByNumber",
+            evm_provider
+                .get_block_by_number(BlockNumberOrTag::Number(latest_block - sample_blocks)),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("Block {} not found", latest_block - sample_blocks))?;
+
+    let elapsed_secs = latest
+        .header
+        .timestamp
+        .saturating_sub(oldest.header.timestamp);
+
+    // Timestamps only have a one second resolution, don't report sub-resolution precision
+    // as a zero block time on chains producing several blocks per second
+    let avg_block_time_secs = (elapsed_secs.max(1) as f64) / sample_blocks as f64;
+
+    Ok(BlockTimeEstimate {
+        chain_id: metrics
+            .track_rpc("eth_chainId", evm_provider.get_chain_id())
+            .await?,
+        latest_block,
+        latest_timestamp: latest.header.timestamp,
+        sample_blocks,
+        avg_block_time_secs,
+    })
+}
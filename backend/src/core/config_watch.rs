@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use actix_web::rt;
+use anyhow::Result;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{
+        CONFIG, CONFIG_WATCH_INTERVAL_SECS, Config, PoolConfig, profile::profile_config_path,
+        remote, toml_config_path,
+    },
+    core,
+    state::AppState,
+    utils::log_sampling::LOG_SAMPLER,
+};
+
+/// Reload the configuration and push its changes into the app state
+///
+/// Only the pools added to or removed from the toml file are tracked or untracked, the
+/// pools registered or removed through the API are left untouched. A pool whose details
+/// can't be fetched is skipped until it is removed from the file and added again, or
+/// registered through the API.
+pub async fn reload_config(app_state: &AppState) -> Result<()> {
+    let previous = CONFIG.get();
+    let config = CONFIG.reload()?;
+    LOG_SAMPLER.set_rates(config.log_sampling.clone());
+    app_state.apply_strategies(&config);
+    info!("Config reloaded");
+
+    sync_pools(app_state, &previous, &config).await;
+    Ok(())
+}
+
+async fn sync_pools(app_state: &AppState, previous: &Config, config: &Config) {
+    let previous_pools: HashSet<String> = previous.toml.pools.iter().map(PoolConfig::key).collect();
+    let pools: HashSet<String> = config.toml.pools.iter().map(PoolConfig::key).collect();
+
+    for address in previous_pools.difference(&pools) {
+        app_state.untrack_pool(address);
+    }
+
+    for pool_config in &config.toml.pools {
+        let address = pool_config.key();
+        // A pool already in the file may have been removed through the API on purpose
+        if previous_pools.contains(&address) || app_state.pools.contains_key(&address) {
+            continue;
+        }
+
+        match core::pools::fetch_pool_blockchain_details(
+            &app_state.evm_provider,
+            &app_state.metrics,
+            &address,
+            &pool_config.dex_type,
+        )
+        .await
+        {
+            Ok(pool) => {
+                app_state.track_pool(address, pool);
+            }
+            Err(e) => error!("Failed to fetch details of pool {}: {}", address, e),
+        }
+    }
+}
+
+fn modified_at(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Reload the configuration every time the toml file or its `APP_ENV` profile changes,
+/// checking their modification time every `CONFIG_WATCH_INTERVAL_SECS`
+pub fn spawn_config_watcher(app_state: Arc<AppState>) {
+    if let Some(url) = remote::config_url() {
+        spawn_remote_config_refresher(app_state, url);
+        return;
+    }
+    let Some(path) = toml_config_path() else {
+        info!("Using the embedded config, there is no config file to watch");
+        return;
+    };
+    info!(
+        "Watching {} for changes every {}s",
+        path, CONFIG_WATCH_INTERVAL_SECS
+    );
+
+    let profile = CONFIG.get().app_env.and_then(profile_config_path);
+    if let Some(profile) = &profile {
+        info!("Watching the {} profile too", profile);
+    }
+
+    rt::spawn(async move {
+        let profile_modified = || profile.as_deref().and_then(modified_at);
+        let mut last_modified = modified_at(&path);
+        let mut last_profile_modified = profile_modified();
+        let mut interval = rt::time::interval(Duration::from_secs(CONFIG_WATCH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+
+            let modified = modified_at(&path);
+            let profile_modified = profile_modified();
+            if modified == last_modified && profile_modified == last_profile_modified {
+                continue;
+            }
+            last_profile_modified = profile_modified;
+            // An editor may briefly remove the file while saving it
+            let Some(modified_time) = modified else {
+                warn!("Config file {} is unreadable", path);
+                last_modified = None;
+                continue;
+            };
+            last_modified = Some(modified_time);
+
+            info!("Config file {} or its profile changed, reloading it", path);
+            if let Err(e) = reload_config(&app_state).await {
+                error!("Failed to reload config: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Reload the configuration every time the toml config of `CONFIG_URL` changes, checking
+/// its ETag every `CONFIG_URL_REFRESH_SECS`
+fn spawn_remote_config_refresher(app_state: Arc<AppState>, url: String) {
+    let url = remote::redacted_url(&url);
+    let Some(refresh_secs) = CONFIG.get().config_refresh_secs else {
+        info!(
+            "CONFIG_URL_REFRESH_SECS is unset, the config of {} is never refreshed",
+            url
+        );
+        return;
+    };
+    info!("Checking {} for changes every {}s", url, refresh_secs);
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(refresh_secs));
+        // The first tick completes immediately, the config was just fetched
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+
+            let timeout = Duration::from_secs(CONFIG.get().toml.runtime.http_timeout_secs);
+            match remote::refresh(timeout).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    info!("Config of {} changed, reloading it", url);
+                    if let Err(e) = reload_config(&app_state).await {
+                        error!("Failed to reload config: {:#}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to refresh the config of {}: {:#}", url, e),
+            }
+        }
+    });
+}
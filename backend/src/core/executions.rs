@@ -0,0 +1,264 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use alloy::{
+    primitives::{Address, U256},
+    rpc::types::TransactionReceipt,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::EXECUTIONS_PATH,
+    types::{Pool, Position, u128_string},
+    utils::{json_file, time::unix_timestamp},
+};
+
+/// Decimals of the native token the gas is paid in
+const NATIVE_DECIMALS: i32 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionKind {
+    Mint,
+    Rebalance,
+    Collect,
+    Close,
+}
+
+/// Swap done by a rebalance, as reported by the Yield contract
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExecutedSwap {
+    /// Whether token0 was sold for token1
+    pub zero_for_one: bool,
+    /// Sold amount, in the smallest unit of the sold token
+    pub amount_in: String,
+    /// Received amount, in the smallest unit of the bought token
+    pub amount_out: String,
+}
+
+/// Transaction mined for a position of a tracked pool
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Execution {
+    pub timestamp: u64,
+    pub pool: String,
+    pub kind: ExecutionKind,
+    pub tx_hash: String,
+    pub token_id: String,
+    /// Gas paid, in wei of the native token
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub gas_cost: u128,
+    /// Price0 of the pool when the transaction was sent
+    pub price0: f64,
+    /// Collected fees of token0, in its smallest unit
+    pub fees0: String,
+    /// Collected fees of token1, in its smallest unit
+    pub fees1: String,
+    /// Principal of token0 deposited by a mint or withdrawn by a close, fees excluded,
+    /// in its smallest unit
+    pub amount0: String,
+    /// Principal of token1 deposited by a mint or withdrawn by a close, fees excluded,
+    /// in its smallest unit
+    pub amount1: String,
+    pub swap: Option<ExecutedSwap>,
+}
+
+impl Execution {
+    /// Execution of a mined transaction, without collected fees, principal nor swap
+    pub fn new(
+        pool: &Pool,
+        kind: ExecutionKind,
+        receipt: &TransactionReceipt,
+        token_id: &str,
+    ) -> Self {
+        Self {
+            timestamp: unix_timestamp(),
+            pool: pool.address.to_lowercase(),
+            kind,
+            tx_hash: receipt.transaction_hash.to_string(),
+            token_id: token_id.to_string(),
+            gas_cost: receipt.gas_used as u128 * receipt.effective_gas_price,
+            price0: pool.price0,
+            fees0: "0".to_string(),
+            fees1: "0".to_string(),
+            amount0: "0".to_string(),
+            amount1: "0".to_string(),
+            swap: None,
+        }
+    }
+}
+
+/// Executions of the positions, persisted to `EXECUTIONS_PATH` so the profitability
+/// reports cover the whole history of the pools
+#[derive(Debug, Default)]
+pub struct ExecutionLedger {
+    executions: Mutex<Vec<Execution>>,
+}
+
+impl ExecutionLedger {
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            executions: Mutex::new(json_file::load(EXECUTIONS_PATH)?),
+        })
+    }
+
+    /// Append an execution and persist the ledger
+    pub fn record(&self, execution: Execution) -> Result<()> {
+        let mut executions = self
+            .executions
+            .lock()
+            .expect("Execution ledger lock poisoned");
+        executions.push(execution);
+        json_file::save(EXECUTIONS_PATH, &*executions)
+    }
+
+    /// Executions of a pool, oldest first
+    pub fn for_pool(&self, address: &str) -> Vec<Execution> {
+        let executions = self
+            .executions
+            .lock()
+            .expect("Execution ledger lock poisoned");
+        executions
+            .iter()
+            .filter(|execution| execution.pool == address)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Whether the automation of a pool earned more than it cost over a period, all the
+/// values being in token1 units unless stated otherwise
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolProfitability {
+    pub address: String,
+    pub from: u64,
+    pub to: u64,
+    pub executions: usize,
+    /// Fees collected over the period, in token0 units
+    pub collected_fees0: f64,
+    /// Fees collected over the period, in token1 units
+    pub collected_fees1: f64,
+    /// Fees collectable right now on the open positions, in token0 units. Only counted
+    /// when the period ends now
+    pub uncollected_fees0: f64,
+    /// Fees collectable right now on the open positions, in token1 units. Only counted
+    /// when the period ends now
+    pub uncollected_fees1: f64,
+    /// Value of the collected and uncollected fees, at the price of their collection
+    pub fee_income: f64,
+    /// The pools have no farming rewards, kept so the report reads the same once they do
+    pub reward_income: f64,
+    /// Value lost by the positions closed over the period compared to holding their
+    /// deposit, at the closing price. Only known for the positions minted and closed
+    /// by this backend, a rebalance reopens a position with an unknown deposit
+    pub impermanent_loss: f64,
+    /// Value lost by the rebalance swaps compared to the pool price, swap fee included
+    pub slippage: f64,
+    /// Gas paid, in native token units
+    pub gas_spent: f64,
+    /// Value of the gas paid, only known when the pool pairs the wrapped native token
+    pub gas_value: Option<f64>,
+    /// Fee and reward income minus the impermanent loss, slippage and, when its value is
+    /// known, gas
+    pub net_income: f64,
+}
+
+impl PoolProfitability {
+    /// Sum the executions of `pool` mined between `from` and `to`
+    ///
+    /// # Arguments:
+    /// * `executions` - All the executions of the pool, oldest first
+    /// * `open_positions` - Positions of the pool whose uncollected fees are counted
+    /// * `wrapped_native` - Address of the wrapped native token, to value the gas
+    pub fn new(
+        pool: &Pool,
+        executions: &[Execution],
+        open_positions: &[Position],
+        wrapped_native: Option<Address>,
+        from: u64,
+        to: u64,
+    ) -> Self {
+        let scale0 = 10f64.powi(pool.token0.decimals as i32);
+        let scale1 = 10f64.powi(pool.token1.decimals as i32);
+        let amount = |raw: &str| U256::from_str(raw).map(f64::from).unwrap_or(0.0);
+        let is_native =
+            |token: &str| Address::from_str(token).is_ok_and(|token| Some(token) == wrapped_native);
+        let (native0, native1) = (
+            is_native(&pool.token0.address),
+            is_native(&pool.token1.address),
+        );
+
+        let mut report = Self {
+            address: pool.address.clone(),
+            from,
+            to,
+            executions: 0,
+            collected_fees0: 0.0,
+            collected_fees1: 0.0,
+            uncollected_fees0: 0.0,
+            uncollected_fees1: 0.0,
+            fee_income: 0.0,
+            reward_income: 0.0,
+            impermanent_loss: 0.0,
+            slippage: 0.0,
+            gas_spent: 0.0,
+            gas_value: (native0 || native1).then_some(0.0),
+            net_income: 0.0,
+        };
+
+        for execution in executions
+            .iter()
+            .filter(|execution| execution.timestamp >= from && execution.timestamp <= to)
+        {
+            let price0 = execution.price0;
+            report.executions += 1;
+            let gas = execution.gas_cost as f64 / 10f64.powi(NATIVE_DECIMALS);
+            report.gas_spent += gas;
+            if let Some(gas_value) = report.gas_value.as_mut() {
+                *gas_value += if native0 { gas * price0 } else { gas };
+            }
+
+            let fees0 = amount(&execution.fees0) / scale0;
+            let fees1 = amount(&execution.fees1) / scale1;
+            report.collected_fees0 += fees0;
+            report.collected_fees1 += fees1;
+            report.fee_income += fees0 * price0 + fees1;
+
+            if let Some(swap) = &execution.swap {
+                let (amount_in, amount_out) = (amount(&swap.amount_in), amount(&swap.amount_out));
+                report.slippage += if swap.zero_for_one {
+                    amount_in / scale0 * price0 - amount_out / scale1
+                } else {
+                    amount_in / scale1 - amount_out / scale0 * price0
+                };
+            }
+
+            if execution.kind == ExecutionKind::Close
+                && let Some(mint) = executions.iter().find(|mint| {
+                    mint.kind == ExecutionKind::Mint && mint.token_id == execution.token_id
+                })
+            {
+                let held = amount(&mint.amount0) / scale0 * price0 + amount(&mint.amount1) / scale1;
+                let withdrawn = amount(&execution.amount0) / scale0 * price0
+                    + amount(&execution.amount1) / scale1;
+                report.impermanent_loss += held - withdrawn;
+            }
+        }
+
+        for position in open_positions {
+            let fees0 = amount(&position.uncollected_fees0) / scale0;
+            let fees1 = amount(&position.uncollected_fees1) / scale1;
+            report.uncollected_fees0 += fees0;
+            report.uncollected_fees1 += fees1;
+            report.fee_income += fees0 * pool.price0 + fees1;
+        }
+
+        report.net_income = report.fee_income + report.reward_income
+            - report.impermanent_loss
+            - report.slippage
+            - report.gas_value.unwrap_or(0.0);
+        report
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::rt;
+use alloy::providers::Provider;
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{GAS_SAMPLE_INTERVAL_SECS, GAS_WAIT_POLL_SECS, GAS_WINDOW_SECS},
+    core::metrics::Metrics,
+    state::AppState,
+    types::{EvmProvider, u128_string},
+    utils::time::unix_timestamp,
+};
+
+/// Gas price percentiles (wei) over the sampling window
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GasPercentiles {
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub p10: u128,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub p25: u128,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub p50: u128,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub p75: u128,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub p90: u128,
+    pub samples: usize,
+    pub window_secs: u64,
+}
+
+/// Gas prices sampled over the last `GAS_WINDOW_SECS`, to send the non urgent transactions
+/// when gas is cheap
+#[derive(Debug, Default)]
+pub struct GasTracker {
+    /// `(timestamp, gas_price)` samples, oldest first
+    samples: Mutex<VecDeque<(u64, u128)>>,
+}
+
+impl GasTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, gas_price: u128) {
+        let now = unix_timestamp();
+        let mut samples = self.samples.lock().expect("Gas samples lock poisoned");
+        samples.push_back((now, gas_price));
+        while samples
+            .front()
+            .is_some_and(|(timestamp, _)| now.saturating_sub(*timestamp) > GAS_WINDOW_SECS)
+        {
+            samples.pop_front();
+        }
+    }
+
+    /// # Returns:
+    /// * `None` until at least one gas price has been sampled
+    pub fn percentiles(&self) -> Option<GasPercentiles> {
+        let mut prices: Vec<u128> = self
+            .samples
+            .lock()
+            .expect("Gas samples lock poisoned")
+            .iter()
+            .map(|(_, gas_price)| *gas_price)
+            .collect();
+        if prices.is_empty() {
+            return None;
+        }
+        prices.sort_unstable();
+
+        // Nearest rank percentile
+        let percentile = |p: usize| prices[(prices.len() * p).div_ceil(100).max(1) - 1];
+
+        Some(GasPercentiles {
+            p10: percentile(10),
+            p25: percentile(25),
+            p50: percentile(50),
+            p75: percentile(75),
+            p90: percentile(90),
+            samples: prices.len(),
+            window_secs: GAS_WINDOW_SECS,
+        })
+    }
+}
+
+pub async fn fetch_gas_price(evm_provider: &EvmProvider, metrics: &Metrics) -> Result<u128> {
+    Ok(metrics
+        .track_rpc("eth_gasPrice", evm_provider.get_gas_price())
+        .await?)
+}
+
+/// Sample the gas price every `GAS_SAMPLE_INTERVAL_SECS` for the lifetime of the process
+pub fn spawn_gas_sampler(app_state: Arc<AppState>) {
+    info!(
+        "Sampling gas price every {}s over a {}s window",
+        GAS_SAMPLE_INTERVAL_SECS, GAS_WINDOW_SECS
+    );
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(GAS_SAMPLE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match fetch_gas_price(&app_state.evm_provider, &app_state.metrics).await {
+                Ok(gas_price) => {
+                    debug!("Sampled gas price: {}", gas_price);
+                    app_state.gas.record(gas_price);
+                }
+                Err(e) => warn!("Failed to sample gas price: {}", e),
+            }
+        }
+    });
+}
+
+/// Wait until the gas price is at or below the median of the window, for at most `max_delay`
+///
+/// Non urgent actions call this before sending their transaction. Without enough history
+/// the gas is considered cheap.
+///
+/// # Returns:
+/// * The last fetched gas price
+pub async fn wait_for_cheap_gas(app_state: &AppState, max_delay: Duration) -> Result<u128> {
+    let start = Instant::now();
+
+    loop {
+        let gas_price = fetch_gas_price(&app_state.evm_provider, &app_state.metrics).await?;
+        let median = app_state
+            .gas
+            .percentiles()
+            .map(|percentiles| percentiles.p50);
+
+        let elapsed = start.elapsed();
+        if median.is_none_or(|median| gas_price <= median) {
+            return Ok(gas_price);
+        }
+        if elapsed >= max_delay {
+            info!(
+                "Gas price {} still above the median after {:?}, proceeding",
+                gas_price, max_delay
+            );
+            return Ok(gas_price);
+        }
+
+        rt::time::sleep(Duration::from_secs(GAS_WAIT_POLL_SECS).min(max_delay - elapsed)).await;
+    }
+}
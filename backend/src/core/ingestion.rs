@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::rt;
+use anyhow::Result;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{
+        CHECKPOINTS_PATH, MAX_BACKFILL_BLOCKS, SWAP_INGESTION_CONFIRMATIONS,
+        SWAP_INGESTION_INTERVAL_SECS,
+    },
+    core::{block_time, swaps},
+    state::AppState,
+    types::Pool,
+    utils::json_file,
+};
+
+/// Last block whose swaps have been ingested, per pool, persisted to `CHECKPOINTS_PATH`
+/// so a restart resumes where the previous run stopped
+#[derive(Debug, Default)]
+pub struct Checkpoints {
+    blocks: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Checkpoints {
+    /// Load the checkpoints of the previous run, none when the file does not exist yet
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            blocks: Mutex::new(json_file::load(CHECKPOINTS_PATH)?),
+        })
+    }
+
+    pub fn get(&self, address: &str) -> Option<u64> {
+        self.blocks
+            .lock()
+            .expect("Checkpoints lock poisoned")
+            .get(address)
+            .copied()
+    }
+
+    /// Move the checkpoint of a pool and persist all the checkpoints
+    pub fn set(&self, address: &str, block: u64) -> Result<()> {
+        let mut blocks = self.blocks.lock().expect("Checkpoints lock poisoned");
+        blocks.insert(address.to_string(), block);
+        json_file::save(CHECKPOINTS_PATH, &*blocks)
+    }
+
+    /// Forget the checkpoint of a pool that is not tracked anymore
+    pub fn forget(&self, address: &str) -> Result<()> {
+        let mut blocks = self.blocks.lock().expect("Checkpoints lock poisoned");
+        if blocks.remove(address).is_some() {
+            json_file::save(CHECKPOINTS_PATH, &*blocks)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ingest the swaps of a pool from its checkpoint up to `to_block`
+///
+/// A pool without checkpoint starts at `to_block`. The checkpoint only moves once all the
+/// swaps of the range are recorded, so a failure retries the same range on the next run.
+/// The backfill is capped to the last `MAX_BACKFILL_BLOCKS` blocks.
+///
+/// # Returns:
+/// * The number of ingested swaps
+async fn ingest_pool(
+    app_state: &AppState,
+    address: &str,
+    pool: &Pool,
+    to_block: u64,
+    block_timestamp: impl Fn(u64) -> u64,
+) -> Result<usize> {
+    let Some(checkpoint) = app_state.checkpoints.get(address) else {
+        app_state.checkpoints.set(address, to_block)?;
+        return Ok(0);
+    };
+    if checkpoint >= to_block {
+        return Ok(0);
+    }
+
+    let from_block = (checkpoint + 1).max(to_block.saturating_sub(MAX_BACKFILL_BLOCKS - 1));
+    if from_block > checkpoint + 1 {
+        warn!(
+            "Pool {} is {} blocks behind, skipping to block {}",
+            address,
+            to_block - checkpoint,
+            from_block
+        );
+    }
+
+    let swaps = swaps::fetch_swaps_between(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        pool,
+        from_block,
+        to_block,
+    )
+    .await?;
+
+    for swap in &swaps {
+        app_state.price_history.record_swap(
+            address,
+            pool,
+            block_timestamp(swap.block_number),
+            swap.tick,
+        );
+    }
+
+    app_state.checkpoints.set(address, to_block)?;
+    Ok(swaps.len())
+}
+
+/// Ingest the swaps of every tracked pool every `SWAP_INGESTION_INTERVAL_SECS`, into the
+/// price history the candles and analytics are built from
+///
+/// Only blocks with `SWAP_INGESTION_CONFIRMATIONS` confirmations are ingested, so a
+/// reorganization does not record swaps that never happened.
+pub fn spawn_swap_ingestion(app_state: Arc<AppState>) {
+    info!(
+        "Ingesting swaps every {}s from the checkpoints in {}",
+        SWAP_INGESTION_INTERVAL_SECS, CHECKPOINTS_PATH
+    );
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(SWAP_INGESTION_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+
+            let estimate =
+                match block_time::estimate_block_time(&app_state.evm_provider, &app_state.metrics)
+                    .await
+                {
+                    Ok(estimate) => estimate,
+                    Err(e) => {
+                        warn!(
+                            "Failed to fetch the latest block, skipping ingestion: {}",
+                            e
+                        );
+                        continue;
+                    }
+                };
+            let to_block = estimate
+                .latest_block
+                .saturating_sub(SWAP_INGESTION_CONFIRMATIONS);
+
+            // Logs only carry block numbers, their timestamps are extrapolated from the
+            // latest block to avoid fetching every block header
+            let block_timestamp = |block: u64| {
+                let behind_secs = (estimate.latest_block.saturating_sub(block) as f64
+                    * estimate.avg_block_time_secs)
+                    .round() as u64;
+                estimate.latest_timestamp.saturating_sub(behind_secs)
+            };
+
+            let pools: Vec<(String, Pool)> = app_state
+                .pools
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect();
+
+            for (address, pool) in pools {
+                match ingest_pool(&app_state, &address, &pool, to_block, block_timestamp).await {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Ingested {} swaps of pool {}", count, address),
+                    Err(e) => error!("Failed to ingest the swaps of pool {}: {:#}", address, e),
+                }
+            }
+        }
+    });
+}
@@ -1,36 +1,144 @@
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use alloy::{providers::ProviderBuilder, signers::local::PrivateKeySigner};
-use anyhow::Result;
+use alloy::{
+    providers::ProviderBuilder,
+    rpc::client::RpcClient,
+    signers::local::PrivateKeySigner,
+    transports::{BoxTransport, http::Http, utils::guess_local_url},
+};
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
 use dashmap::DashMap;
 use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::{
+    Url,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
+};
 use tokio::sync::Semaphore;
 use tracing::{debug, info};
 
 use crate::{
-    config::CONFIG,
-    core,
+    config::{CONFIG, ChainConfig, RpcAuth},
+    core::{
+        self,
+        metrics::Metrics,
+        rpc_failover::{Endpoint, FailoverTransport},
+    },
     types::{EvmProvider, Pool},
 };
 
 /// Initialize the EVM provider using the configuration of the toml file and .env
 pub async fn init_evm_provider() -> Result<EvmProvider> {
-    let private_key = CONFIG.private_key.as_str();
-    let chain_id = CONFIG.toml.chain.chain_id;
-    let rpc_url = CONFIG.toml.chain.rpc_url.as_str();
+    let config = CONFIG.get();
+    let private_key = config.private_key.expose().as_str();
+    let chain_id = config.toml.chain.chain_id;
+    let rpc_url = config.toml.chain.rpc_url.as_str();
 
     let evm_signer = PrivateKeySigner::from_str(private_key)?;
 
-    // Init provider with the specified rpc url in config
-    let evm_provider = ProviderBuilder::new()
+    let builder = ProviderBuilder::new()
         .with_chain_id(chain_id)
-        .wallet(evm_signer)
-        .connect(rpc_url)
-        .await?;
+        .wallet(evm_signer);
+
+    let chain = &config.toml.chain;
+    let is_http = Url::parse(rpc_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !is_http {
+        if !chain.rpc_headers.0.is_empty()
+            || chain.rpc_auth.is_some()
+            || !chain.fallback_rpc_urls.is_empty()
+        {
+            bail!(
+                "RPC headers, authentication and fallback urls are only supported for http(s) RPC urls"
+            );
+        }
+        // Websocket and IPC urls keep the transport of alloy, without the request timeout
+        return Ok(builder.connect(rpc_url).await?);
+    }
+
+    // Our own HTTP client carries the headers, authentication and request timeout, for
+    // the primary and the fallback endpoints
+    let url = Url::parse(rpc_url).context("Invalid RPC url")?;
+    let rpc_timeout = Duration::from_secs(config.toml.runtime.rpc_timeout_secs);
+    let client = reqwest::Client::builder()
+        .default_headers(rpc_headers(chain)?)
+        .connect_timeout(rpc_timeout);
+    let client = if chain.fallback_rpc_urls.is_empty() {
+        client.timeout(rpc_timeout)
+    } else {
+        // The failover transport times the requests out itself, to count the timeouts
+        client
+    }
+    .build()?;
+    let is_local = guess_local_url(rpc_url);
+    let primary = Http::with_client(client.clone(), url);
+
+    if chain.fallback_rpc_urls.is_empty() {
+        return Ok(builder.connect_client(RpcClient::new(primary, is_local)));
+    }
+
+    let mut endpoints = vec![Endpoint {
+        name: "rpc_url".to_string(),
+        transport: BoxTransport::new(primary),
+    }];
+    for (index, fallback_url) in chain.fallback_rpc_urls.iter().enumerate() {
+        let url = Url::parse(fallback_url)
+            .with_context(|| format!("Invalid fallback RPC url {}", index))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Fallback RPC url {} must be an http(s) url", index);
+        }
+        endpoints.push(Endpoint {
+            name: format!("fallback_rpc_urls[{}]", index),
+            transport: BoxTransport::new(Http::with_client(client.clone(), url)),
+        });
+    }
+    info!(
+        "RPC requests fail over to {} fallback urls",
+        chain.fallback_rpc_urls.len()
+    );
 
-    Ok(evm_provider)
+    Ok(builder.connect_client(RpcClient::new(
+        FailoverTransport::new(endpoints, rpc_timeout),
+        is_local,
+    )))
+}
+
+/// Build the headers sent with every RPC request from the chain config
+fn rpc_headers(chain: &ChainConfig) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in &chain.rpc_headers.0 {
+        let name = HeaderName::from_str(name)
+            .with_context(|| format!("Invalid RPC header name: {}", name))?;
+        let mut value = HeaderValue::from_str(value.expose())
+            .with_context(|| format!("Invalid value for RPC header {}", name))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+
+    if let Some(auth) = &chain.rpc_auth {
+        let credentials = match auth {
+            RpcAuth::Bearer { token } => format!("Bearer {}", token.expose()),
+            RpcAuth::Basic { username, password } => format!(
+                "Basic {}",
+                STANDARD.encode(format!(
+                    "{}:{}",
+                    username,
+                    password
+                        .as_ref()
+                        .map(|password| password.expose().as_str())
+                        .unwrap_or_default()
+                ))
+            ),
+        };
+        let mut value =
+            HeaderValue::from_str(&credentials).context("Invalid RPC authentication")?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    Ok(headers)
 }
 
 /// Initialize the pools state by concurrently fetching all pools defined in the toml file
@@ -48,10 +156,14 @@ pub async fn init_evm_provider() -> Result<EvmProvider> {
 ///
 /// # Arguments:
 /// * `evm_provider` - A reference to the blockchain provider used to make RPC calls
+/// * `metrics` - The metrics registry recording the RPC calls
 ///
 /// # Returns:
 /// * `Result<DashMap<String, Pool>>` - A thread-safe HashMap containing all pool data, or an error
-pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<String, Pool>> {
+pub async fn init_pools_state(
+    evm_provider: &EvmProvider,
+    metrics: &Metrics,
+) -> Result<DashMap<String, Pool>> {
     // ============================================================================
     // STEP 1: Setup - Prepare timing and logging
     // ============================================================================
@@ -59,14 +171,17 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
     // Record the start time so we can measure how long initialization takes
     let start_time = Instant::now();
 
+    // Take a snapshot of the config so the pool list can't change while we iterate it
+    let config = CONFIG.get();
+
     // Get the total number of pools we need to fetch
-    let pool_count = CONFIG.toml.pools.len();
+    let pool_count = config.toml.pools.len();
+    let max_concurrency = config.toml.runtime.max_concurrency;
 
     // Log that we're starting the initialization process
     info!(
         "Starting concurrent pool initialization for {} pools with max {} concurrent tasks",
-        pool_count,
-        crate::config::MAX_ALLOWED_THREADS
+        pool_count, max_concurrency
     );
 
     // ============================================================================
@@ -80,9 +195,9 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
 
     // Create a semaphore to limit how many tasks can run at the same time
     // Semaphore = A counter that controls access to a resource
-    // Example: If MAX_ALLOWED_THREADS = 8, only 8 tasks can fetch data at once
+    // Example: If max_concurrency = 8, only 8 tasks can fetch data at once
     // When a task finishes, it releases its "permit" and another task can start
-    let semaphore = Arc::new(Semaphore::new(crate::config::MAX_ALLOWED_THREADS));
+    let semaphore = Arc::new(Semaphore::new(max_concurrency));
 
     // ============================================================================
     // STEP 3: Create a stream of concurrent tasks
@@ -90,7 +205,7 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
 
     // stream::iter() - Converts the pool configs into a stream (like an iterator but for async)
     // .map() - Transforms each pool config into an async task
-    let fetch_tasks = stream::iter(CONFIG.toml.pools.iter())
+    let fetch_tasks = stream::iter(config.toml.pools.iter())
         .map(|pool_config| {
             // Clone the Arc pointers so each async task has its own reference
             // This is cheap - we're not copying the data, just incrementing a reference counter
@@ -99,7 +214,7 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
 
             // Clone the pool data we need for this specific task
             // We need to clone because the async block needs to own this data
-            let address = pool_config.address.clone();
+            let address = pool_config.key();
             let dex_type = pool_config.dex_type.clone();
 
             // Create an async block that will fetch data for ONE pool
@@ -126,9 +241,13 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
 
                 // Make the actual RPC call to fetch pool details
                 // This is the slow I/O operation we're trying to parallelize
-                let result =
-                    core::pools::fetch_pool_blockchain_details(evm_provider, &address, &dex_type)
-                        .await;
+                let result = core::pools::fetch_pool_blockchain_details(
+                    evm_provider,
+                    metrics,
+                    &address,
+                    &dex_type,
+                )
+                .await;
 
                 // ----------------------------------------------------------------
                 // STEP 3c: Handle the result and store in DashMap
@@ -156,7 +275,7 @@ pub async fn init_pools_state(evm_provider: &EvmProvider) -> Result<DashMap<Stri
         })
         // buffer_unordered() - Run up to N tasks concurrently and collect results as they complete
         // The "unordered" part means we don't care what order the results come back in
-        .buffer_unordered(crate::config::MAX_ALLOWED_THREADS);
+        .buffer_unordered(max_concurrency);
 
     // ============================================================================
     // STEP 4: Wait for all tasks to complete and check for errors
@@ -0,0 +1,249 @@
+use alloy::primitives::Address;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{CONFIG, DEFAULT_INVEST_SLIPPAGE_PERCENT},
+    core::{
+        quotes::{self, PoolQuote},
+        strategy, swaps,
+    },
+    state::AppState,
+    types::{DexType, MintPositionRequest, Pool},
+    utils::amm_math::{liquidity_to_amounts, tick_to_price},
+};
+
+/// Liquidity used to compute the token split of a range, only its ratio matters
+const NOMINAL_LIQUIDITY: u128 = 1_000_000_000_000_000_000;
+
+/// How wide the range of the investment is, relative to the default width of the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskTier {
+    /// Twice the default width, rebalanced less often but earning less fees
+    Low,
+    #[default]
+    Medium,
+    /// Half the default width
+    High,
+}
+
+impl RiskTier {
+    fn width_factor(&self) -> f64 {
+        match self {
+            RiskTier::Low => 2.0,
+            RiskTier::Medium => 1.0,
+            RiskTier::High => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct InvestRequest {
+    /// Address of the deposited token
+    pub token: String,
+    /// Deposited amount, in the smallest unit of the token
+    pub amount: String,
+    #[serde(default)]
+    pub risk: RiskTier,
+    /// Only consider the pools pairing the token with one of these tokens (symbol or
+    /// address, case insensitive), any pool when empty
+    #[serde(default)]
+    pub pair_with: Vec<String>,
+    /// Only consider the pools of this dex
+    pub dex_type: Option<DexType>,
+    /// Largest accepted shortfall of the swap output below its estimate, in percent.
+    /// Defaults to `DEFAULT_INVEST_SLIPPAGE_PERCENT`
+    pub max_slippage_percent: Option<f64>,
+}
+
+/// ERC-20 approval the signer wallet needs before a step of the plan
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InvestApproval {
+    pub token: String,
+    pub spender: String,
+    /// Amount to approve, in the smallest unit of the token
+    pub amount: String,
+}
+
+/// Swap of part of the deposit into the other token of the pool
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InvestSwap {
+    pub token_in: String,
+    pub token_out: String,
+    /// Sold amount, in the smallest unit of `token_in`
+    pub amount_in: String,
+    /// Estimated bought amount, in the smallest unit of `token_out`
+    pub expected_amount_out: String,
+    /// Least bought amount to accept, the estimate minus the maximum slippage
+    pub amount_out_min: String,
+    pub price_impact_percent: f64,
+    /// Swap router of the dex, unknown when not configured for the chain
+    pub router: Option<String>,
+}
+
+/// Plan putting a deposit to work in the best tracked pool, nothing is executed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InvestPlan {
+    pub pool: String,
+    pub dex_type: DexType,
+    pub risk: RiskTier,
+    pub current_tick: i32,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub price0_lower: f64,
+    pub price0_upper: f64,
+    /// Approvals to give first: the sold amount to the router, when known, and both
+    /// minted amounts to the Yield contract. `POST /positions` sends the missing Yield
+    /// approvals itself.
+    pub approvals: Vec<InvestApproval>,
+    /// Swap to do next so the deposit matches the token split of the range
+    pub swap: InvestSwap,
+    /// Confirm step: the request to send to `POST /positions` once the swap is done. The
+    /// Yield contract pulls both amounts from the signer wallet, the bought one is the
+    /// least swap output so the mint doesn't exceed the balance.
+    pub mint: MintPositionRequest,
+    /// Quotes of the other candidate pools, the best first
+    pub alternatives: Vec<PoolQuote>,
+}
+
+/// Plan an investment of `amount` of `token`
+///
+/// The candidates are the tracked pools of the token matching the preferences. The one
+/// where swapping half of the deposit has the lowest price impact is selected. The range
+/// is centered on the current tick, with the strategy width of the pool or its tuned
+/// width, scaled by the risk tier.
+///
+/// # Returns:
+/// * `Ok(None)` if no tracked pool with liquidity matches the request
+pub async fn plan(
+    app_state: &AppState,
+    request: &InvestRequest,
+    token: Address,
+    amount: u128,
+) -> Result<Option<InvestPlan>> {
+    let token_key = token.to_string();
+    let matches = |pool: &Pool| {
+        let other = if pool.token0.address.eq_ignore_ascii_case(&token_key) {
+            &pool.token1
+        } else {
+            &pool.token0
+        };
+        request
+            .dex_type
+            .as_ref()
+            .is_none_or(|dex| *dex == pool.dex_type)
+            && (request.pair_with.is_empty()
+                || request.pair_with.iter().any(|preferred| {
+                    other.address.eq_ignore_ascii_case(preferred)
+                        || other.symbol.eq_ignore_ascii_case(preferred)
+                }))
+    };
+
+    let ranking_amount = (amount / 2).max(1);
+    let mut candidates: Vec<(Pool, PoolQuote)> =
+        quotes::quote_all(app_state, token, ranking_amount)
+            .await?
+            .quotes
+            .into_iter()
+            .filter_map(|quote| {
+                let pool = app_state.pools.get(&quote.pool.to_lowercase())?.clone();
+                (matches(&pool) && quote.amount_out != "0").then_some((pool, quote))
+            })
+            .collect();
+    candidates.sort_by(|(_, a), (_, b)| a.price_impact_percent.total_cmp(&b.price_impact_percent));
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let (pool, quote) = candidates.remove(0);
+
+    let address = pool.address.to_lowercase();
+    let width = match app_state.strategies.get(&address) {
+        Some(strategy) => strategy::range_width_ticks(strategy.range_width_percent),
+        None => app_state.range_tuner.width(&address, &pool),
+    };
+    let width = (width as f64 * request.risk.width_factor()).round() as i32;
+    let (tick_lower, tick_upper) =
+        swaps::centered_range(pool.current_tick, pool.tick_spacing, width);
+
+    // Share of the deposit to swap so both amounts match the split of the range
+    let (nominal0, nominal1) =
+        liquidity_to_amounts(NOMINAL_LIQUIDITY, pool.current_tick, tick_lower, tick_upper);
+    let raw_price0 = 1.0001f64.powi(pool.current_tick);
+    let zero_for_one = pool.token0.address.eq_ignore_ascii_case(&token_key);
+    let swap_share = if zero_for_one {
+        let value1 = nominal1 / raw_price0;
+        value1 / (nominal0 + value1)
+    } else {
+        let value0 = nominal0 * raw_price0;
+        value0 / (value0 + nominal1)
+    };
+
+    let swap_amount = (amount as f64 * swap_share).floor() as u128;
+    let ranking_out: f64 = quote.amount_out.parse().context("Invalid quoted amount")?;
+    // The ranking quote sized half the deposit, its impact is close enough for the split
+    let expected_out = (ranking_out * swap_amount as f64 / ranking_amount as f64).floor() as u128;
+    let max_slippage_percent = request
+        .max_slippage_percent
+        .unwrap_or(DEFAULT_INVEST_SLIPPAGE_PERCENT);
+    let amount_out_min =
+        (expected_out as f64 * (1.0 - max_slippage_percent / 100.0)).floor() as u128;
+    let kept = amount - swap_amount;
+    let (amount0, amount1) = if zero_for_one {
+        (kept, amount_out_min)
+    } else {
+        (amount_out_min, kept)
+    };
+
+    let config = CONFIG.get();
+    let router = config.toml.addresses.dex(&pool.dex_type).router;
+    let yield_contract = config.contract_address.to_string();
+    let approval = |token: &str, spender: String, amount: u128| {
+        (amount > 0).then(|| InvestApproval {
+            token: token.to_string(),
+            spender,
+            amount: amount.to_string(),
+        })
+    };
+    let approvals = [
+        router.and_then(|router| approval(&token_key, router.to_string(), swap_amount)),
+        approval(&pool.token0.address, yield_contract.clone(), amount0),
+        approval(&pool.token1.address, yield_contract, amount1),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let (decimals0, decimals1) = (pool.token0.decimals, pool.token1.decimals);
+    Ok(Some(InvestPlan {
+        pool: pool.address.clone(),
+        dex_type: pool.dex_type.clone(),
+        risk: request.risk,
+        current_tick: pool.current_tick,
+        tick_lower,
+        tick_upper,
+        price0_lower: tick_to_price(tick_lower, decimals0, decimals1)?,
+        price0_upper: tick_to_price(tick_upper, decimals0, decimals1)?,
+        approvals,
+        swap: InvestSwap {
+            token_in: token_key,
+            token_out: quote.token_out.clone(),
+            amount_in: swap_amount.to_string(),
+            expected_amount_out: expected_out.to_string(),
+            amount_out_min: amount_out_min.to_string(),
+            price_impact_percent: quote.price_impact_percent,
+            router: router.map(|router| router.to_string()),
+        },
+        mint: MintPositionRequest {
+            pool: address,
+            tick_lower,
+            tick_upper,
+            amount0_desired: amount0.to_string(),
+            amount1_desired: amount1.to_string(),
+            amount0_min: None,
+            amount1_min: None,
+        },
+        alternatives: candidates.into_iter().map(|(_, quote)| quote).collect(),
+    }))
+}
@@ -0,0 +1,282 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{Address, U256, aliases::I24},
+    sol,
+};
+use anyhow::Result;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::CONFIG,
+    core::metrics::Metrics,
+    types::{EvmProvider, Pool, u128_string},
+    utils::{
+        self,
+        amm_math::{MAX_TICK, MIN_TICK},
+    },
+};
+
+sol!(
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IPoolTicks {
+        function tickBitmap(int16 wordPosition) external view returns (uint256);
+
+        function ticks(int24 tick) external view returns (
+            uint128 liquidityGross,
+            int128 liquidityNet,
+            uint256 feeGrowthOutside0X128,
+            uint256 feeGrowthOutside1X128,
+            int56 tickCumulativeOutside,
+            uint160 secondsPerLiquidityOutsideX128,
+            uint32 secondsOutside,
+            bool initialized
+        );
+    }
+);
+
+/// Range between two consecutive initialized ticks, with the liquidity active within it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LiquidityBand {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub price0_lower: f64,
+    pub price0_upper: f64,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+}
+
+/// Liquidity histogram of a pool around its current tick
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LiquidityDistribution {
+    pub address: String,
+    pub current_tick: i32,
+    /// Lowest tick covered by the scanned tick bitmap words
+    pub scanned_tick_lower: i32,
+    /// Highest tick covered by the scanned tick bitmap words
+    pub scanned_tick_upper: i32,
+    /// Bands ordered by tick, the band containing the current tick holds the pool liquidity
+    pub bands: Vec<LiquidityBand>,
+}
+
+/// Read the initialized ticks of a pool from its tick bitmap, `words` bitmap words on each
+/// side of the current tick, and build the liquidity histogram from their `liquidityNet`
+///
+/// One bitmap word covers 256 tick spacings.
+pub async fn fetch_liquidity_distribution(
+    evm_provider: &EvmProvider,
+    metrics: &Metrics,
+    pool: &Pool,
+    words: i16,
+) -> Result<LiquidityDistribution> {
+    let pool_contract = IPoolTicks::new(Address::from_str(&pool.address)?, evm_provider);
+    let spacing = pool.tick_spacing.max(1);
+
+    let current_word = (pool.current_tick.div_euclid(spacing) >> 8) as i16;
+    let word_positions = current_word.saturating_sub(words)..=current_word.saturating_add(words);
+
+    let bitmaps: Vec<(i16, U256)> = stream::iter(word_positions.clone())
+        .map(|word_position| {
+            let pool_contract = &pool_contract;
+            async move {
+                let bitmap = metrics
+                    .track_rpc("tickBitmap", pool_contract.tickBitmap(word_position).call())
+                    .await?;
+                Ok::<_, anyhow::Error>((word_position, bitmap))
+            }
+        })
+        .buffered(CONFIG.get().toml.runtime.max_concurrency)
+        .try_collect()
+        .await?;
+
+    let initialized_ticks: Vec<i32> = bitmaps
+        .into_iter()
+        .flat_map(|(word_position, bitmap)| {
+            (0..256)
+                .filter(move |bit| bitmap.bit(*bit))
+                .map(move |bit| ((word_position as i32) * 256 + bit as i32) * spacing)
+        })
+        .collect();
+
+    // Ticks come out of the bitmap words in ascending order
+    let liquidity_nets: Vec<(i32, i128)> = stream::iter(initialized_ticks)
+        .map(|tick| {
+            let pool_contract = &pool_contract;
+            async move {
+                let info = metrics
+                    .track_rpc("ticks", pool_contract.ticks(I24::try_from(tick)?).call())
+                    .await?;
+                Ok::<_, anyhow::Error>((tick, info.liquidityNet))
+            }
+        })
+        .buffered(CONFIG.get().toml.runtime.max_concurrency)
+        .try_collect()
+        .await?;
+
+    let scanned_tick_lower = ((*word_positions.start() as i32) * 256 * spacing).max(MIN_TICK);
+    let scanned_tick_upper =
+        (((*word_positions.end() as i32) + 1) * 256 * spacing - 1).min(MAX_TICK);
+
+    let bands = build_bands(
+        pool,
+        &liquidity_nets,
+        scanned_tick_lower,
+        scanned_tick_upper,
+    )?;
+
+    Ok(LiquidityDistribution {
+        address: pool.address.clone(),
+        current_tick: pool.current_tick,
+        scanned_tick_lower,
+        scanned_tick_upper,
+        bands,
+    })
+}
+
+/// Split the scanned ticks into bands at the initialized ticks, walking the liquidity out
+/// of the current tick
+///
+/// # Arguments:
+/// * `liquidity_nets` - `(tick, liquidityNet)` of the initialized ticks, in ascending order
+fn build_bands(
+    pool: &Pool,
+    liquidity_nets: &[(i32, i128)],
+    scanned_tick_lower: i32,
+    scanned_tick_upper: i32,
+) -> Result<Vec<LiquidityBand>> {
+    let split = liquidity_nets.partition_point(|(tick, _)| *tick <= pool.current_tick);
+    let (below, above) = liquidity_nets.split_at(split);
+
+    let mut bands = Vec::with_capacity(liquidity_nets.len() + 1);
+
+    // Crossing a tick downward subtracts its liquidityNet
+    let mut liquidity = pool.liquidity as i128;
+    let mut upper = above.first().map_or(scanned_tick_upper, |(tick, _)| *tick);
+    for (tick, liquidity_net) in below.iter().rev() {
+        bands.push((*tick, upper, liquidity));
+        liquidity -= liquidity_net;
+        upper = *tick;
+    }
+    bands.push((scanned_tick_lower, upper, liquidity));
+    bands.reverse();
+
+    // Crossing a tick upward adds its liquidityNet
+    let mut liquidity = pool.liquidity as i128;
+    for (index, (tick, liquidity_net)) in above.iter().enumerate() {
+        liquidity += liquidity_net;
+        let next = above
+            .get(index + 1)
+            .map_or(scanned_tick_upper, |(tick, _)| *tick);
+        bands.push((*tick, next, liquidity));
+    }
+
+    bands
+        .into_iter()
+        .filter(|(tick_lower, tick_upper, _)| tick_lower < tick_upper)
+        .map(|(tick_lower, tick_upper, liquidity)| {
+            Ok(LiquidityBand {
+                tick_lower,
+                tick_upper,
+                price0_lower: utils::amm_math::tick_to_price(
+                    tick_lower,
+                    pool.token0.decimals,
+                    pool.token1.decimals,
+                )?,
+                price0_upper: utils::amm_math::tick_to_price(
+                    tick_upper,
+                    pool.token0.decimals,
+                    pool.token1.decimals,
+                )?,
+                // The ticks are read over several blocks, a concurrent mint or burn can make
+                // the sums inconsistent
+                liquidity: liquidity.max(0) as u128,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DexType, Token};
+
+    fn pool(current_tick: i32, liquidity: u128) -> Pool {
+        let token = |symbol: &str| Token {
+            address: format!("0x{}", symbol),
+            symbol: symbol.to_string(),
+            decimals: 18,
+        };
+        Pool {
+            address: "0xpool".to_string(),
+            dex_type: DexType::UniswapV3,
+            token0: token("A"),
+            token1: token("B"),
+            fee: 0.3,
+            tick_spacing: 60,
+            current_tick,
+            price0: 1.0,
+            price1: 1.0,
+            liquidity,
+            updated_at: 0,
+            block_number: 0,
+            annotation: None,
+        }
+    }
+
+    fn ranges(bands: &[LiquidityBand]) -> Vec<(i32, i32, u128)> {
+        bands
+            .iter()
+            .map(|band| (band.tick_lower, band.tick_upper, band.liquidity))
+            .collect()
+    }
+
+    fn tick_to_price(tick: i32) -> f64 {
+        utils::amm_math::tick_to_price(tick, 18, 18).unwrap()
+    }
+
+    #[test]
+    fn walks_the_liquidity_out_of_the_current_tick() {
+        let liquidity_nets = [(-60, 40), (60, -30), (120, -70)];
+        let bands = build_bands(&pool(0, 100), &liquidity_nets, -600, 599).unwrap();
+
+        assert_eq!(
+            ranges(&bands),
+            [
+                (-600, -60, 60),
+                (-60, 60, 100),
+                (60, 120, 70),
+                (120, 599, 0),
+            ]
+        );
+        assert_eq!(bands[1].price0_lower, tick_to_price(-60));
+        assert_eq!(bands[1].price0_upper, tick_to_price(60));
+    }
+
+    #[test]
+    fn an_initialized_current_tick_starts_the_band_above_it() {
+        let liquidity_nets = [(0, 40), (60, -40)];
+        let bands = build_bands(&pool(0, 40), &liquidity_nets, -600, 599).unwrap();
+
+        assert_eq!(ranges(&bands), [(-600, 0, 0), (0, 60, 40), (60, 599, 0)]);
+    }
+
+    #[test]
+    fn drops_the_empty_bands_and_the_negative_liquidity() {
+        let liquidity_nets = [(-600, 10), (60, -80)];
+        let bands = build_bands(&pool(0, 50), &liquidity_nets, -600, 599).unwrap();
+
+        assert_eq!(ranges(&bands), [(-600, 60, 50), (60, 599, 0)]);
+    }
+
+    #[test]
+    fn a_pool_without_initialized_ticks_is_one_band() {
+        let bands = build_bands(&pool(10, 5), &[], -600, 599).unwrap();
+
+        assert_eq!(ranges(&bands), [(-600, 599, 5)]);
+    }
+}
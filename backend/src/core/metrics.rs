@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::IntoFuture;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Upper bounds (seconds) of the latency histograms buckets
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Histogram,
+}
+
+#[derive(Debug, Clone)]
+enum Series {
+    Counter(u64),
+    Histogram {
+        /// Non cumulative count per bucket, the last one being +Inf
+        buckets: [u64; LATENCY_BUCKETS.len() + 1],
+        sum: f64,
+        count: u64,
+    },
+}
+
+#[derive(Debug)]
+struct Family {
+    help: &'static str,
+    kind: Kind,
+    /// Series keyed by their rendered labels
+    series: BTreeMap<String, Series>,
+}
+
+/// Application metrics registry, rendered in the Prometheus text exposition format
+#[derive(Debug)]
+pub struct Metrics {
+    families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let families = [
+            (
+                "http_requests_total",
+                "Total number of HTTP requests",
+                Kind::Counter,
+            ),
+            (
+                "http_request_duration_seconds",
+                "HTTP requests latency",
+                Kind::Histogram,
+            ),
+            (
+                "rpc_requests_total",
+                "Total number of blockchain RPC calls",
+                Kind::Counter,
+            ),
+            (
+                "rpc_failures_total",
+                "Total number of failed blockchain RPC calls",
+                Kind::Counter,
+            ),
+            (
+                "rpc_request_duration_seconds",
+                "Blockchain RPC calls latency",
+                Kind::Histogram,
+            ),
+            (
+                "pool_refresh_total",
+                "Total number of pool refreshes",
+                Kind::Counter,
+            ),
+            (
+                "pool_refresh_duration_seconds",
+                "Pool refreshes duration",
+                Kind::Histogram,
+            ),
+            (
+                "tx_failsafe_trips_total",
+                "Total number of switches to read-only after repeated transaction failures",
+                Kind::Counter,
+            ),
+            (
+                "price_guard_trips_total",
+                "Total number of executions aborted because the prices of their pool diverged",
+                Kind::Counter,
+            ),
+        ]
+        .into_iter()
+        .map(|(name, help, kind)| {
+            (
+                name,
+                Family {
+                    help,
+                    kind,
+                    series: BTreeMap::new(),
+                },
+            )
+        })
+        .collect();
+
+        let metrics = Self {
+            families: Mutex::new(families),
+        };
+        // Exposed from the start, an alert on its increase needs a sample before the first trip
+        metrics.update("tx_failsafe_trips_total", &[], |_| {});
+        metrics.update("price_guard_trips_total", &[], |_| {});
+        metrics
+    }
+
+    pub fn record_http_request(&self, method: &str, path: &str, status: u16, duration: Duration) {
+        let status = status.to_string();
+        let labels = [("method", method), ("path", path), ("status", &status)];
+        self.inc("http_requests_total", &labels);
+        self.observe("http_request_duration_seconds", &labels, duration);
+    }
+
+    pub fn record_rpc_call(&self, method: &str, success: bool, duration: Duration) {
+        let labels = [("method", method)];
+        self.inc("rpc_requests_total", &labels);
+        if !success {
+            self.inc("rpc_failures_total", &labels);
+        }
+        self.observe("rpc_request_duration_seconds", &labels, duration);
+    }
+
+    pub fn record_pool_refresh(&self, success: bool, duration: Duration) {
+        let labels = [("result", if success { "success" } else { "failure" })];
+        self.inc("pool_refresh_total", &labels);
+        self.observe("pool_refresh_duration_seconds", &labels, duration);
+    }
+
+    pub fn record_failsafe_trip(&self) {
+        self.inc("tx_failsafe_trips_total", &[]);
+    }
+
+    pub fn record_price_guard_trip(&self, pool: &str, reason: &str) {
+        self.inc(
+            "price_guard_trips_total",
+            &[("pool", pool), ("reason", reason)],
+        );
+    }
+
+    /// Run an RPC call and record its outcome and latency
+    pub async fn track_rpc<T, E>(
+        &self,
+        method: &str,
+        call: impl IntoFuture<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let start = Instant::now();
+        let result = call.await;
+        self.record_rpc_call(method, result.is_ok(), start.elapsed());
+        result
+    }
+
+    /// Render all the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let families = self.families.lock().expect("Metrics lock poisoned");
+        let mut output = String::new();
+
+        for (name, family) in families.iter() {
+            let kind = match family.kind {
+                Kind::Counter => "counter",
+                Kind::Histogram => "histogram",
+            };
+            let _ = writeln!(output, "# HELP {} {}", name, family.help);
+            let _ = writeln!(output, "# TYPE {} {}", name, kind);
+
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Counter(value) => {
+                        let _ = writeln!(output, "{}{{{}}} {}", name, labels, value);
+                    }
+                    Series::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let mut cumulative = 0;
+                        for (index, bucket) in buckets.iter().enumerate() {
+                            cumulative += bucket;
+                            let le = LATENCY_BUCKETS
+                                .get(index)
+                                .map_or("+Inf".to_string(), |bound| bound.to_string());
+                            let _ = writeln!(
+                                output,
+                                "{}_bucket{{{},le=\"{}\"}} {}",
+                                name, labels, le, cumulative
+                            );
+                        }
+                        let _ = writeln!(output, "{}_sum{{{}}} {}", name, labels, sum);
+                        let _ = writeln!(output, "{}_count{{{}}} {}", name, labels, count);
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    fn inc(&self, name: &'static str, labels: &[(&str, &str)]) {
+        self.update(name, labels, |series| {
+            if let Series::Counter(value) = series {
+                *value += 1;
+            }
+        });
+    }
+
+    fn observe(&self, name: &'static str, labels: &[(&str, &str)], duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.update(name, labels, |series| {
+            if let Series::Histogram {
+                buckets,
+                sum,
+                count,
+            } = series
+            {
+                buckets[bucket] += 1;
+                *sum += secs;
+                *count += 1;
+            }
+        });
+    }
+
+    fn update(
+        &self,
+        name: &'static str,
+        labels: &[(&str, &str)],
+        change: impl FnOnce(&mut Series),
+    ) {
+        let mut families = self.families.lock().expect("Metrics lock poisoned");
+        let Some(family) = families.get_mut(name) else {
+            debug_assert!(false, "Unknown metric {}", name);
+            return;
+        };
+
+        let kind = family.kind;
+        let series = family
+            .series
+            .entry(render_labels(labels))
+            .or_insert_with(|| match kind {
+                Kind::Counter => Series::Counter(0),
+                Kind::Histogram => Series::Histogram {
+                    buckets: [0; LATENCY_BUCKETS.len() + 1],
+                    sum: 0.0,
+                    count: 0,
+                },
+            });
+
+        change(series);
+    }
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -1,2 +1,30 @@
-pub mod pools;
+pub mod alerts;
+pub mod analytics;
+pub mod annotations;
+pub mod anomaly;
+pub mod as_of;
+pub mod auth;
+pub mod block_time;
+pub mod config_watch;
+pub mod executions;
+pub mod gas;
+pub mod ingestion;
 pub mod init;
+pub mod invest;
+pub mod liquidity;
+pub mod metrics;
+pub mod pool_refresh;
+pub mod pool_verification;
+pub mod pools;
+pub mod positions;
+pub mod price_guard;
+pub mod quotes;
+pub mod range_tuning;
+pub mod rate_limit;
+pub mod rpc_failover;
+pub mod snapshots;
+pub mod strategy;
+pub mod swap_subscription;
+pub mod swaps;
+pub mod tx;
+pub mod wallets;
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::rt;
+use alloy::{primitives::Address, providers::Provider, rpc::types::Filter};
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{CONFIG, MAX_LOG_BLOCK_RANGE, RefreshTier},
+    core::{pools::refresh_pool, positions},
+    state::AppState,
+    types::address_key,
+    utils::time::unix_timestamp,
+};
+
+/// Refresh the tracked pools according to their refresh tier, for the lifetime of the
+/// process
+///
+/// Every `runtime.hot_poll_interval_secs`, the logs of the hot pools in the new blocks
+/// are read in a single `eth_getLogs` call and the pools with a log are refreshed. The
+/// pools whose state is older than the interval of their tier are refreshed too, a hot
+/// pool without any log at the warm interval. The pools holding positions, which the
+/// automatic tier is based on, are looked up again every cold interval.
+///
+/// The `Updated` events of the refreshes reach the WebSocket and SSE subscribers. The
+/// intervals and tiers are read on every pass, so a config reload changes them.
+pub fn spawn_pool_refresher(app_state: Arc<AppState>) {
+    let runtime = CONFIG.get().toml.runtime.clone();
+    info!(
+        "Refreshing the hot pools on new blocks, the warm ones every {}s and the cold ones every {}s",
+        runtime.warm_refresh_interval_secs, runtime.cold_refresh_interval_secs
+    );
+
+    rt::spawn(async move {
+        let mut last_block: Option<u64> = None;
+        // `None` until the positions could be read, the automatic tier is warm meanwhile
+        let mut held_pools: Option<HashSet<String>> = None;
+        let mut held_pools_at = 0;
+
+        loop {
+            let poll_secs = CONFIG.get().toml.runtime.hot_poll_interval_secs;
+            rt::time::sleep(Duration::from_secs(poll_secs)).await;
+            let config = CONFIG.get();
+            let runtime = config.toml.runtime.clone();
+
+            let now = unix_timestamp();
+            // Retried at the warm interval while the positions couldn't be read
+            let held_pools_max_age = match held_pools {
+                Some(_) => runtime.cold_refresh_interval_secs,
+                None => runtime.warm_refresh_interval_secs,
+            };
+            if now.saturating_sub(held_pools_at) >= held_pools_max_age {
+                held_pools_at = now;
+                match positions::fetch_positions(&app_state).await {
+                    Ok(positions) => {
+                        held_pools = Some(
+                            positions
+                                .iter()
+                                .filter(|position| position.liquidity > 0)
+                                .filter_map(|position| position.pool.as_ref())
+                                .map(|pool| pool.to_lowercase())
+                                .collect(),
+                        );
+                    }
+                    Err(e) => warn!("Failed to fetch the positions held per pool: {:#}", e),
+                }
+            }
+
+            let mut hot = Vec::new();
+            let mut due = Vec::new();
+            for entry in app_state.pools.iter() {
+                let address = entry.key();
+                let tier = config
+                    .toml
+                    .pools
+                    .iter()
+                    .find(|pool| pool.key() == *address)
+                    .and_then(|pool| pool.refresh_tier)
+                    .unwrap_or(match &held_pools {
+                        Some(held) if held.contains(address) => RefreshTier::Hot,
+                        Some(_) => RefreshTier::Cold,
+                        None => RefreshTier::Warm,
+                    });
+                let max_age = match tier {
+                    RefreshTier::Hot | RefreshTier::Warm => runtime.warm_refresh_interval_secs,
+                    RefreshTier::Cold => runtime.cold_refresh_interval_secs,
+                };
+
+                if now.saturating_sub(entry.updated_at) >= max_age {
+                    due.push(address.clone());
+                } else if tier == RefreshTier::Hot {
+                    hot.push(address.clone());
+                }
+            }
+
+            if !hot.is_empty() {
+                match pools_with_new_logs(&app_state, &hot, &mut last_block).await {
+                    Ok(active) => due.extend(active),
+                    Err(e) => warn!("Failed to read the logs of the hot pools: {:#}", e),
+                }
+            }
+            if due.is_empty() {
+                continue;
+            }
+
+            let count = due.len();
+            let failures = stream::iter(due)
+                .map(|address| {
+                    let app_state = &app_state;
+                    async move {
+                        let result = refresh_pool(app_state, &address).await;
+                        if let Err(e) = &result {
+                            warn!("Failed to refresh pool {}: {}", address, e);
+                        }
+                        result.is_err()
+                    }
+                })
+                .buffer_unordered(runtime.max_concurrency)
+                .filter(|failed| std::future::ready(*failed))
+                .count()
+                .await;
+
+            debug!("Refreshed {} pools, {} failed", count, failures);
+        }
+    });
+}
+
+/// Hot pools with a log in the blocks mined since `last_block`, which is moved to the
+/// latest block
+///
+/// The first call only records the latest block. When more than `MAX_LOG_BLOCK_RANGE`
+/// blocks were mined since, every hot pool is returned.
+async fn pools_with_new_logs(
+    app_state: &AppState,
+    hot: &[String],
+    last_block: &mut Option<u64>,
+) -> Result<Vec<String>> {
+    let latest = app_state
+        .metrics
+        .track_rpc("eth_blockNumber", app_state.evm_provider.get_block_number())
+        .await?;
+    let Some(previous) = last_block.replace(latest) else {
+        return Ok(Vec::new());
+    };
+    if latest <= previous {
+        return Ok(Vec::new());
+    }
+    if latest - previous > MAX_LOG_BLOCK_RANGE {
+        return Ok(hot.to_vec());
+    }
+
+    let addresses = hot
+        .iter()
+        .map(|address| Address::from_str(address))
+        .collect::<Result<Vec<_>, _>>()?;
+    let filter = Filter::new()
+        .address(addresses)
+        .from_block(previous + 1)
+        .to_block(latest);
+    let logs = match app_state
+        .metrics
+        .track_rpc("eth_getLogs", app_state.evm_provider.get_logs(&filter))
+        .await
+    {
+        Ok(logs) => logs,
+        Err(e) => {
+            // Read the same blocks again on the next pass
+            *last_block = Some(previous);
+            return Err(e.into());
+        }
+    };
+
+    let active: HashSet<String> = logs.iter().map(|log| address_key(&log.address())).collect();
+    Ok(hot
+        .iter()
+        .filter(|address| active.contains(*address))
+        .cloned()
+        .collect())
+}
@@ -0,0 +1,192 @@
+use alloy::{primitives::Address, providers::Provider, sol};
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{CONFIG, PoolConfig},
+    core::positions,
+    state::AppState,
+    types::DexType,
+};
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IV3PoolImmutables {
+        function factory() external view returns (address);
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+        function fee() external view returns (uint24);
+    }
+
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IV3Factory {
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool);
+    }
+
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IPeripheryImmutableState {
+        function factory() external view returns (address);
+    }
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolVerificationStatus {
+    /// The pool was deployed by the factory of its declared DEX
+    Verified,
+    /// The pool was deployed by the factory of another DEX than the declared one
+    DexTypeMismatch,
+    /// The contract looks like a V3 pool but none of the known factories deployed it
+    UnknownFactory,
+    /// The contract does not implement the V3 pool interface
+    NotAPool,
+    /// The verification could not complete, e.g. an RPC failure
+    Error,
+}
+
+/// On-chain verification of a configured pool
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolVerification {
+    pub address: String,
+    pub declared_dex_type: DexType,
+    /// DEX whose factory deployed the pool, if any
+    pub detected_dex_type: Option<DexType>,
+    pub status: PoolVerificationStatus,
+    pub details: Option<String>,
+}
+
+/// Factory of each DEX, from the address registry or read from the position manager the
+/// Yield contract uses for it
+async fn dex_factories(app_state: &AppState) -> Result<Vec<(DexType, Address)>> {
+    let config = CONFIG.get();
+    let mut factories = Vec::new();
+    for dex_type in [DexType::UniswapV3, DexType::PancakeSwapV3] {
+        if let Some(factory) = config.toml.addresses.dex(&dex_type).factory {
+            factories.push((dex_type, factory));
+            continue;
+        }
+
+        let nfpm = positions::nfpm_address(app_state, &dex_type).await?;
+        let factory = app_state
+            .metrics
+            .track_rpc(
+                "factory",
+                IPeripheryImmutableState::new(nfpm, &app_state.evm_provider)
+                    .factory()
+                    .call(),
+            )
+            .await?;
+        factories.push((dex_type, factory));
+    }
+    Ok(factories)
+}
+
+/// Find the DEX that deployed a pool
+///
+/// The factory reported by the pool is not trusted: the factory must also return the
+/// pool for its tokens and fee, so a contract merely exposing the pool interface is
+/// rejected.
+///
+/// # Returns:
+/// * `Ok(None)` if none of the known factories deployed the pool
+async fn detect_dex_type(
+    app_state: &AppState,
+    factories: &[(DexType, Address)],
+    address: Address,
+) -> Result<Option<DexType>> {
+    let pool = IV3PoolImmutables::new(address, &app_state.evm_provider);
+    let factory = app_state
+        .metrics
+        .track_rpc("factory", pool.factory().call())
+        .await?;
+
+    let Some((dex_type, _)) = factories.iter().find(|(_, known)| *known == factory) else {
+        return Ok(None);
+    };
+
+    let metrics = &app_state.metrics;
+    let token0 = metrics.track_rpc("token0", pool.token0().call()).await?;
+    let token1 = metrics.track_rpc("token1", pool.token1().call()).await?;
+    let fee = metrics.track_rpc("fee", pool.fee().call()).await?;
+    let deployed = metrics
+        .track_rpc(
+            "getPool",
+            IV3Factory::new(factory, &app_state.evm_provider)
+                .getPool(token0, token1, fee)
+                .call(),
+        )
+        .await?;
+
+    Ok((deployed == address).then(|| dex_type.clone()))
+}
+
+async fn verify_pool(
+    app_state: &AppState,
+    factories: &[(DexType, Address)],
+    pool_config: &PoolConfig,
+) -> PoolVerification {
+    let mut verification = PoolVerification {
+        address: pool_config.key(),
+        declared_dex_type: pool_config.dex_type.clone(),
+        detected_dex_type: None,
+        status: PoolVerificationStatus::Error,
+        details: None,
+    };
+
+    let address = pool_config.address;
+
+    let has_code = match app_state
+        .metrics
+        .track_rpc("eth_getCode", app_state.evm_provider.get_code_at(address))
+        .await
+    {
+        Ok(code) => !code.is_empty(),
+        Err(e) => {
+            verification.details = Some(format!("Failed to fetch the contract code: {}", e));
+            return verification;
+        }
+    };
+    if !has_code {
+        verification.status = PoolVerificationStatus::NotAPool;
+        verification.details = Some("No contract deployed at this address".to_string());
+        return verification;
+    }
+
+    match detect_dex_type(app_state, factories, address).await {
+        Ok(Some(dex_type)) => {
+            verification.status = if dex_type == pool_config.dex_type {
+                PoolVerificationStatus::Verified
+            } else {
+                PoolVerificationStatus::DexTypeMismatch
+            };
+            verification.detected_dex_type = Some(dex_type);
+        }
+        Ok(None) => verification.status = PoolVerificationStatus::UnknownFactory,
+        // The contract has code, so a failing call means it lacks the V3 pool interface
+        Err(e) => {
+            verification.status = PoolVerificationStatus::NotAPool;
+            verification.details = Some(format!("Pool interface probe failed: {}", e));
+        }
+    }
+
+    verification
+}
+
+/// Verify on-chain that every pool is a pool of its declared DEX
+pub async fn verify_pools(
+    app_state: &AppState,
+    pools: &[PoolConfig],
+) -> Result<Vec<PoolVerification>> {
+    let factories = dex_factories(app_state).await?;
+
+    Ok(stream::iter(pools)
+        .map(|pool_config| verify_pool(app_state, &factories, pool_config))
+        .buffered(CONFIG.get().toml.runtime.max_concurrency)
+        .collect()
+        .await)
+}
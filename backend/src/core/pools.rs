@@ -1,18 +1,28 @@
 use std::str::FromStr;
+use std::time::Instant;
 
+use alloy::eips::BlockId;
 use alloy::primitives::Address;
+use alloy::providers::Provider;
 use alloy::sol;
 use anyhow::Result;
+use tracing::{debug, warn};
 
 use crate::config::CONFIG;
 use crate::config::FEE_FACTOR;
+use crate::core::metrics::Metrics;
+use crate::state::AppState;
 use crate::types::DexType;
 use crate::types::EvmProvider;
 use crate::types::Pool;
+use crate::types::PoolEvent;
+use crate::types::PoolPriceState;
 use crate::types::Token;
 use crate::utils;
+use crate::utils::time::unix_timestamp;
 
 sol!(
+    #[allow(clippy::too_many_arguments)]
     #[derive(Debug)]
     #[sol(rpc)]
     Yield,
@@ -21,16 +31,28 @@ sol!(
 
 pub async fn fetch_pool_blockchain_details(
     evm_provider: &EvmProvider,
+    metrics: &Metrics,
     pool_address: &str,
     dex_type: &DexType,
 ) -> Result<Pool> {
-    let contract_address = Address::from_str(&CONFIG.contract_address)?;
+    let contract_address = CONFIG.get().contract_address;
     let pool_address = Address::from_str(pool_address)?;
 
     let yield_contract = Yield::new(contract_address, evm_provider);
 
-    let pool_details: Yield::PoolDetails =
-        yield_contract.getPoolDetails(pool_address).call().await?;
+    // Read at a known block so an older state doesn't replace the one of a later Swap log
+    let block_number = metrics
+        .track_rpc("eth_blockNumber", evm_provider.get_block_number())
+        .await?;
+    let pool_details: Yield::PoolDetails = metrics
+        .track_rpc(
+            "getPoolDetails",
+            yield_contract
+                .getPoolDetails(pool_address)
+                .call()
+                .block(BlockId::number(block_number)),
+        )
+        .await?;
 
     // Descale fee value
     let fee_scaled: f64 = pool_details.fee.into();
@@ -65,5 +87,106 @@ pub async fn fetch_pool_blockchain_details(
         current_tick,
         price0,
         price1,
+        liquidity: pool_details.liquidity,
+        updated_at: unix_timestamp(),
+        block_number,
+        annotation: None,
     })
 }
+
+/// Re-fetch the blockchain details of a tracked pool and swap them into the pools map.
+///
+/// The map entry is replaced in place so a pool removed while the RPC call was in flight
+/// is not re-inserted. An `Updated` event is published when the tick or prices changed,
+/// and an `Anomaly` event for every suspicious difference between the two snapshots.
+///
+/// # Returns:
+/// * `Ok(None)` if the pool is not tracked
+/// * `Ok(Some((old, new)))` with the pool before and after the refresh
+pub async fn refresh_pool(app_state: &AppState, address: &str) -> Result<Option<(Pool, Pool)>> {
+    // Don't hold the DashMap lock across the RPC call
+    let dex_type = match app_state.pools.get(address) {
+        Some(entry) => entry.dex_type.clone(),
+        None => return Ok(None),
+    };
+
+    let start_time = Instant::now();
+    let result = fetch_pool_blockchain_details(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        address,
+        &dex_type,
+    )
+    .await;
+    app_state
+        .metrics
+        .record_pool_refresh(result.is_ok(), start_time.elapsed());
+    let new_pool = result?;
+
+    Ok(replace_pool(app_state, address, new_pool))
+}
+
+/// Swap the new state of a tracked pool into the pools map and notify the subscribers
+///
+/// A state read at an older block than the current one is dropped, the current one is
+/// kept.
+///
+/// # Returns:
+/// * The pool before and after the change, none if the pool is not tracked anymore
+pub fn replace_pool(app_state: &AppState, address: &str, new_pool: Pool) -> Option<(Pool, Pool)> {
+    apply_pool_state(app_state, address, new_pool, true)
+}
+
+/// `replace_pool` for a state taken from a `Swap` log, not recorded in the price history
+/// since the swap ingestion records every swap already
+pub fn replace_pool_from_swap(
+    app_state: &AppState,
+    address: &str,
+    new_pool: Pool,
+) -> Option<(Pool, Pool)> {
+    apply_pool_state(app_state, address, new_pool, false)
+}
+
+fn apply_pool_state(
+    app_state: &AppState,
+    address: &str,
+    new_pool: Pool,
+    record_price: bool,
+) -> Option<(Pool, Pool)> {
+    let mut stale = false;
+    let refreshed = app_state.pools.get_mut(address).map(|mut entry| {
+        if new_pool.block_number < entry.block_number {
+            stale = true;
+            return (entry.clone(), entry.clone());
+        }
+        let old_pool = std::mem::replace(entry.value_mut(), new_pool.clone());
+        (old_pool, new_pool)
+    });
+    if stale {
+        debug!("Dropped an outdated state of pool {}", address);
+        return refreshed;
+    }
+
+    if let Some((old_pool, new_pool)) = &refreshed
+        && (old_pool.current_tick != new_pool.current_tick
+            || old_pool.price0 != new_pool.price0
+            || old_pool.price1 != new_pool.price1)
+    {
+        app_state.publish_pool_event(PoolEvent::Updated {
+            address: address.to_string(),
+            state: PoolPriceState::from(new_pool),
+        });
+    }
+
+    if let Some((old_pool, new_pool)) = &refreshed {
+        if record_price {
+            app_state.price_history.record(address, new_pool);
+        }
+        for anomaly in app_state.anomalies.inspect(address, old_pool, new_pool) {
+            warn!("Anomaly detected on pool {}: {:?}", address, anomaly.kind);
+            app_state.publish_pool_event(PoolEvent::Anomaly { anomaly });
+        }
+    }
+
+    refreshed
+}
@@ -0,0 +1,1369 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use alloy::{
+    primitives::{
+        Address, TxHash, U256,
+        aliases::{I24, U24},
+    },
+    providers::WalletProvider,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    sol,
+    sol_types::{SolCall, SolEvent},
+};
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use tracing::{info, warn};
+
+use crate::{
+    config::{
+        CONFIG, FEE_FACTOR, MAX_NFT_METADATA_BYTES, NFT_METADATA_CACHE_SECS, TX_DEADLINE_SECS,
+    },
+    core::{
+        executions::{ExecutedSwap, Execution, ExecutionKind},
+        pools::{INonfungiblePositionManager::MintParams, Yield},
+        price_guard,
+        snapshots::{PositionSnapshot, SnapshotPhase},
+    },
+    state::AppState,
+    types::{
+        ClosedPosition, CollectedFees, DexType, MintPositionRequest, MintedPosition, NftMetadata,
+        Pool, Position, RebalancePositionRequest, RebalancedPosition,
+    },
+    utils::{
+        self,
+        amm_math::{MAX_TICK, MIN_TICK, liquidity_to_amounts},
+        time::unix_timestamp,
+    },
+};
+
+sol!(
+    #[allow(clippy::too_many_arguments)]
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface INonfungiblePositionManager {
+        struct CollectParams {
+            uint256 tokenId;
+            address recipient;
+            uint128 amount0Max;
+            uint128 amount1Max;
+        }
+
+        function balanceOf(address owner) external view returns (uint256 balance);
+
+        function ownerOf(uint256 tokenId) external view returns (address owner);
+
+        function tokenOfOwnerByIndex(address owner, uint256 index) external view returns (uint256 tokenId);
+
+        function positions(uint256 tokenId) external view returns (
+            uint96 nonce,
+            address operator,
+            address token0,
+            address token1,
+            uint24 fee,
+            int24 tickLower,
+            int24 tickUpper,
+            uint128 liquidity,
+            uint256 feeGrowthInside0LastX128,
+            uint256 feeGrowthInside1LastX128,
+            uint128 tokensOwed0,
+            uint128 tokensOwed1
+        );
+
+        function collect(CollectParams calldata params) external payable returns (uint256 amount0, uint256 amount1);
+
+        function tokenURI(uint256 tokenId) external view returns (string uri);
+
+        function isApprovedForAll(address owner, address operator) external view returns (bool);
+
+        function setApprovalForAll(address operator, bool approved) external;
+
+        event Collect(uint256 indexed tokenId, address recipient, uint256 amount0, uint256 amount1);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IERC20 {
+        function allowance(address owner, address spender) external view returns (uint256);
+
+        function approve(address spender, uint256 amount) external returns (bool);
+    }
+);
+
+/// Arguments of the Yield contract `rebalance` call
+#[derive(Debug, Clone)]
+pub struct RebalanceParams {
+    pub dex_type: DexType,
+    pub token_id: U256,
+    pub tick_lower: I24,
+    pub tick_upper: I24,
+    pub swap_token_in: Address,
+    pub swap_token_out: Address,
+    pub swap_amount: U256,
+    pub swap_amount_out_min: U256,
+    pub swap_fee: U24,
+}
+
+/// Address of the NonfungiblePositionManager the Yield contract uses for a DEX
+pub async fn nfpm_address(app_state: &AppState, dex_type: &DexType) -> Result<Address> {
+    let contract_address = CONFIG.get().contract_address;
+    let yield_contract = Yield::new(contract_address, &app_state.evm_provider);
+
+    let address = match dex_type {
+        DexType::UniswapV3 => {
+            app_state
+                .metrics
+                .track_rpc("uniswapNFPM", yield_contract.uniswapNFPM().call())
+                .await?
+        }
+        DexType::PancakeSwapV3 => {
+            app_state
+                .metrics
+                .track_rpc("pancakeswapNFPM", yield_contract.pancakeswapNFPM().call())
+                .await?
+        }
+    };
+
+    Ok(address)
+}
+
+/// Accounts whose positions are managed: the Yield contract and the signer wallet
+fn position_owners(app_state: &AppState) -> Result<[Address; 2]> {
+    Ok([
+        CONFIG.get().contract_address,
+        app_state.evm_provider.default_signer_address(),
+    ])
+}
+
+/// Fetch a position held by the Yield contract or the signer wallet, looking it up on every
+/// DEX position manager
+///
+/// # Returns:
+/// * `Ok(None)` if neither of them holds this position
+pub async fn fetch_position(app_state: &AppState, token_id: U256) -> Result<Option<Position>> {
+    match locate_position(app_state, token_id).await? {
+        Some((dex_type, nfpm_address, owner)) => Ok(Some(
+            fetch_position_details(app_state, &dex_type, nfpm_address, owner, token_id).await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Find the position manager of a position held by the Yield contract or the signer wallet
+///
+/// # Returns:
+/// * `Ok(Some((dex_type, nfpm_address, owner)))` if one of them holds the position
+async fn locate_position(
+    app_state: &AppState,
+    token_id: U256,
+) -> Result<Option<(DexType, Address, Address)>> {
+    let owners = position_owners(app_state)?;
+
+    for dex_type in [DexType::UniswapV3, DexType::PancakeSwapV3] {
+        let nfpm_address = nfpm_address(app_state, &dex_type).await?;
+        if nfpm_address.is_zero() {
+            continue;
+        }
+
+        let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+        let owner = match app_state
+            .metrics
+            .track_rpc("ownerOf", nfpm.ownerOf(token_id).call())
+            .await
+        {
+            Ok(owner) => owner,
+            // `ownerOf` reverts for tokens this position manager never minted
+            Err(e) if e.as_revert_data().is_some() => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        // Token IDs are only unique per position manager
+        if !owners.contains(&owner) {
+            continue;
+        }
+
+        return Ok(Some((dex_type, nfpm_address, owner)));
+    }
+
+    Ok(None)
+}
+
+/// Fetch the tokenURI metadata of a position NFT, decoding the on-chain data URIs
+///
+/// Off-chain metadata are only downloaded over HTTPS, up to `MAX_NFT_METADATA_BYTES`.
+/// Metadata are cached for `NFT_METADATA_CACHE_SECS`.
+///
+/// # Returns:
+/// * `Ok(None)` if neither the Yield contract nor the signer wallet holds this position
+pub async fn fetch_nft_metadata(
+    app_state: &AppState,
+    token_id: U256,
+) -> Result<Option<NftMetadata>> {
+    let key = token_id.to_string();
+    if let Some(cached) = app_state.nft_metadata.get(&key)
+        && unix_timestamp().saturating_sub(cached.fetched_at) < NFT_METADATA_CACHE_SECS
+    {
+        return Ok(Some(cached.clone()));
+    }
+
+    let Some((dex_type, nfpm_address, _)) = locate_position(app_state, token_id).await? else {
+        return Ok(None);
+    };
+
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+    let token_uri = app_state
+        .metrics
+        .track_rpc("tokenURI", nfpm.tokenURI(token_id).call())
+        .await?;
+
+    let json = match decode_data_uri(&token_uri) {
+        Some((_, data)) => data,
+        None if token_uri.starts_with("https://") => {
+            let timeout = Duration::from_secs(CONFIG.get().toml.runtime.http_timeout_secs);
+            let response = reqwest::Client::builder()
+                .timeout(timeout)
+                .https_only(true)
+                .build()?
+                .get(&token_uri)
+                .send()
+                .await?
+                .error_for_status()?;
+            read_capped(response, MAX_NFT_METADATA_BYTES).await?
+        }
+        None if token_uri.starts_with("http://") => {
+            bail!("Insecure token URI, only https is allowed")
+        }
+        None => bail!("Unsupported token URI scheme"),
+    };
+    let json: serde_json::Value =
+        serde_json::from_slice(&json).context("Invalid token metadata JSON")?;
+
+    let image = json["image"].as_str().map(str::to_string);
+    let svg = image
+        .as_deref()
+        .and_then(decode_data_uri)
+        .filter(|(mime, _)| mime == "image/svg+xml")
+        .and_then(|(_, data)| String::from_utf8(data).ok());
+
+    let metadata = NftMetadata {
+        token_id: key.clone(),
+        dex_type,
+        name: json["name"].as_str().map(str::to_string),
+        description: json["description"].as_str().map(str::to_string),
+        image,
+        svg,
+        fetched_at: unix_timestamp(),
+    };
+
+    app_state.nft_metadata.insert(key, metadata.clone());
+
+    Ok(Some(metadata))
+}
+
+/// Read the body of a response, failing once it exceeds `max_bytes`
+async fn read_capped(mut response: reqwest::Response, max_bytes: usize) -> Result<Vec<u8>> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        bail!("Token metadata larger than {} bytes", max_bytes);
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            bail!("Token metadata larger than {} bytes", max_bytes);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Decode a `data:<mime>[;base64],<data>` URI
+///
+/// # Returns:
+/// * `Some((mime, data))`, or `None` if this is not a valid data URI
+fn decode_data_uri(uri: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = uri.strip_prefix("data:")?.split_once(',')?;
+
+    match header.strip_suffix(";base64") {
+        Some(mime) => Some((mime.to_string(), STANDARD.decode(data).ok()?)),
+        None => Some((header.to_string(), data.as_bytes().to_vec())),
+    }
+}
+
+/// Fetch the LP positions held by the Yield contract and by the signer wallet, on every DEX
+pub async fn fetch_positions(app_state: &AppState) -> Result<Vec<Position>> {
+    let owners = position_owners(app_state)?;
+
+    let mut positions = Vec::new();
+    for dex_type in [DexType::UniswapV3, DexType::PancakeSwapV3] {
+        let nfpm = nfpm_address(app_state, &dex_type).await?;
+        // The position manager of a DEX may not be configured on the contract
+        if nfpm.is_zero() {
+            continue;
+        }
+
+        for owner in owners {
+            positions.extend(fetch_owner_positions(app_state, &dex_type, nfpm, owner).await?);
+        }
+    }
+
+    Ok(positions)
+}
+
+/// Fetch the LP positions of any wallet, on every DEX
+///
+/// Looks them up in the position manager of the address registry, or the one the Yield
+/// contract uses when the registry has none.
+pub async fn fetch_wallet_positions(app_state: &AppState, owner: Address) -> Result<Vec<Position>> {
+    let mut positions = Vec::new();
+    for dex_type in [DexType::UniswapV3, DexType::PancakeSwapV3] {
+        let position_manager = CONFIG.get().toml.addresses.dex(&dex_type).position_manager;
+        let nfpm = match position_manager {
+            Some(nfpm) => nfpm,
+            None => nfpm_address(app_state, &dex_type).await?,
+        };
+        if nfpm.is_zero() {
+            continue;
+        }
+        positions.extend(fetch_owner_positions(app_state, &dex_type, nfpm, owner).await?);
+    }
+
+    Ok(positions)
+}
+
+async fn fetch_owner_positions(
+    app_state: &AppState,
+    dex_type: &DexType,
+    nfpm_address: Address,
+    owner: Address,
+) -> Result<Vec<Position>> {
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+
+    let balance: U256 = app_state
+        .metrics
+        .track_rpc("balanceOf", nfpm.balanceOf(owner).call())
+        .await?;
+    let balance: u64 = balance.try_into()?;
+
+    stream::iter(0..balance)
+        .map(|index| {
+            let nfpm = &nfpm;
+            async move {
+                let token_id = app_state
+                    .metrics
+                    .track_rpc(
+                        "tokenOfOwnerByIndex",
+                        nfpm.tokenOfOwnerByIndex(owner, U256::from(index)).call(),
+                    )
+                    .await?;
+                fetch_position_details(app_state, dex_type, nfpm_address, owner, token_id).await
+            }
+        })
+        .buffered(CONFIG.get().toml.runtime.max_concurrency)
+        .try_collect()
+        .await
+}
+
+async fn fetch_position_details(
+    app_state: &AppState,
+    dex_type: &DexType,
+    nfpm_address: Address,
+    owner: Address,
+    token_id: U256,
+) -> Result<Position> {
+    let (details, fees) = read_position_state(app_state, nfpm_address, owner, token_id).await?;
+
+    let fee_scaled: f64 = details.fee.into();
+    let fee = fee_scaled / FEE_FACTOR;
+    let pool = find_pool(app_state, dex_type, &details);
+
+    let mut position = Position {
+        token_id: token_id.to_string(),
+        dex_type: dex_type.clone(),
+        owner: owner.to_string(),
+        token0: details.token0.to_string(),
+        token1: details.token1.to_string(),
+        fee,
+        tick_lower: details.tickLower.as_i32(),
+        tick_upper: details.tickUpper.as_i32(),
+        liquidity: details.liquidity,
+        uncollected_fees0: fees.amount0.to_string(),
+        uncollected_fees1: fees.amount1.to_string(),
+        pool: None,
+        price_lower: None,
+        price_upper: None,
+        in_range: None,
+        annotation: app_state.annotations.position(&token_id.to_string()),
+    };
+
+    if let Some(pool) = pool {
+        apply_pool(&mut position, &pool)?;
+    }
+
+    Ok(position)
+}
+
+/// Snapshot the full on-chain state of a position held by the Yield contract or the
+/// signer wallet
+///
+/// # Returns:
+/// * `Ok(None)` if neither of them holds this position, e.g. once it is burned
+pub async fn snapshot_position(
+    app_state: &AppState,
+    token_id: U256,
+    phase: SnapshotPhase,
+) -> Result<Option<PositionSnapshot>> {
+    let Some((dex_type, nfpm_address, owner)) = locate_position(app_state, token_id).await? else {
+        return Ok(None);
+    };
+    let (details, fees) = read_position_state(app_state, nfpm_address, owner, token_id).await?;
+
+    let tick_lower = details.tickLower.as_i32();
+    let tick_upper = details.tickUpper.as_i32();
+    let pool = find_pool(app_state, &dex_type, &details);
+    let amounts = pool.as_ref().map(|pool| {
+        liquidity_to_amounts(details.liquidity, pool.current_tick, tick_lower, tick_upper)
+    });
+
+    Ok(Some(PositionSnapshot {
+        timestamp: unix_timestamp(),
+        token_id: token_id.to_string(),
+        phase,
+        kind: None,
+        tx_hash: None,
+        dex_type,
+        owner: owner.to_string(),
+        pool: pool.as_ref().map(|pool| pool.address.clone()),
+        tick_lower,
+        tick_upper,
+        liquidity: details.liquidity,
+        current_tick: pool.as_ref().map(|pool| pool.current_tick),
+        amount0: amounts.map(|(amount0, _)| format!("{:.0}", amount0.floor())),
+        amount1: amounts.map(|(_, amount1)| format!("{:.0}", amount1.floor())),
+        fee_growth_inside0_last_x128: details.feeGrowthInside0LastX128.to_string(),
+        fee_growth_inside1_last_x128: details.feeGrowthInside1LastX128.to_string(),
+        tokens_owed0: details.tokensOwed0.to_string(),
+        tokens_owed1: details.tokensOwed1.to_string(),
+        uncollected_fees0: fees.amount0.to_string(),
+        uncollected_fees1: fees.amount1.to_string(),
+    }))
+}
+
+/// Snapshot the managed positions that have no snapshot yet, opened before the snapshots
+/// were recorded
+///
+/// # Returns:
+/// * The recorded snapshots
+pub async fn backfill_snapshots(app_state: &AppState) -> Result<Vec<PositionSnapshot>> {
+    let token_ids = fetch_positions(app_state)
+        .await?
+        .into_iter()
+        .filter(|position| !app_state.snapshots.contains(&position.token_id))
+        .map(|position| U256::from_str_radix(&position.token_id, 10))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let snapshots: Vec<PositionSnapshot> = stream::iter(token_ids)
+        .map(|token_id| snapshot_position(app_state, token_id, SnapshotPhase::Backfill))
+        .buffered(CONFIG.get().toml.runtime.max_concurrency)
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    app_state.snapshots.record(snapshots.clone())?;
+    Ok(snapshots)
+}
+
+/// Read a position from its position manager, with the fees its owner can collect
+async fn read_position_state(
+    app_state: &AppState,
+    nfpm_address: Address,
+    owner: Address,
+    token_id: U256,
+) -> Result<(
+    INonfungiblePositionManager::positionsReturn,
+    INonfungiblePositionManager::collectReturn,
+)> {
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+
+    let details = app_state
+        .metrics
+        .track_rpc("positions", nfpm.positions(token_id).call())
+        .await?;
+
+    // Simulating a collect from the owner is the only way to get the fees accrued since
+    // the last liquidity change, `tokensOwed` only holds the already accounted ones
+    let fees = app_state
+        .metrics
+        .track_rpc(
+            "collect",
+            nfpm.collect(INonfungiblePositionManager::CollectParams {
+                tokenId: token_id,
+                recipient: owner,
+                amount0Max: u128::MAX,
+                amount1Max: u128::MAX,
+            })
+            .from(owner)
+            .call(),
+        )
+        .await?;
+
+    Ok((details, fees))
+}
+
+/// Tracked pool of a position, matched on its DEX, tokens and fee
+fn find_pool(
+    app_state: &AppState,
+    dex_type: &DexType,
+    details: &INonfungiblePositionManager::positionsReturn,
+) -> Option<Pool> {
+    let fee_scaled: f64 = details.fee.into();
+    let fee = fee_scaled / FEE_FACTOR;
+    let token0 = details.token0.to_string();
+    let token1 = details.token1.to_string();
+
+    app_state
+        .pools
+        .iter()
+        .find(|entry| {
+            entry.dex_type == *dex_type
+                && entry.token0.address == token0
+                && entry.token1.address == token1
+                && entry.fee == fee
+        })
+        .map(|entry| entry.value().clone())
+}
+
+/// Validate a mint request against its pool and build the parameters of the mint
+///
+/// # Arguments:
+/// * `recipient` - Owner of the minted position NFT
+pub fn build_mint_params(
+    pool: &Pool,
+    request: &MintPositionRequest,
+    recipient: Address,
+) -> Result<MintParams> {
+    validate_tick_range(pool, request.tick_lower, request.tick_upper)?;
+
+    let amount0_desired = parse_amount(&request.amount0_desired, "amount0_desired")?;
+    let amount1_desired = parse_amount(&request.amount1_desired, "amount1_desired")?;
+    let amount0_min = parse_optional_amount(request.amount0_min.as_deref(), "amount0_min")?;
+    let amount1_min = parse_optional_amount(request.amount1_min.as_deref(), "amount1_min")?;
+    if amount0_desired.is_zero() && amount1_desired.is_zero() {
+        bail!("At least one of the desired amounts must be positive");
+    }
+
+    Ok(MintParams {
+        token0: Address::from_str(&pool.token0.address)?,
+        token1: Address::from_str(&pool.token1.address)?,
+        fee: U24::from((pool.fee * FEE_FACTOR).round() as u32),
+        tickLower: I24::try_from(request.tick_lower)?,
+        tickUpper: I24::try_from(request.tick_upper)?,
+        amount0Desired: amount0_desired,
+        amount1Desired: amount1_desired,
+        amount0Min: amount0_min,
+        amount1Min: amount1_min,
+        recipient,
+        deadline: U256::from(unix_timestamp() + TX_DEADLINE_SECS),
+    })
+}
+
+/// Mint a new position through the Yield contract `addLiquidity`, which pulls the desired
+/// amounts from the signer wallet
+///
+/// The Yield contract is approved first on each token whose allowance doesn't cover the
+/// desired amount.
+pub async fn mint_position(
+    app_state: &AppState,
+    pool: &Pool,
+    params: MintParams,
+) -> Result<MintedPosition> {
+    price_guard::check_prices(app_state, pool).await?;
+
+    let mut approval_tx_hashes = Vec::new();
+    for (token, amount) in [
+        (params.token0, params.amount0Desired),
+        (params.token1, params.amount1Desired),
+    ] {
+        if let Some(tx_hash) = approve_token(app_state, token, amount).await? {
+            approval_tx_hashes.push(tx_hash.to_string());
+        }
+    }
+
+    let contract_address = CONFIG.get().contract_address;
+    let yield_contract = Yield::new(contract_address, &app_state.evm_provider);
+
+    let tx = yield_contract
+        .addLiquidity(pool.dex_type.contract_id(), params)
+        .into_transaction_request();
+
+    let (tx_hash, receipt) = execute(app_state, tx).await?;
+    let added: Yield::LiquidityAdded =
+        decode_event(&receipt).context("LiquidityAdded event not found in the mint receipt")?;
+
+    info!(
+        "Minted position {} in transaction {}",
+        added.tokenId, tx_hash
+    );
+
+    record_execution(
+        app_state,
+        Some(Execution {
+            amount0: added.amount0.to_string(),
+            amount1: added.amount1.to_string(),
+            ..Execution::new(
+                pool,
+                ExecutionKind::Mint,
+                &receipt,
+                &added.tokenId.to_string(),
+            )
+        }),
+    );
+    let after = try_snapshot(app_state, added.tokenId, SnapshotPhase::After).await;
+    record_snapshots(app_state, ExecutionKind::Mint, tx_hash, [after]);
+
+    Ok(MintedPosition {
+        approval_tx_hashes,
+        tx_hash: tx_hash.to_string(),
+        token_id: added.tokenId.to_string(),
+        liquidity: added.liquidity,
+        amount0: added.amount0.to_string(),
+        amount1: added.amount1.to_string(),
+    })
+}
+
+/// Validate a rebalance request against the pool of the position and build the contract call
+/// arguments, the swap being optional
+pub fn build_rebalance_params(
+    position: &Position,
+    pool: &Pool,
+    request: &RebalancePositionRequest,
+) -> Result<RebalanceParams> {
+    validate_tick_range(pool, request.tick_lower, request.tick_upper)?;
+
+    let token0 = Address::from_str(&pool.token0.address)?;
+    let token1 = Address::from_str(&pool.token1.address)?;
+
+    let (swap_token_in, swap_token_out, swap_amount, swap_amount_out_min) = match &request.swap {
+        Some(swap) => {
+            let token_in = Address::from_str(&swap.token_in).context("Invalid swap token_in")?;
+            let token_out = if token_in == token0 {
+                token1
+            } else if token_in == token1 {
+                token0
+            } else {
+                bail!("The swap token_in must be one of the pool tokens");
+            };
+            (
+                token_in,
+                token_out,
+                parse_amount(&swap.amount, "swap amount")?,
+                parse_optional_amount(swap.amount_out_min.as_deref(), "swap amount_out_min")?,
+            )
+        }
+        None => (token0, token1, U256::ZERO, U256::ZERO),
+    };
+
+    Ok(RebalanceParams {
+        dex_type: position.dex_type.clone(),
+        token_id: U256::from_str_radix(&position.token_id, 10)?,
+        tick_lower: I24::try_from(request.tick_lower)?,
+        tick_upper: I24::try_from(request.tick_upper)?,
+        swap_token_in,
+        swap_token_out,
+        swap_amount,
+        swap_amount_out_min,
+        swap_fee: U24::from((pool.fee * FEE_FACTOR).round() as u32),
+    })
+}
+
+/// Withdraw all the liquidity of a position held by the signer wallet, collect its fees and
+/// burn its NFT with the Yield contract `removeLiquidity`
+///
+/// The Yield contract is approved as operator of the positions of the signer first if needed,
+/// a dry run fails when it isn't since nothing is sent.
+///
+/// # Arguments:
+/// * `dry_run` - Only simulate the transaction, returning the amounts it would withdraw
+pub async fn close_position(
+    app_state: &AppState,
+    position: &Position,
+    amount0_min: U256,
+    amount1_min: U256,
+    dry_run: bool,
+) -> Result<ClosedPosition> {
+    let contract_address = CONFIG.get().contract_address;
+    let yield_contract = Yield::new(contract_address, &app_state.evm_provider);
+    let token_id = U256::from_str_radix(&position.token_id, 10)?;
+
+    let call = yield_contract.removeLiquidity(
+        position.dex_type.contract_id(),
+        token_id,
+        position.liquidity,
+        amount0_min,
+        amount1_min,
+        true,
+    );
+
+    if dry_run {
+        let output = app_state
+            .tx_manager
+            .simulate(
+                &app_state.metrics,
+                &app_state.pools,
+                call.clone().into_transaction_request(),
+            )
+            .await?;
+        let amounts = Yield::removeLiquidityCall::abi_decode_returns(&output)?;
+
+        return Ok(ClosedPosition {
+            token_id: position.token_id.clone(),
+            dry_run: true,
+            approval_tx_hash: None,
+            tx_hash: None,
+            amount0: amounts.amount0.to_string(),
+            amount1: amounts.amount1.to_string(),
+            burned: false,
+        });
+    }
+
+    if let Some(pool) = tracked_pool(app_state, position) {
+        price_guard::check_prices(app_state, &pool).await?;
+    }
+
+    let approval_tx_hash = approve_positions(app_state, &position.dex_type).await?;
+
+    let before = try_snapshot(app_state, token_id, SnapshotPhase::Before).await;
+    let (tx_hash, receipt) = execute(app_state, call.into_transaction_request()).await?;
+    let removed: Yield::LiquidityRemoved =
+        decode_event(&receipt).context("LiquidityRemoved event not found in the receipt")?;
+
+    info!(
+        "Closed position {} in transaction {}",
+        position.token_id, tx_hash
+    );
+
+    // The withdrawn amounts include the fees collected by the same transaction
+    let fees0 = U256::from_str_radix(&position.uncollected_fees0, 10)?.min(removed.amount0);
+    let fees1 = U256::from_str_radix(&position.uncollected_fees1, 10)?.min(removed.amount1);
+    record_execution(
+        app_state,
+        tracked_pool(app_state, position).map(|pool| Execution {
+            fees0: fees0.to_string(),
+            fees1: fees1.to_string(),
+            amount0: (removed.amount0 - fees0).to_string(),
+            amount1: (removed.amount1 - fees1).to_string(),
+            ..Execution::new(&pool, ExecutionKind::Close, &receipt, &position.token_id)
+        }),
+    );
+    // Absent once the NFT is burned
+    let after = try_snapshot(app_state, token_id, SnapshotPhase::After).await;
+    record_snapshots(app_state, ExecutionKind::Close, tx_hash, [before, after]);
+
+    Ok(ClosedPosition {
+        token_id: position.token_id.clone(),
+        dry_run: false,
+        approval_tx_hash: approval_tx_hash.map(|tx_hash| tx_hash.to_string()),
+        tx_hash: Some(tx_hash.to_string()),
+        amount0: removed.amount0.to_string(),
+        amount1: removed.amount1.to_string(),
+        burned: removed.burned,
+    })
+}
+
+/// Close a position and reopen its liquidity in a new range with the Yield contract `rebalance`,
+/// which removes the liquidity, collects the fees, optionally swaps and mints in one transaction
+///
+/// The position must be held by the signer wallet, the Yield contract is approved as operator
+/// of its positions first if needed. The new position is minted to the signer too.
+pub async fn rebalance_position(
+    app_state: &AppState,
+    position: &Position,
+    params: RebalanceParams,
+) -> Result<RebalancedPosition> {
+    let contract_address = CONFIG.get().contract_address;
+    let yield_contract = Yield::new(contract_address, &app_state.evm_provider);
+
+    let tx = yield_contract
+        .rebalance(
+            params.dex_type.contract_id(),
+            params.token_id,
+            params.tick_lower,
+            params.tick_upper,
+            params.swap_token_in,
+            params.swap_token_out,
+            params.swap_amount,
+            params.swap_amount_out_min,
+            params.swap_fee,
+        )
+        .into_transaction_request();
+
+    if let Some(pool) = tracked_pool(app_state, position) {
+        price_guard::check_prices(app_state, &pool).await?;
+    }
+
+    let mut tx_hashes = Vec::new();
+    if let Some(tx_hash) = approve_positions(app_state, &params.dex_type).await? {
+        tx_hashes.push(tx_hash.to_string());
+    }
+
+    let before = try_snapshot(app_state, params.token_id, SnapshotPhase::Before).await;
+    let (tx_hash, receipt) = execute(app_state, tx).await?;
+    tx_hashes.push(tx_hash.to_string());
+    let rebalanced: Yield::PositionRebalanced = decode_event(&receipt)
+        .context("PositionRebalanced event not found in the rebalance receipt")?;
+
+    info!(
+        "Rebalanced position {} into {} in transaction {}",
+        rebalanced.oldTokenId, rebalanced.newTokenId, tx_hash
+    );
+
+    let swapped: Option<Yield::TokensSwapped> = decode_event(&receipt);
+    record_execution(
+        app_state,
+        tracked_pool(app_state, position).map(|pool| Execution {
+            fees0: position.uncollected_fees0.clone(),
+            fees1: position.uncollected_fees1.clone(),
+            swap: swapped.map(|swapped| ExecutedSwap {
+                zero_for_one: swapped
+                    .tokenIn
+                    .to_string()
+                    .eq_ignore_ascii_case(&pool.token0.address),
+                amount_in: swapped.amountIn.to_string(),
+                amount_out: swapped.amountOut.to_string(),
+            }),
+            ..Execution::new(
+                &pool,
+                ExecutionKind::Rebalance,
+                &receipt,
+                &position.token_id,
+            )
+        }),
+    );
+    let old_after = try_snapshot(app_state, params.token_id, SnapshotPhase::After).await;
+    let new_after = try_snapshot(app_state, rebalanced.newTokenId, SnapshotPhase::After).await;
+    record_snapshots(
+        app_state,
+        ExecutionKind::Rebalance,
+        tx_hash,
+        [before, old_after, new_after],
+    );
+
+    Ok(RebalancedPosition {
+        tx_hashes,
+        old_token_id: rebalanced.oldTokenId.to_string(),
+        new_token_id: rebalanced.newTokenId.to_string(),
+        liquidity: rebalanced.newLiquidity,
+    })
+}
+
+/// Collect all the fees of a position held by the signer wallet, calling its position manager
+///
+/// The Yield contract has no collect function, the fees of the positions it holds are
+/// collected when they are rebalanced or removed.
+pub async fn collect_fees(app_state: &AppState, position: &Position) -> Result<CollectedFees> {
+    let signer = app_state.evm_provider.default_signer_address();
+    if Address::from_str(&position.owner)? != signer {
+        bail!("Only the fees of the positions held by the signer wallet can be collected");
+    }
+
+    let nfpm_address = nfpm_address(app_state, &position.dex_type).await?;
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+    let token_id = U256::from_str_radix(&position.token_id, 10)?;
+
+    let tx = nfpm
+        .collect(INonfungiblePositionManager::CollectParams {
+            tokenId: token_id,
+            recipient: signer,
+            amount0Max: u128::MAX,
+            amount1Max: u128::MAX,
+        })
+        .into_transaction_request();
+
+    let before = try_snapshot(app_state, token_id, SnapshotPhase::Before).await;
+    let (tx_hash, receipt) = execute(app_state, tx).await?;
+    let collected: INonfungiblePositionManager::Collect =
+        decode_event(&receipt).context("Collect event not found in the collect receipt")?;
+
+    info!(
+        "Collected fees of position {} in transaction {}",
+        position.token_id, tx_hash
+    );
+
+    record_execution(
+        app_state,
+        tracked_pool(app_state, position).map(|pool| Execution {
+            fees0: collected.amount0.to_string(),
+            fees1: collected.amount1.to_string(),
+            ..Execution::new(&pool, ExecutionKind::Collect, &receipt, &position.token_id)
+        }),
+    );
+    let after = try_snapshot(app_state, token_id, SnapshotPhase::After).await;
+    record_snapshots(app_state, ExecutionKind::Collect, tx_hash, [before, after]);
+
+    Ok(CollectedFees {
+        tx_hash: tx_hash.to_string(),
+        token_id: position.token_id.clone(),
+        amount0: collected.amount0.to_string(),
+        amount1: collected.amount1.to_string(),
+    })
+}
+
+/// Let the Yield contract pull `amount` of a token from the signer wallet, unless its
+/// allowance already covers it
+///
+/// # Returns:
+/// * The hash of the approval, none if the allowance was enough
+async fn approve_token(
+    app_state: &AppState,
+    token: Address,
+    amount: U256,
+) -> Result<Option<TxHash>> {
+    if amount.is_zero() {
+        return Ok(None);
+    }
+
+    let contract_address = CONFIG.get().contract_address;
+    let signer = app_state.evm_provider.default_signer_address();
+    let erc20 = IERC20::new(token, &app_state.evm_provider);
+    let allowance = app_state
+        .metrics
+        .track_rpc(
+            "allowance",
+            erc20.allowance(signer, contract_address).call(),
+        )
+        .await?;
+    if allowance >= amount {
+        return Ok(None);
+    }
+
+    let tx = erc20
+        .approve(contract_address, amount)
+        .into_transaction_request();
+    let (tx_hash, _) = execute(app_state, tx).await?;
+    info!(
+        "Approved {} of token {} to the Yield contract in transaction {}",
+        amount, token, tx_hash
+    );
+
+    Ok(Some(tx_hash))
+}
+
+/// Whether the Yield contract may move the positions of the signer wallet on the position
+/// manager of a DEX, which `removeLiquidity` and `rebalance` need
+pub async fn positions_approved(app_state: &AppState, dex_type: &DexType) -> Result<bool> {
+    let nfpm_address = nfpm_address(app_state, dex_type).await?;
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+    let signer = app_state.evm_provider.default_signer_address();
+
+    Ok(app_state
+        .metrics
+        .track_rpc(
+            "isApprovedForAll",
+            nfpm.isApprovedForAll(signer, CONFIG.get().contract_address)
+                .call(),
+        )
+        .await?)
+}
+
+/// Approve the Yield contract as operator of the positions of the signer wallet on the
+/// position manager of a DEX, unless it already is
+///
+/// # Returns:
+/// * The hash of the approval, none if the Yield contract was already approved
+async fn approve_positions(app_state: &AppState, dex_type: &DexType) -> Result<Option<TxHash>> {
+    if positions_approved(app_state, dex_type).await? {
+        return Ok(None);
+    }
+
+    let nfpm_address = nfpm_address(app_state, dex_type).await?;
+    let nfpm = INonfungiblePositionManager::new(nfpm_address, &app_state.evm_provider);
+    let tx = nfpm
+        .setApprovalForAll(CONFIG.get().contract_address, true)
+        .into_transaction_request();
+    let (tx_hash, _) = execute(app_state, tx).await?;
+    info!(
+        "Approved the Yield contract on position manager {} in transaction {}",
+        nfpm_address, tx_hash
+    );
+
+    Ok(Some(tx_hash))
+}
+
+/// Send a transaction through the tx manager and wait for it to be mined
+///
+/// # Returns:
+/// * The hash and receipt of the transaction, or an error if it reverted
+async fn execute(
+    app_state: &AppState,
+    tx: TransactionRequest,
+) -> Result<(TxHash, TransactionReceipt)> {
+    let pending = app_state
+        .tx_manager
+        .send(&app_state.metrics, &app_state.pools, tx)
+        .await?;
+    let tx_hash: TxHash = *pending.tx_hash();
+    let receipt = pending.get_receipt().await?;
+    if !receipt.status() {
+        bail!("Transaction {} reverted", tx_hash);
+    }
+    Ok((tx_hash, receipt))
+}
+
+/// Tracked pool of a position, only their executions are recorded
+fn tracked_pool(app_state: &AppState, position: &Position) -> Option<Pool> {
+    let address = position.pool.as_ref()?.to_lowercase();
+    app_state.pools.get(&address).map(|pool| pool.clone())
+}
+
+/// Add an execution to the ledger. The transaction is already mined, failing to persist
+/// it is only logged
+fn record_execution(app_state: &AppState, execution: Option<Execution>) {
+    let Some(execution) = execution else {
+        return;
+    };
+    let tx_hash = execution.tx_hash.clone();
+    if let Err(e) = app_state.executions.record(execution) {
+        warn!(
+            "Failed to record the execution of transaction {}: {:#}",
+            tx_hash, e
+        );
+    }
+}
+
+/// Snapshot a position around an execution. A failure is only logged, the snapshots must
+/// never prevent an execution
+async fn try_snapshot(
+    app_state: &AppState,
+    token_id: U256,
+    phase: SnapshotPhase,
+) -> Option<PositionSnapshot> {
+    match snapshot_position(app_state, token_id, phase).await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            warn!("Failed to snapshot position {}: {:#}", token_id, e);
+            None
+        }
+    }
+}
+
+/// Persist the snapshots taken around a mined execution
+fn record_snapshots<const N: usize>(
+    app_state: &AppState,
+    kind: ExecutionKind,
+    tx_hash: TxHash,
+    snapshots: [Option<PositionSnapshot>; N],
+) {
+    let snapshots = snapshots
+        .into_iter()
+        .flatten()
+        .map(|snapshot| PositionSnapshot {
+            kind: Some(kind),
+            tx_hash: Some(tx_hash.to_string()),
+            ..snapshot
+        })
+        .collect();
+    if let Err(e) = app_state.snapshots.record(snapshots) {
+        warn!(
+            "Failed to record the snapshots of transaction {}: {:#}",
+            tx_hash, e
+        );
+    }
+}
+
+/// First event of the given type emitted in a transaction
+fn decode_event<E: SolEvent>(receipt: &TransactionReceipt) -> Option<E> {
+    receipt
+        .inner
+        .logs()
+        .iter()
+        .find_map(|log| log.log_decode::<E>().ok())
+        .map(|log| log.inner.data)
+}
+
+/// Check a tick range is ordered, within the protocol bounds and aligned on the pool tick spacing
+fn validate_tick_range(pool: &Pool, tick_lower: i32, tick_upper: i32) -> Result<()> {
+    if tick_lower >= tick_upper {
+        bail!("tick_lower must be lower than tick_upper");
+    }
+    if tick_lower < MIN_TICK || tick_upper > MAX_TICK {
+        bail!("Ticks must be between {} and {}", MIN_TICK, MAX_TICK);
+    }
+    if pool.tick_spacing <= 0
+        || tick_lower % pool.tick_spacing != 0
+        || tick_upper % pool.tick_spacing != 0
+    {
+        bail!(
+            "Ticks must be multiples of the pool tick spacing ({})",
+            pool.tick_spacing
+        );
+    }
+    Ok(())
+}
+
+/// Parse a token amount given as a decimal string in the smallest unit of the token
+fn parse_amount(amount: &str, name: &str) -> Result<U256> {
+    U256::from_str_radix(amount, 10).with_context(|| format!("Invalid {}", name))
+}
+
+/// Parse an optional token amount, defaulting to 0
+pub fn parse_optional_amount(amount: Option<&str>, name: &str) -> Result<U256> {
+    amount.map_or(Ok(U256::ZERO), |amount| parse_amount(amount, name))
+}
+
+/// Complete a position with the prices and range status of its tracked pool
+fn apply_pool(position: &mut Position, pool: &Pool) -> Result<()> {
+    let decimals0 = pool.token0.decimals;
+    let decimals1 = pool.token1.decimals;
+
+    position.pool = Some(pool.address.clone());
+    position.price_lower = Some(utils::amm_math::tick_to_price(
+        position.tick_lower,
+        decimals0,
+        decimals1,
+    )?);
+    position.price_upper = Some(utils::amm_math::tick_to_price(
+        position.tick_upper,
+        decimals0,
+        decimals1,
+    )?);
+    position.in_range =
+        Some(position.tick_lower <= pool.current_tick && pool.current_tick < position.tick_upper);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{RebalanceSwap, Token};
+
+    const TOKEN0: &str = "0x1111111111111111111111111111111111111111";
+    const TOKEN1: &str = "0x2222222222222222222222222222222222222222";
+
+    fn pool(tick_spacing: i32) -> Pool {
+        let token = |address: &str| Token {
+            address: address.to_string(),
+            symbol: "T".to_string(),
+            decimals: 18,
+        };
+        Pool {
+            address: "0x3333333333333333333333333333333333333333".to_string(),
+            dex_type: DexType::PancakeSwapV3,
+            token0: token(TOKEN0),
+            token1: token(TOKEN1),
+            fee: 0.05,
+            tick_spacing,
+            current_tick: 0,
+            price0: 1.0,
+            price1: 1.0,
+            liquidity: 0,
+            updated_at: 0,
+            block_number: 0,
+            annotation: None,
+        }
+    }
+
+    fn mint_request(
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0: &str,
+        amount1: &str,
+    ) -> MintPositionRequest {
+        MintPositionRequest {
+            pool: pool(10).address,
+            tick_lower,
+            tick_upper,
+            amount0_desired: amount0.to_string(),
+            amount1_desired: amount1.to_string(),
+            amount0_min: None,
+            amount1_min: Some("5".to_string()),
+        }
+    }
+
+    fn position() -> Position {
+        Position {
+            token_id: "42".to_string(),
+            dex_type: DexType::PancakeSwapV3,
+            owner: Address::ZERO.to_string(),
+            token0: TOKEN0.to_string(),
+            token1: TOKEN1.to_string(),
+            fee: 0.05,
+            tick_lower: -100,
+            tick_upper: 100,
+            liquidity: 1_000,
+            uncollected_fees0: "0".to_string(),
+            uncollected_fees1: "0".to_string(),
+            pool: None,
+            price_lower: None,
+            price_upper: None,
+            in_range: None,
+            annotation: None,
+        }
+    }
+
+    fn rebalance_request(swap: Option<(&str, &str)>) -> RebalancePositionRequest {
+        RebalancePositionRequest {
+            tick_lower: -200,
+            tick_upper: 200,
+            swap: swap.map(|(token_in, amount)| RebalanceSwap {
+                token_in: token_in.to_string(),
+                amount: amount.to_string(),
+                amount_out_min: None,
+            }),
+        }
+    }
+
+    fn error(result: Result<impl std::fmt::Debug>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn accepts_an_ordered_range_on_the_tick_spacing() {
+        assert!(validate_tick_range(&pool(10), -100, 100).is_ok());
+        assert!(validate_tick_range(&pool(1), MIN_TICK, MAX_TICK).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_unordered_range() {
+        let expected = "tick_lower must be lower than tick_upper";
+        assert_eq!(error(validate_tick_range(&pool(10), 100, -100)), expected);
+        assert_eq!(error(validate_tick_range(&pool(10), 100, 100)), expected);
+    }
+
+    #[test]
+    fn rejects_the_ticks_out_of_bounds() {
+        let expected = format!("Ticks must be between {} and {}", MIN_TICK, MAX_TICK);
+        assert_eq!(
+            error(validate_tick_range(&pool(1), MIN_TICK - 1, 0)),
+            expected
+        );
+        assert_eq!(
+            error(validate_tick_range(&pool(1), 0, MAX_TICK + 1)),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_the_ticks_off_the_tick_spacing() {
+        assert_eq!(
+            error(validate_tick_range(&pool(10), -100, 105)),
+            "Ticks must be multiples of the pool tick spacing (10)"
+        );
+        assert_eq!(
+            error(validate_tick_range(&pool(0), -100, 100)),
+            "Ticks must be multiples of the pool tick spacing (0)"
+        );
+        assert_eq!(
+            error(validate_tick_range(&pool(-10), -100, 100)),
+            "Ticks must be multiples of the pool tick spacing (-10)"
+        );
+    }
+
+    #[test]
+    fn builds_the_mint_params() {
+        let recipient = Address::repeat_byte(9);
+        let params =
+            build_mint_params(&pool(10), &mint_request(-100, 100, "0", "7"), recipient).unwrap();
+
+        assert_eq!(params.token0, Address::from_str(TOKEN0).unwrap());
+        assert_eq!(params.token1, Address::from_str(TOKEN1).unwrap());
+        // A 0.05% fee is 500 hundredths of a basis point
+        assert_eq!(params.fee, U24::from(500));
+        assert_eq!(params.tickLower, I24::try_from(-100).unwrap());
+        assert_eq!(params.tickUpper, I24::try_from(100).unwrap());
+        assert_eq!(params.amount0Desired, U256::ZERO);
+        assert_eq!(params.amount1Desired, U256::from(7));
+        assert_eq!(params.amount0Min, U256::ZERO);
+        assert_eq!(params.amount1Min, U256::from(5));
+        assert_eq!(params.recipient, recipient);
+    }
+
+    #[test]
+    fn rejects_a_mint_without_any_amount() {
+        assert_eq!(
+            error(build_mint_params(
+                &pool(10),
+                &mint_request(-100, 100, "0", "0"),
+                Address::ZERO
+            )),
+            "At least one of the desired amounts must be positive"
+        );
+        assert_eq!(
+            error(build_mint_params(
+                &pool(10),
+                &mint_request(-100, 100, "-1", "0"),
+                Address::ZERO
+            )),
+            "Invalid amount0_desired"
+        );
+        assert!(
+            build_mint_params(&pool(10), &mint_request(-100, 105, "1", "1"), Address::ZERO)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn builds_the_rebalance_params_with_a_swap() {
+        let params = build_rebalance_params(
+            &position(),
+            &pool(10),
+            &rebalance_request(Some((TOKEN1, "30"))),
+        )
+        .unwrap();
+
+        assert_eq!(params.token_id, U256::from(42));
+        assert_eq!(params.swap_token_in, Address::from_str(TOKEN1).unwrap());
+        assert_eq!(params.swap_token_out, Address::from_str(TOKEN0).unwrap());
+        assert_eq!(params.swap_amount, U256::from(30));
+        assert_eq!(params.swap_amount_out_min, U256::ZERO);
+        assert_eq!(params.swap_fee, U24::from(500));
+    }
+
+    #[test]
+    fn builds_the_rebalance_params_without_a_swap() {
+        let params =
+            build_rebalance_params(&position(), &pool(10), &rebalance_request(None)).unwrap();
+
+        assert_eq!(params.swap_token_in, Address::from_str(TOKEN0).unwrap());
+        assert_eq!(params.swap_amount, U256::ZERO);
+    }
+
+    #[test]
+    fn rejects_a_swap_of_another_token() {
+        let request = rebalance_request(Some(("0x4444444444444444444444444444444444444444", "30")));
+        assert_eq!(
+            error(build_rebalance_params(&position(), &pool(10), &request)),
+            "The swap token_in must be one of the pool tokens"
+        );
+
+        let request = rebalance_request(Some(("not an address", "30")));
+        assert_eq!(
+            error(build_rebalance_params(&position(), &pool(10), &request)),
+            "Invalid swap token_in"
+        );
+    }
+
+    #[test]
+    fn scales_the_fee_to_hundredths_of_a_basis_point() {
+        for (fee, scaled) in [
+            (0.01, 100),
+            (0.05, 500),
+            (0.25, 2_500),
+            (0.3, 3_000),
+            (1.0, 10_000),
+        ] {
+            let pool = Pool { fee, ..pool(10) };
+            let params =
+                build_mint_params(&pool, &mint_request(-100, 100, "1", "1"), Address::ZERO)
+                    .unwrap();
+            assert_eq!(params.fee, U24::from(scaled));
+        }
+    }
+}
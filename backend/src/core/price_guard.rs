@@ -0,0 +1,182 @@
+use std::str::FromStr;
+
+use alloy::{primitives::Address, sol};
+use anyhow::{Context, Result, anyhow};
+use tracing::error;
+
+use crate::{
+    config::{CONFIG, PriceGuardConfig},
+    core::pools::fetch_pool_blockchain_details,
+    state::AppState,
+    types::Pool,
+};
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IPoolOracle {
+        function observe(uint32[] calldata secondsAgos) external view returns (
+            int56[] tickCumulatives,
+            uint160[] secondsPerLiquidityCumulativeX128s
+        );
+    }
+);
+
+/// Check the prices of a pool before an execution, like a circuit breaker: the spot price
+/// must be close to both the tracked price the execution was built from and the TWAP
+///
+/// A divergence means the tracked state is stale or the spot price is being manipulated.
+/// The execution is then aborted and the trip is counted by the
+/// `price_guard_trips_total` metric, which a critical alert watches.
+pub async fn check_prices(app_state: &AppState, pool: &Pool) -> Result<()> {
+    let config = CONFIG.get().toml.price_guard.clone();
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let spot = fetch_pool_blockchain_details(
+        &app_state.evm_provider,
+        &app_state.metrics,
+        &pool.address,
+        &pool.dex_type,
+    )
+    .await
+    .context("Failed to read the spot price")?;
+    let twap_tick = twap_tick(app_state, &pool.address, config.twap_secs)
+        .await
+        .context("Failed to read the TWAP")?;
+
+    match divergence(&config, pool.current_tick, spot.current_tick, twap_tick) {
+        Some((reason, details)) => Err(trip(app_state, pool, reason, details)),
+        None => Ok(()),
+    }
+}
+
+/// Compare the spot tick to the tracked tick the execution was built from, then to the
+/// TWAP tick
+///
+/// A missing TWAP counts as a divergence, the guard fails closed.
+///
+/// # Returns:
+/// * The reason and details of the first divergence above its threshold, `None` if the
+///   prices agree
+fn divergence(
+    config: &PriceGuardConfig,
+    tracked_tick: i32,
+    spot_tick: i32,
+    twap_tick: Option<f64>,
+) -> Option<(&'static str, String)> {
+    let stale = tick_deviation_percent(tracked_tick as f64, spot_tick as f64);
+    if stale > config.max_stale_deviation_percent {
+        return Some((
+            "stale",
+            format!(
+                "the tracked price is {:.2}% away from the spot price, above {}%",
+                stale, config.max_stale_deviation_percent
+            ),
+        ));
+    }
+
+    let Some(twap_tick) = twap_tick.filter(|tick| tick.is_finite()) else {
+        return Some((
+            "twap",
+            format!("no {}s TWAP to compare the spot price to", config.twap_secs),
+        ));
+    };
+    let manipulated = tick_deviation_percent(spot_tick as f64, twap_tick);
+    if manipulated > config.max_twap_deviation_percent {
+        return Some((
+            "twap",
+            format!(
+                "the spot price is {:.2}% away from the {}s TWAP, above {}%",
+                manipulated, config.twap_secs, config.max_twap_deviation_percent
+            ),
+        ));
+    }
+
+    None
+}
+
+/// Arithmetic mean of the tick of a pool over the last `secs` seconds, `None` over an
+/// empty window
+async fn twap_tick(app_state: &AppState, address: &str, secs: u32) -> Result<Option<f64>> {
+    if secs == 0 {
+        return Ok(None);
+    }
+    let pool_contract = IPoolOracle::new(Address::from_str(address)?, &app_state.evm_provider);
+    let observations = app_state
+        .metrics
+        .track_rpc("observe", pool_contract.observe(vec![secs, 0]).call())
+        .await?;
+
+    let [start, end] = observations.tickCumulatives[..] else {
+        anyhow::bail!("Unexpected number of observations");
+    };
+    Ok(Some((end.as_i64() - start.as_i64()) as f64 / secs as f64))
+}
+
+/// Divergence between the prices of two ticks, in percent of the lowest one
+fn tick_deviation_percent(tick: f64, other: f64) -> f64 {
+    (1.0001f64.powf((tick - other).abs()) - 1.0) * 100.0
+}
+
+fn trip(app_state: &AppState, pool: &Pool, reason: &str, details: String) -> anyhow::Error {
+    app_state
+        .metrics
+        .record_price_guard_trip(&pool.address, reason);
+    error!("Price guard tripped on pool {}: {}", pool.address, details);
+    anyhow!("Execution aborted by the price guard: {}", details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PriceGuardConfig {
+        PriceGuardConfig {
+            enabled: true,
+            twap_secs: 300,
+            max_twap_deviation_percent: 2.0,
+            max_stale_deviation_percent: 1.0,
+        }
+    }
+
+    fn reason(tracked_tick: i32, spot_tick: i32, twap_tick: Option<f64>) -> Option<&'static str> {
+        divergence(&config(), tracked_tick, spot_tick, twap_tick).map(|(reason, _)| reason)
+    }
+
+    #[test]
+    fn measures_the_divergence_between_two_ticks() {
+        assert_eq!(tick_deviation_percent(0.0, 0.0), 0.0);
+        let deviation = tick_deviation_percent(-100.0, 0.0);
+        assert!((deviation - (1.0001f64.powi(100) - 1.0) * 100.0).abs() < 1e-9);
+        assert_eq!(deviation, tick_deviation_percent(0.0, -100.0));
+    }
+
+    #[test]
+    fn passes_under_the_thresholds() {
+        // 1.0001^99 is 0.99% and 1.0001^198 is 1.98% away
+        assert_eq!(reason(1_000, 1_099, Some(1_099.0 - 198.0)), None);
+        assert_eq!(reason(0, 0, Some(0.0)), None);
+    }
+
+    #[test]
+    fn aborts_a_stale_execution() {
+        assert_eq!(reason(1_000, 1_101, Some(1_101.0)), Some("stale"));
+        assert_eq!(reason(1_000, 899, Some(899.0)), Some("stale"));
+    }
+
+    #[test]
+    fn aborts_when_the_spot_price_leaves_the_twap() {
+        assert_eq!(reason(1_000, 1_000, Some(1_200.0)), Some("twap"));
+        assert_eq!(reason(-50, -50, Some(150.0)), Some("twap"));
+    }
+
+    #[test]
+    fn aborts_without_a_twap() {
+        assert_eq!(reason(0, 0, None), Some("twap"));
+        assert_eq!(reason(0, 0, Some(f64::NAN)), Some("twap"));
+        // The stale check still comes first
+        assert_eq!(reason(0, 500, None), Some("stale"));
+    }
+}
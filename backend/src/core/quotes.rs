@@ -0,0 +1,338 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{Address, Bytes},
+    sol,
+    sol_types::SolCall,
+};
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{config::CONFIG, state::AppState, types::DexType};
+
+sol!(
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Result[] memory returnData);
+    }
+);
+
+sol!(
+    #[derive(Debug)]
+    interface IPoolState {
+        // `feeProtocol` is an uint8 on Uniswap V3 and an uint32 on PancakeSwap V3, both
+        // decode as an uint32
+        function slot0() external view returns (
+            uint160 sqrtPriceX96,
+            int24 tick,
+            uint16 observationIndex,
+            uint16 observationCardinality,
+            uint16 observationCardinalityNext,
+            uint32 feeProtocol,
+            bool unlocked
+        );
+
+        function liquidity() external view returns (uint128);
+    }
+);
+
+/// Result of swapping a size of a token in one tracked pool
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolQuote {
+    pub pool: String,
+    pub dex_type: DexType,
+    /// Swap fee, in percent
+    pub fee: f64,
+    /// Address of the bought token
+    pub token_out: String,
+    pub symbol_out: String,
+    /// Bought amount, in the smallest unit of `token_out`
+    pub amount_out: String,
+    /// Spot price of the sold token in the bought one, before the swap
+    pub spot_price: f64,
+    /// How much worse than the spot price the swap executes, fee excluded, in percent
+    pub price_impact_percent: f64,
+}
+
+/// Tracked pool whose state couldn't be read
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FailedQuote {
+    pub pool: String,
+    pub error: String,
+}
+
+/// Quotes of a swap size in every tracked pool of a token
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Quotes {
+    pub token: String,
+    /// Sold amount, in the smallest unit of `token`
+    pub amount_in: String,
+    /// Grouped by bought token, the best output first within a group. The outputs of
+    /// different bought tokens are not comparable.
+    pub quotes: Vec<PoolQuote>,
+    /// Pools left out because their state couldn't be read
+    pub failed: Vec<FailedQuote>,
+}
+
+/// Quote selling `amount_in` of `token` in every tracked pool containing it
+///
+/// The price and in-range liquidity of the pools are read on-chain in a single Multicall3
+/// call, a pool failing to answer is listed in `failed` instead of failing the whole
+/// quote. The swap is computed within the current liquidity, so the impact of a size
+/// crossing initialized ticks is an estimate.
+pub async fn quote_all(app_state: &AppState, token: Address, amount_in: u128) -> Result<Quotes> {
+    let token_key = token.to_string();
+    let mut pools: Vec<_> = app_state
+        .pools
+        .iter()
+        .filter(|entry| {
+            entry.token0.address.eq_ignore_ascii_case(&token_key)
+                || entry.token1.address.eq_ignore_ascii_case(&token_key)
+        })
+        .map(|entry| entry.value().clone())
+        .collect();
+    pools.sort_by(|a, b| a.address.cmp(&b.address));
+
+    if pools.is_empty() {
+        return Ok(Quotes {
+            token: token_key,
+            amount_in: amount_in.to_string(),
+            quotes: Vec::new(),
+            failed: Vec::new(),
+        });
+    }
+
+    let multicall = CONFIG
+        .get()
+        .toml
+        .addresses
+        .multicall
+        .context("addresses.multicall must be configured to quote")?;
+    let mut calls = Vec::with_capacity(pools.len() * 2);
+    for pool in &pools {
+        let target = Address::from_str(&pool.address)?;
+        for call_data in [
+            IPoolState::slot0Call {}.abi_encode(),
+            IPoolState::liquidityCall {}.abi_encode(),
+        ] {
+            calls.push(IMulticall3::Call3 {
+                target,
+                allowFailure: true,
+                callData: Bytes::from(call_data),
+            });
+        }
+    }
+
+    let multicall = IMulticall3::new(multicall, &app_state.evm_provider);
+    let results = app_state
+        .metrics
+        .track_rpc("aggregate3", multicall.aggregate3(calls).call())
+        .await?;
+    if results.len() != pools.len() * 2 {
+        return Err(anyhow!("Unexpected number of multicall results"));
+    }
+
+    let mut quotes = Vec::with_capacity(pools.len());
+    let mut failed = Vec::new();
+    for (pool, results) in pools.iter().zip(results.chunks(2)) {
+        let state = decode_pool_state(&results[0], &results[1]);
+        let (slot0, liquidity) = match state {
+            Ok(state) => state,
+            Err(e) => {
+                failed.push(FailedQuote {
+                    pool: pool.address.clone(),
+                    error: format!("{:#}", e),
+                });
+                continue;
+            }
+        };
+
+        let zero_for_one = pool.token0.address.eq_ignore_ascii_case(&token_key);
+        let (decimals_in, decimals_out, token_out) = if zero_for_one {
+            (pool.token0.decimals, pool.token1.decimals, &pool.token1)
+        } else {
+            (pool.token1.decimals, pool.token0.decimals, &pool.token0)
+        };
+        let sqrt_price = f64::from(slot0.sqrtPriceX96) / 2f64.powi(96);
+        let amount_in_after_fee = amount_in as f64 * (1.0 - pool.fee / 100.0);
+        let (amount_out, raw_spot_price) = swap_within_liquidity(
+            sqrt_price,
+            liquidity as f64,
+            amount_in_after_fee,
+            zero_for_one,
+        );
+
+        let price_impact_percent = if amount_out > 0.0 {
+            (1.0 - amount_out / amount_in_after_fee / raw_spot_price) * 100.0
+        } else {
+            100.0
+        };
+
+        quotes.push((
+            amount_out,
+            PoolQuote {
+                pool: pool.address.clone(),
+                dex_type: pool.dex_type.clone(),
+                fee: pool.fee,
+                token_out: token_out.address.clone(),
+                symbol_out: token_out.symbol.clone(),
+                amount_out: format!("{:.0}", amount_out.floor()),
+                spot_price: raw_spot_price * 10f64.powi(decimals_in as i32 - decimals_out as i32),
+                price_impact_percent,
+            },
+        ));
+    }
+    rank(&mut quotes);
+
+    Ok(Quotes {
+        token: token_key,
+        amount_in: amount_in.to_string(),
+        quotes: quotes.into_iter().map(|(_, quote)| quote).collect(),
+        failed,
+    })
+}
+
+/// Decode the `slot0` and `liquidity` results of a pool
+fn decode_pool_state(
+    slot0: &IMulticall3::Result,
+    liquidity: &IMulticall3::Result,
+) -> Result<(IPoolState::slot0Return, u128)> {
+    if !slot0.success {
+        return Err(anyhow!("slot0 reverted"));
+    }
+    if !liquidity.success {
+        return Err(anyhow!("liquidity reverted"));
+    }
+    Ok((
+        IPoolState::slot0Call::abi_decode_returns(&slot0.returnData)?,
+        IPoolState::liquidityCall::abi_decode_returns(&liquidity.returnData)?,
+    ))
+}
+
+/// Sort the quotes by bought token, then by output from the best, the outputs being in
+/// the smallest unit of the bought token
+fn rank(quotes: &mut [(f64, PoolQuote)]) {
+    quotes.sort_by(|(a, quote_a), (b, quote_b)| {
+        quote_a
+            .token_out
+            .to_lowercase()
+            .cmp(&quote_b.token_out.to_lowercase())
+            .then(b.total_cmp(a))
+    });
+}
+
+/// Output of a swap that stays within the current liquidity of a pool
+///
+/// # Arguments:
+/// * `amount_in` - Sold amount once the swap fee is taken, in smallest units
+///
+/// # Returns:
+/// * The bought amount and the spot price of the sold token, both in smallest units
+fn swap_within_liquidity(
+    sqrt_price: f64,
+    liquidity: f64,
+    amount_in: f64,
+    zero_for_one: bool,
+) -> (f64, f64) {
+    if liquidity <= 0.0 || sqrt_price <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    if zero_for_one {
+        let next_sqrt_price = liquidity * sqrt_price / (liquidity + amount_in * sqrt_price);
+        (
+            liquidity * (sqrt_price - next_sqrt_price),
+            sqrt_price * sqrt_price,
+        )
+    } else {
+        let next_sqrt_price = sqrt_price + amount_in / liquidity;
+        (
+            liquidity * (1.0 / sqrt_price - 1.0 / next_sqrt_price),
+            1.0 / (sqrt_price * sqrt_price),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(token_out: &str, amount_out: f64) -> (f64, PoolQuote) {
+        (
+            amount_out,
+            PoolQuote {
+                pool: format!("{}-{}", token_out, amount_out),
+                dex_type: DexType::UniswapV3,
+                fee: 0.05,
+                token_out: token_out.to_string(),
+                symbol_out: token_out.to_string(),
+                amount_out: amount_out.to_string(),
+                spot_price: 1.0,
+                price_impact_percent: 0.0,
+            },
+        )
+    }
+
+    #[test]
+    fn swaps_within_the_liquidity() {
+        let (amount_out, spot_price) = swap_within_liquidity(1.0, 1_000.0, 10.0, true);
+        assert!((amount_out - 1_000.0 * (1.0 - 1_000.0 / 1_010.0)).abs() < 1e-9);
+        assert_eq!(spot_price, 1.0);
+
+        let (amount_out, spot_price) = swap_within_liquidity(2.0, 1_000.0, 10.0, false);
+        assert!((amount_out - 1_000.0 * (1.0 / 2.0 - 1.0 / 2.01)).abs() < 1e-9);
+        assert_eq!(spot_price, 0.25);
+    }
+
+    #[test]
+    fn small_swaps_execute_at_the_spot_price() {
+        for zero_for_one in [true, false] {
+            let (amount_out, spot_price) = swap_within_liquidity(1.5, 1e12, 1e3, zero_for_one);
+            assert!((amount_out / 1e3 / spot_price - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn nothing_is_bought_without_liquidity() {
+        assert_eq!(swap_within_liquidity(1.0, 0.0, 10.0, true), (0.0, 0.0));
+        assert_eq!(swap_within_liquidity(0.0, 1_000.0, 10.0, false), (0.0, 0.0));
+    }
+
+    #[test]
+    fn ranks_the_outputs_of_a_token_only_against_each_other() {
+        let mut quotes = vec![
+            quote("0xB", 5.0),
+            quote("0xA", 1.0),
+            quote("0xb", 7.0),
+            quote("0xA", 3.0),
+        ];
+        rank(&mut quotes);
+
+        let ranked: Vec<_> = quotes
+            .iter()
+            .map(|(amount_out, quote)| (quote.token_out.to_lowercase(), *amount_out))
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                ("0xa".to_string(), 3.0),
+                ("0xa".to_string(), 1.0),
+                ("0xb".to_string(), 7.0),
+                ("0xb".to_string(), 5.0),
+            ]
+        );
+    }
+}
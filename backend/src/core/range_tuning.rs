@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::rt;
+use dashmap::DashMap;
+use serde::Serialize;
+use tracing::{debug, info};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{
+        DEFAULT_RANGE_WIDTH_SPACINGS, MAX_RANGE_TUNING_HISTORY, RANGE_TUNING_INTERVAL_SECS,
+        RANGE_TUNING_MAX_STEP, RANGE_TUNING_MIN_HISTORY_SECS, RANGE_TUNING_TARGET_PER_WEEK,
+        RANGE_TUNING_WINDOW_SECS,
+    },
+    core::{analytics::PriceHistory, swaps},
+    state::AppState,
+    types::Pool,
+    utils::time::unix_timestamp,
+};
+
+const WEEK_SECS: f64 = (7 * 24 * 60 * 60) as f64;
+
+/// One change of the default range width of a pool
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WidthAdjustment {
+    /// Unix timestamp (seconds) of the adjustment
+    pub timestamp: u64,
+    pub previous_width: i32,
+    pub width: i32,
+    /// Out of range events per week the previous width had over the tuning window
+    pub crossings_per_week: f64,
+}
+
+/// Default range width of a pool, with the adjustments that led to it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TunedRangeWidth {
+    pub address: String,
+    /// Width in ticks, a multiple of the tick spacing
+    pub width: i32,
+    pub target_crossings_per_week: f64,
+    /// Most recent adjustments, oldest first
+    pub history: Vec<WidthAdjustment>,
+}
+
+#[derive(Debug, Clone)]
+struct TunedWidth {
+    width: i32,
+    history: VecDeque<WidthAdjustment>,
+}
+
+/// Feedback controller of the default range width of each pool
+///
+/// The recorded ticks of the last `RANGE_TUNING_WINDOW_SECS` are replayed against the
+/// current width: a range going out of range more often than
+/// `RANGE_TUNING_TARGET_PER_WEEK` is widened, a range going out of range less often is
+/// narrowed. The exit time of a random walk grows with the square of the width, so the
+/// width is scaled by the square root of the ratio, by at most `RANGE_TUNING_MAX_STEP`
+/// per adjustment.
+#[derive(Debug, Default)]
+pub struct RangeTuner {
+    widths: DashMap<String, TunedWidth>,
+}
+
+impl RangeTuner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current default range width of a pool, `DEFAULT_RANGE_WIDTH_SPACINGS` tick spacings
+    /// until it has been tuned
+    pub fn width(&self, address: &str, pool: &Pool) -> i32 {
+        self.widths.get(address).map_or(
+            DEFAULT_RANGE_WIDTH_SPACINGS * pool.tick_spacing.max(1),
+            |tuned| tuned.width,
+        )
+    }
+
+    pub fn tuned_width(&self, address: &str, pool: &Pool) -> TunedRangeWidth {
+        TunedRangeWidth {
+            address: pool.address.clone(),
+            width: self.width(address, pool),
+            target_crossings_per_week: RANGE_TUNING_TARGET_PER_WEEK,
+            history: self
+                .widths
+                .get(address)
+                .map(|tuned| tuned.history.iter().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Forget the width of a pool that is not tracked anymore
+    pub fn forget(&self, address: &str) {
+        self.widths.remove(address);
+    }
+
+    /// Adjust the width of a pool from its recorded ticks
+    ///
+    /// # Returns:
+    /// * The adjustment, `None` when there isn't enough history or the out of range
+    ///   frequency is already close to the target
+    pub fn tune(
+        &self,
+        address: &str,
+        pool: &Pool,
+        price_history: &PriceHistory,
+        now: u64,
+    ) -> Option<WidthAdjustment> {
+        let ticks =
+            price_history.ticks_since(address, now.saturating_sub(RANGE_TUNING_WINDOW_SECS));
+        let covered_secs = now.saturating_sub(ticks.first()?.0);
+        if covered_secs < RANGE_TUNING_MIN_HISTORY_SECS {
+            return None;
+        }
+
+        let previous_width = self.width(address, pool);
+        let crossings = swaps::count_crossings(
+            ticks.into_iter().map(|(_, tick)| tick),
+            pool.tick_spacing,
+            previous_width,
+        );
+        let crossings_per_week = crossings as f64 * WEEK_SECS / covered_secs as f64;
+
+        // No crossing at all gives a zero ratio, clamped to a full narrowing step
+        let ratio = (crossings_per_week / RANGE_TUNING_TARGET_PER_WEEK).sqrt();
+        let factor = ratio.clamp(1.0 / RANGE_TUNING_MAX_STEP, RANGE_TUNING_MAX_STEP);
+        let width = swaps::round_width(
+            (previous_width as f64 * factor).round() as i32,
+            pool.tick_spacing,
+        );
+        if width == previous_width {
+            return None;
+        }
+
+        let adjustment = WidthAdjustment {
+            timestamp: now,
+            previous_width,
+            width,
+            crossings_per_week,
+        };
+
+        let mut tuned = self
+            .widths
+            .entry(address.to_string())
+            .or_insert_with(|| TunedWidth {
+                width: previous_width,
+                history: VecDeque::new(),
+            });
+        tuned.width = width;
+        tuned.history.push_back(adjustment.clone());
+        while tuned.history.len() > MAX_RANGE_TUNING_HISTORY {
+            tuned.history.pop_front();
+        }
+
+        Some(adjustment)
+    }
+}
+
+/// Tune the range width of every tracked pool every `RANGE_TUNING_INTERVAL_SECS`
+pub fn spawn_range_tuner(app_state: Arc<AppState>) {
+    info!(
+        "Tuning range widths every {}s toward {} out of range events per week",
+        RANGE_TUNING_INTERVAL_SECS, RANGE_TUNING_TARGET_PER_WEEK
+    );
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(RANGE_TUNING_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let now = unix_timestamp();
+            let pools: Vec<(String, Pool)> = app_state
+                .pools
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect();
+
+            for (address, pool) in pools {
+                match app_state
+                    .range_tuner
+                    .tune(&address, &pool, &app_state.price_history, now)
+                {
+                    Some(adjustment) => info!(
+                        "Range width of pool {} tuned from {} to {} ticks ({:.2} out of range events per week)",
+                        address,
+                        adjustment.previous_width,
+                        adjustment.width,
+                        adjustment.crossings_per_week
+                    ),
+                    None => debug!("Range width of pool {} left unchanged", address),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        types::{DexType, Token},
+        utils::amm_math::MAX_RANGE_WIDTH,
+    };
+
+    const NOW: u64 = 100 * 7 * 24 * 60 * 60;
+    const HOUR_SECS: u64 = 60 * 60;
+
+    fn pool(tick_spacing: i32) -> Pool {
+        let token = |symbol: &str| Token {
+            address: format!("0x{}", symbol),
+            symbol: symbol.to_string(),
+            decimals: 18,
+        };
+        Pool {
+            address: "0xpool".to_string(),
+            dex_type: DexType::UniswapV3,
+            token0: token("A"),
+            token1: token("B"),
+            fee: 0.3,
+            tick_spacing,
+            current_tick: 0,
+            price0: 1.0,
+            price1: 1.0,
+            liquidity: 1_000,
+            updated_at: 0,
+            block_number: 0,
+            annotation: None,
+        }
+    }
+
+    /// Price history of `ticks`, the first one taken `covered_secs` ago and the others
+    /// spread evenly until now
+    fn history(pool: &Pool, ticks: &[i32], covered_secs: u64) -> PriceHistory {
+        let history = PriceHistory::new();
+        let step = covered_secs / (ticks.len() as u64 - 1).max(1);
+        for (index, tick) in ticks.iter().enumerate() {
+            let timestamp = NOW - covered_secs + index as u64 * step;
+            history.record_swap("pool", pool, timestamp, *tick);
+        }
+        history
+    }
+
+    #[test]
+    fn waits_for_enough_history() {
+        let (tuner, pool) = (RangeTuner::new(), pool(10));
+        let history = history(&pool, &[0, 1_000, 0, 1_000], 12 * HOUR_SECS);
+
+        assert!(tuner.tune("pool", &pool, &history, NOW).is_none());
+        assert_eq!(tuner.width("pool", &pool), 100);
+    }
+
+    #[test]
+    fn widens_a_range_that_leaves_the_price_often() {
+        let (tuner, pool) = (RangeTuner::new(), pool(10));
+        let ticks: Vec<i32> = (0..20)
+            .map(|i| if i % 2 == 0 { 0 } else { 1_000 })
+            .collect();
+        let history = history(&pool, &ticks, 48 * HOUR_SECS);
+
+        let adjustment = tuner.tune("pool", &pool, &history, NOW).unwrap();
+        // 19 exits over 2 days, widened by the largest step then rounded up to the spacing
+        assert!((adjustment.crossings_per_week - 19.0 * 3.5).abs() < 1e-9);
+        assert_eq!((adjustment.previous_width, adjustment.width), (100, 130));
+        assert_eq!(tuner.width("pool", &pool), 130);
+        assert_eq!(tuner.tuned_width("pool", &pool).history.len(), 1);
+    }
+
+    #[test]
+    fn narrows_a_range_the_price_stays_in() {
+        let (tuner, pool) = (RangeTuner::new(), pool(10));
+        let history = history(&pool, &[0, 10, -10, 0], 48 * HOUR_SECS);
+
+        let adjustment = tuner.tune("pool", &pool, &history, NOW).unwrap();
+        assert_eq!(adjustment.crossings_per_week, 0.0);
+        assert_eq!((adjustment.previous_width, adjustment.width), (100, 80));
+    }
+
+    #[test]
+    fn holds_a_range_at_the_target_frequency() {
+        let (tuner, pool) = (RangeTuner::new(), pool(10));
+        // One exit over the whole week
+        let history = history(&pool, &[0, 0, 1_000, 1_000], RANGE_TUNING_WINDOW_SECS);
+
+        assert!(tuner.tune("pool", &pool, &history, NOW).is_none());
+        assert_eq!(tuner.width("pool", &pool), 100);
+        assert!(tuner.tuned_width("pool", &pool).history.is_empty());
+    }
+
+    #[test]
+    fn never_widens_past_the_widest_range() {
+        let (tuner, pool) = (RangeTuner::new(), pool(1));
+        tuner.widths.insert(
+            "pool".to_string(),
+            TunedWidth {
+                width: 1_500_000,
+                history: VecDeque::new(),
+            },
+        );
+        let ticks: Vec<i32> = (0..20)
+            .map(|i| if i % 2 == 0 { -880_000 } else { 880_000 })
+            .collect();
+        let history = history(&pool, &ticks, 48 * HOUR_SECS);
+
+        let adjustment = tuner.tune("pool", &pool, &history, NOW).unwrap();
+        assert_eq!(adjustment.width, MAX_RANGE_WIDTH);
+        assert_eq!(tuner.width("pool", &pool), MAX_RANGE_WIDTH);
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::rt;
+use dashmap::DashMap;
+use tracing::debug;
+
+use crate::{
+    config::{RATE_LIMIT_IDLE_SECS, RATE_LIMIT_SWEEP_SECS},
+    state::AppState,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket rate limiter keyed by client (API key or IP address)
+///
+/// Every client starts with `burst` tokens, each request takes one and tokens are refilled
+/// at `requests_per_second`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    buckets: DashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst: burst as f64,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Take a token for this client
+    ///
+    /// # Returns:
+    /// * `Ok(())` if the request is allowed
+    /// * `Err(retry_after)` with the time until the next token is available otherwise
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        let elapsed = now
+            .saturating_duration_since(bucket.last_refill)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.requests_per_second).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / self.requests_per_second))
+        }
+    }
+
+    /// Forget the clients idle for long enough that their bucket would be full again
+    fn sweep(&self, now: Instant) {
+        let idle = Duration::from_secs(RATE_LIMIT_IDLE_SECS);
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < idle);
+    }
+}
+
+/// Forget the idle rate limited clients of every limiter every `RATE_LIMIT_SWEEP_SECS`,
+/// for the lifetime of the process
+pub fn spawn_rate_limit_sweeper(app_state: Arc<AppState>) {
+    let rate_limiters: Vec<Arc<RateLimiter>> = app_state
+        .rate_limiter
+        .iter()
+        .chain([&app_state.swap_history_limiter])
+        .cloned()
+        .collect();
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(RATE_LIMIT_SWEEP_SECS));
+        loop {
+            interval.tick().await;
+            for rate_limiter in &rate_limiters {
+                let before = rate_limiter.buckets.len();
+                rate_limiter.sweep(Instant::now());
+                debug!(
+                    "Forgot {} idle rate limited clients",
+                    before.saturating_sub(rate_limiter.buckets.len())
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_the_burst_then_limits() {
+        let limiter = RateLimiter::new(2.0, 3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at("ip:1.2.3.4", now), Ok(()));
+        }
+        let retry_after = limiter.check_at("ip:1.2.3.4", now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn refills_at_the_configured_rate() {
+        let limiter = RateLimiter::new(2.0, 1);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at("key:a", now), Ok(()));
+        assert!(limiter.check_at("key:a", now).is_err());
+        assert_eq!(
+            limiter.check_at("key:a", now + Duration::from_millis(500)),
+            Ok(())
+        );
+        // The bucket never holds more than the burst
+        let later = now + Duration::from_secs(60);
+        assert_eq!(limiter.check_at("key:a", later), Ok(()));
+        assert!(limiter.check_at("key:a", later).is_err());
+    }
+
+    #[test]
+    fn clients_have_their_own_bucket() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check_at("ip:1.2.3.4", now), Ok(()));
+        assert!(limiter.check_at("ip:1.2.3.4", now).is_err());
+        assert_eq!(limiter.check_at("ip:5.6.7.8", now), Ok(()));
+    }
+
+    #[test]
+    fn sweep_forgets_only_idle_clients() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let now = Instant::now();
+        let idle = Duration::from_secs(RATE_LIMIT_IDLE_SECS);
+
+        limiter.check_at("ip:idle", now).unwrap();
+        limiter.check_at("ip:active", now + idle).unwrap();
+        limiter.sweep(now + idle);
+
+        assert!(!limiter.buckets.contains_key("ip:idle"));
+        assert!(limiter.buckets.contains_key("ip:active"));
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket},
+    transports::{BoxTransport, RpcError, TransportError, TransportErrorKind, TransportFut},
+};
+use tower::Service;
+use tracing::warn;
+
+use crate::config::RPC_FAILOVER_MAX_TIMEOUTS;
+
+/// RPC endpoint of a failover transport
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    /// Shown in the logs instead of the url, which may hold an API key
+    pub name: String,
+    pub transport: BoxTransport,
+}
+
+/// Transport sending the requests to one endpoint at a time, rotating to the next one on
+/// connection errors or after `RPC_FAILOVER_MAX_TIMEOUTS` timeouts in a row
+///
+/// A request failing on an endpoint is retried on the following ones, so it only fails
+/// once they all failed. The node error responses are returned as is, another node
+/// would answer the same.
+#[derive(Debug, Clone)]
+pub struct FailoverTransport {
+    endpoints: Arc<Vec<Endpoint>>,
+    /// Index of the endpoint receiving the requests
+    current: Arc<AtomicUsize>,
+    /// Requests of the current endpoint that timed out in a row
+    timeouts: Arc<AtomicU32>,
+    /// Time an endpoint has to answer a request
+    timeout: Duration,
+}
+
+impl FailoverTransport {
+    pub fn new(endpoints: Vec<Endpoint>, timeout: Duration) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "A failover transport needs an endpoint"
+        );
+        Self {
+            endpoints: Arc::new(endpoints),
+            current: Arc::new(AtomicUsize::new(0)),
+            timeouts: Arc::new(AtomicU32::new(0)),
+            timeout,
+        }
+    }
+
+    /// Make `index` the current endpoint, unless another request already rotated away
+    /// from `from`
+    fn rotate(&self, from: usize, index: usize) {
+        if self
+            .current
+            .compare_exchange(from, index, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            self.timeouts.store(0, Ordering::SeqCst);
+            warn!(
+                "Switched RPC endpoint from {} to {}",
+                self.endpoints[from].name, self.endpoints[index].name
+            );
+        }
+    }
+
+    async fn send(self, request: RequestPacket) -> Result<ResponsePacket, TransportError> {
+        let count = self.endpoints.len();
+        let start = self.current.load(Ordering::SeqCst);
+        let mut last_error = None;
+
+        for offset in 0..count {
+            let index = (start + offset) % count;
+            let endpoint = &self.endpoints[index];
+            let mut transport = endpoint.transport.clone();
+
+            match tokio::time::timeout(self.timeout, transport.call(request.clone())).await {
+                Ok(Ok(response)) => {
+                    if index != start {
+                        self.rotate(start, index);
+                    } else {
+                        self.timeouts.store(0, Ordering::SeqCst);
+                    }
+                    return Ok(response);
+                }
+                Ok(Err(RpcError::Transport(kind))) => {
+                    warn!("RPC endpoint {} failed: {}", endpoint.name, kind);
+                    last_error = Some(RpcError::Transport(kind));
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    warn!("RPC endpoint {} timed out", endpoint.name);
+                    // Only the timeouts in a row of the current endpoint make it rotate, a
+                    // slow request alone fails without trying the other endpoints
+                    if index == start
+                        && self.timeouts.fetch_add(1, Ordering::SeqCst) + 1
+                            < RPC_FAILOVER_MAX_TIMEOUTS
+                    {
+                        return Err(TransportErrorKind::custom_str("RPC request timed out"));
+                    }
+                    last_error = Some(TransportErrorKind::custom_str("RPC request timed out"));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| TransportErrorKind::custom_str("No RPC endpoint")))
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Readiness is checked on the endpoint a request is sent to
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().send(request))
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::SNAPSHOTS_PATH,
+    core::executions::ExecutionKind,
+    types::{DexType, u128_string},
+    utils::json_file,
+};
+
+/// When a snapshot was taken, relative to the execution it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotPhase {
+    Before,
+    After,
+    /// Taken for a position opened before the snapshots were recorded
+    Backfill,
+}
+
+/// Full on-chain state of a position at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PositionSnapshot {
+    pub timestamp: u64,
+    pub token_id: String,
+    pub phase: SnapshotPhase,
+    /// Execution the snapshot surrounds, absent for a backfill
+    pub kind: Option<ExecutionKind>,
+    pub tx_hash: Option<String>,
+    pub dex_type: DexType,
+    pub owner: String,
+    /// Address of the tracked pool of the position, if any
+    pub pool: Option<String>,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+    /// Tick of the tracked pool the amounts are computed at
+    pub current_tick: Option<i32>,
+    /// Principal of token0 at the current tick, in its smallest unit
+    pub amount0: Option<String>,
+    /// Principal of token1 at the current tick, in its smallest unit
+    pub amount1: Option<String>,
+    pub fee_growth_inside0_last_x128: String,
+    pub fee_growth_inside1_last_x128: String,
+    /// Fees already accounted by the position manager, in the smallest unit of token0
+    pub tokens_owed0: String,
+    /// Fees already accounted by the position manager, in the smallest unit of token1
+    pub tokens_owed1: String,
+    /// Fees collectable at that time, in the smallest unit of token0
+    pub uncollected_fees0: String,
+    /// Fees collectable at that time, in the smallest unit of token1
+    pub uncollected_fees1: String,
+}
+
+/// Snapshots of the positions, persisted to `SNAPSHOTS_PATH` so their timeline can be
+/// replayed exactly
+#[derive(Debug, Default)]
+pub struct SnapshotStore {
+    snapshots: Mutex<Vec<PositionSnapshot>>,
+}
+
+impl SnapshotStore {
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            snapshots: Mutex::new(json_file::load(SNAPSHOTS_PATH)?),
+        })
+    }
+
+    /// Append the snapshots of an execution and persist them
+    pub fn record(&self, new_snapshots: Vec<PositionSnapshot>) -> Result<()> {
+        if new_snapshots.is_empty() {
+            return Ok(());
+        }
+        let mut snapshots = self.snapshots.lock().expect("Snapshot store lock poisoned");
+        snapshots.extend(new_snapshots);
+        json_file::save(SNAPSHOTS_PATH, &*snapshots)
+    }
+
+    /// Snapshots of a position, oldest first
+    pub fn for_position(&self, token_id: &str) -> Vec<PositionSnapshot> {
+        let snapshots = self.snapshots.lock().expect("Snapshot store lock poisoned");
+        snapshots
+            .iter()
+            .filter(|snapshot| snapshot.token_id == token_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Last snapshot of each position of a pool taken at or before `timestamp`, the
+    /// positions closed by then excluded
+    pub fn for_pool_as_of(&self, pool: &str, timestamp: u64) -> Vec<PositionSnapshot> {
+        let snapshots = self.snapshots.lock().expect("Snapshot store lock poisoned");
+        let mut latest: BTreeMap<&str, &PositionSnapshot> = BTreeMap::new();
+        for snapshot in snapshots.iter() {
+            if snapshot.timestamp <= timestamp
+                && snapshot
+                    .pool
+                    .as_deref()
+                    .is_some_and(|address| address.eq_ignore_ascii_case(pool))
+            {
+                latest.insert(&snapshot.token_id, snapshot);
+            }
+        }
+
+        latest
+            .into_values()
+            .filter(|snapshot| {
+                !(snapshot.kind == Some(ExecutionKind::Close)
+                    && snapshot.phase == SnapshotPhase::After)
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn contains(&self, token_id: &str) -> bool {
+        let snapshots = self.snapshots.lock().expect("Snapshot store lock poisoned");
+        snapshots
+            .iter()
+            .any(|snapshot| snapshot.token_id == token_id)
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::PoolStrategy,
+    core::swaps,
+    types::{Pool, Position},
+    utils::{amm_math::tick_to_price, time::unix_timestamp},
+};
+
+/// What the strategy of a pool would do with one of its positions
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum StrategyAction {
+    /// The price is within the rebalance threshold of the range center
+    Hold,
+    /// Move the liquidity to a range centered on the current price
+    Rebalance {
+        tick_lower: i32,
+        tick_upper: i32,
+        price0_lower: f64,
+        price0_upper: f64,
+    },
+}
+
+/// Decision of the strategy for one position, with the inputs it was made from
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PositionDecision {
+    pub token_id: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub in_range: bool,
+    /// Distance of the current tick from the range center, in percent of the half range
+    /// width: 100 at the range bounds
+    pub price_move_percent: f64,
+    pub action: StrategyAction,
+    /// Whether the action would be sent right now, which needs automatic rebalances and
+    /// a gas price under `max_gas_price`
+    pub would_execute: bool,
+    pub reason: String,
+}
+
+/// Result of running the strategy of a pool without executing anything
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StrategyDryRun {
+    pub address: String,
+    pub timestamp: u64,
+    pub strategy: PoolStrategy,
+    pub current_tick: i32,
+    pub price0: f64,
+    /// Width in ticks of the ranges opened by the strategy
+    pub range_width_ticks: i32,
+    /// Gas price in wei, `None` when it couldn't be read
+    pub gas_price: Option<u128>,
+    /// Decisions for the positions of the pool held by the signer wallet
+    pub positions: Vec<PositionDecision>,
+}
+
+/// Run the strategy of a pool against its current state
+///
+/// # Arguments:
+/// * `positions` - Positions of the pool the strategy manages
+/// * `gas_price` - Current gas price in wei, if known
+pub fn dry_run(
+    pool: &Pool,
+    strategy: &PoolStrategy,
+    positions: &[Position],
+    gas_price: Option<u128>,
+) -> Result<StrategyDryRun> {
+    let range_width_ticks = range_width_ticks(strategy.range_width_percent);
+    let gas_blocker = match (strategy.max_gas_price, gas_price) {
+        (Some(max), Some(gas_price)) if gas_price > max => Some(format!(
+            "the gas price {} is above max_gas_price {}",
+            gas_price, max
+        )),
+        (Some(_), None) => Some("the gas price couldn't be read".to_string()),
+        _ => None,
+    };
+
+    let mut decisions = Vec::with_capacity(positions.len());
+    for position in positions {
+        let center = (position.tick_lower + position.tick_upper) as f64 / 2.0;
+        let half_width = ((position.tick_upper - position.tick_lower) as f64 / 2.0).max(1.0);
+        let price_move_percent = (pool.current_tick as f64 - center).abs() / half_width * 100.0;
+        let in_range =
+            pool.current_tick >= position.tick_lower && pool.current_tick < position.tick_upper;
+
+        let mut reason = format!(
+            "The price moved {:.1}% of the half range away from its center, the threshold is {}%",
+            price_move_percent, strategy.rebalance_threshold_percent
+        );
+        if price_move_percent < strategy.rebalance_threshold_percent {
+            decisions.push(PositionDecision {
+                token_id: position.token_id.clone(),
+                tick_lower: position.tick_lower,
+                tick_upper: position.tick_upper,
+                in_range,
+                price_move_percent,
+                action: StrategyAction::Hold,
+                would_execute: false,
+                reason,
+            });
+            continue;
+        }
+
+        let (tick_lower, tick_upper) =
+            swaps::centered_range(pool.current_tick, pool.tick_spacing, range_width_ticks);
+        let (decimals0, decimals1) = (pool.token0.decimals, pool.token1.decimals);
+        let would_execute = strategy.auto_rebalance && gas_blocker.is_none();
+        if !strategy.auto_rebalance {
+            reason.push_str(", automatic rebalances are disabled so it is only suggested");
+        } else if let Some(blocker) = &gas_blocker {
+            reason.push_str(&format!(", it waits since {}", blocker));
+        }
+
+        decisions.push(PositionDecision {
+            token_id: position.token_id.clone(),
+            tick_lower: position.tick_lower,
+            tick_upper: position.tick_upper,
+            in_range,
+            price_move_percent,
+            action: StrategyAction::Rebalance {
+                tick_lower,
+                tick_upper,
+                price0_lower: tick_to_price(tick_lower, decimals0, decimals1)?,
+                price0_upper: tick_to_price(tick_upper, decimals0, decimals1)?,
+            },
+            would_execute,
+            reason,
+        });
+    }
+
+    Ok(StrategyDryRun {
+        address: pool.address.clone(),
+        timestamp: unix_timestamp(),
+        strategy: strategy.clone(),
+        current_tick: pool.current_tick,
+        price0: pool.price0,
+        range_width_ticks,
+        gas_price,
+        positions: decisions,
+    })
+}
+
+/// Width in ticks of a range spanning `width_percent` of the price at its center
+pub fn range_width_ticks(width_percent: f64) -> i32 {
+    let half = width_percent / 200.0;
+    (((1.0 + half) / (1.0 - half)).ln() / 1.0001f64.ln()).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DexType, Token};
+
+    fn pool() -> Pool {
+        let token = |symbol: &str, decimals: u8| Token {
+            address: format!("0x{}", symbol),
+            symbol: symbol.to_string(),
+            decimals,
+        };
+        Pool {
+            address: "0xpool".to_string(),
+            dex_type: DexType::UniswapV3,
+            token0: token("A", 18),
+            token1: token("B", 6),
+            fee: 0.3,
+            tick_spacing: 60,
+            current_tick: 1_000,
+            price0: 1.0,
+            price1: 1.0,
+            liquidity: 1_000,
+            updated_at: 0,
+            block_number: 0,
+            annotation: None,
+        }
+    }
+
+    fn strategy(auto_rebalance: bool, max_gas_price: Option<u128>) -> PoolStrategy {
+        PoolStrategy {
+            range_width_percent: 10.0,
+            rebalance_threshold_percent: 50.0,
+            max_gas_price,
+            auto_rebalance,
+        }
+    }
+
+    fn position(token_id: &str, tick_lower: i32, tick_upper: i32) -> Position {
+        Position {
+            token_id: token_id.to_string(),
+            dex_type: DexType::UniswapV3,
+            owner: "0xsigner".to_string(),
+            token0: "0xA".to_string(),
+            token1: "0xB".to_string(),
+            fee: 0.3,
+            tick_lower,
+            tick_upper,
+            liquidity: 1_000,
+            uncollected_fees0: "0".to_string(),
+            uncollected_fees1: "0".to_string(),
+            pool: None,
+            price_lower: None,
+            price_upper: None,
+            in_range: None,
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn converts_the_range_width_to_ticks() {
+        assert_eq!(range_width_ticks(10.0), 1_001);
+        assert_eq!(range_width_ticks(1.0), 100);
+        assert_eq!(range_width_ticks(0.0), 0);
+    }
+
+    #[test]
+    fn rebalances_the_positions_past_the_threshold() {
+        let positions = [position("1", -300, 300), position("2", 900, 1_140)];
+        let dry_run = dry_run(&pool(), &strategy(true, None), &positions, Some(5)).unwrap();
+
+        assert_eq!(dry_run.range_width_ticks, 1_001);
+        assert_eq!(dry_run.current_tick, 1_000);
+        assert_eq!(dry_run.gas_price, Some(5));
+
+        let moved = &dry_run.positions[0];
+        assert!(!moved.in_range);
+        assert!((moved.price_move_percent - 1_000.0 / 3.0).abs() < 1e-9);
+        assert!(moved.would_execute);
+        let StrategyAction::Rebalance {
+            tick_lower,
+            tick_upper,
+            price0_lower,
+            price0_upper,
+        } = moved.action
+        else {
+            panic!("Expected a rebalance, got {:?}", moved.action);
+        };
+        assert_eq!((tick_lower, tick_upper), (480, 1_500));
+        assert_eq!(price0_lower, tick_to_price(480, 18, 6).unwrap());
+        assert_eq!(price0_upper, tick_to_price(1_500, 18, 6).unwrap());
+
+        let held = &dry_run.positions[1];
+        assert!(held.in_range);
+        assert!((held.price_move_percent - 50.0 / 3.0).abs() < 1e-9);
+        assert!(matches!(held.action, StrategyAction::Hold));
+        assert!(!held.would_execute);
+    }
+
+    #[test]
+    fn only_suggests_the_rebalances_it_can_not_send() {
+        let positions = [position("1", -300, 300)];
+        let pool = pool();
+
+        let manual = dry_run(&pool, &strategy(false, None), &positions, None).unwrap();
+        assert!(!manual.positions[0].would_execute);
+        assert!(
+            manual.positions[0]
+                .reason
+                .ends_with("so it is only suggested")
+        );
+
+        let expensive = dry_run(&pool, &strategy(true, Some(10)), &positions, Some(11)).unwrap();
+        assert!(!expensive.positions[0].would_execute);
+        assert!(
+            expensive.positions[0]
+                .reason
+                .ends_with("above max_gas_price 10")
+        );
+
+        let unknown_gas = dry_run(&pool, &strategy(true, Some(10)), &positions, None).unwrap();
+        assert!(!unknown_gas.positions[0].would_execute);
+
+        let cheap = dry_run(&pool, &strategy(true, Some(10)), &positions, Some(10)).unwrap();
+        assert!(cheap.positions[0].would_execute);
+    }
+}
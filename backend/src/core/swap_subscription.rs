@@ -0,0 +1,201 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::rt;
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    transports::Authorization,
+};
+use anyhow::{Result, bail};
+use futures::StreamExt;
+use reqwest::Url;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{CONFIG, ChainConfig, RpcAuth, SWAP_SUBSCRIPTION_RETRY_SECS},
+    core::{pools::replace_pool_from_swap, swaps},
+    state::AppState,
+    types::{DexType, Pool, PoolEvent, address_key},
+    utils::{amm_math::tick_to_price, time::unix_timestamp},
+};
+
+/// Update the tick, prices and liquidity of the tracked pools from their `Swap` logs, for
+/// the lifetime of the process
+///
+/// The logs are subscribed on `chain.ws_url`, or on `rpc_url` when it is a ws(s) url,
+/// with the `rpc_auth` of the chain. The subscription is renewed when a pool is added or
+/// removed, and after a failure. Without a WebSocket url nothing is spawned and the pools
+/// are only polled.
+pub fn spawn_swap_subscription(app_state: Arc<AppState>) {
+    let chain = &CONFIG.get().toml.chain;
+    let Some(ws_url) = chain.ws_url.clone().or_else(|| {
+        Url::parse(&chain.rpc_url)
+            .is_ok_and(|url| matches!(url.scheme(), "ws" | "wss"))
+            .then(|| chain.rpc_url.clone())
+    }) else {
+        info!("No WebSocket RPC url, the pool ticks are only updated by polling");
+        return;
+    };
+    let Some(auth) = ws_auth(chain) else {
+        warn!(
+            "The WebSocket connection can only send the Authorization RPC header, the pool \
+             ticks are only updated by polling"
+        );
+        return;
+    };
+    let ws_connect = WsConnect::new(ws_url).with_auth_opt(auth);
+    info!("Subscribing to the Swap logs of the tracked pools");
+
+    rt::spawn(async move {
+        loop {
+            match subscribe(&app_state, &ws_connect).await {
+                // The tracked pools changed
+                Ok(()) => continue,
+                Err(e) => warn!("Swap log subscription failed: {:#}", e),
+            }
+            rt::time::sleep(Duration::from_secs(SWAP_SUBSCRIPTION_RETRY_SECS)).await;
+        }
+    });
+}
+
+/// Authorization of the WebSocket connection, from `rpc_auth` or an `Authorization` entry
+/// of `rpc_headers`
+///
+/// # Returns:
+/// * `None` if `rpc_headers` has other headers, the WebSocket connection can't send them
+fn ws_auth(chain: &ChainConfig) -> Option<Option<Authorization>> {
+    let mut auth = None;
+    for (name, value) in &chain.rpc_headers.0 {
+        if !name.eq_ignore_ascii_case("authorization") {
+            return None;
+        }
+        auth = Some(Authorization::raw(value.expose()));
+    }
+
+    Some(match &chain.rpc_auth {
+        Some(RpcAuth::Bearer { token }) => Some(Authorization::bearer(token.expose())),
+        Some(RpcAuth::Basic { username, password }) => Some(Authorization::basic(
+            username,
+            password
+                .as_ref()
+                .map(|password| password.expose().as_str())
+                .unwrap_or_default(),
+        )),
+        None => auth,
+    })
+}
+
+/// Apply the `Swap` logs of the tracked pools until the subscription fails or a pool is
+/// added or removed
+async fn subscribe(app_state: &AppState, ws_connect: &WsConnect) -> Result<()> {
+    // Subscribed first so a pool added while connecting renews the subscription
+    let mut pool_events = app_state.pool_events.subscribe();
+
+    let addresses = app_state
+        .pools
+        .iter()
+        .map(|entry| Address::from_str(entry.key()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if addresses.is_empty() {
+        // An empty address filter would match the logs of every contract
+        return wait_for_pool_change(&mut pool_events).await;
+    }
+
+    let provider = ProviderBuilder::new()
+        .connect_ws(ws_connect.clone())
+        .await?;
+    let filter = Filter::new()
+        .address(addresses.clone())
+        .event_signature(vec![
+            swaps::swap_signature(&DexType::UniswapV3),
+            swaps::swap_signature(&DexType::PancakeSwapV3),
+        ]);
+    let mut logs = provider.subscribe_logs(&filter).await?.into_stream();
+    debug!("Subscribed to the Swap logs of {} pools", addresses.len());
+
+    loop {
+        tokio::select! {
+            log = logs.next() => {
+                let Some(log) = log else {
+                    bail!("Subscription closed by the node");
+                };
+                apply_swap_log(app_state, &log);
+            }
+            changed = wait_for_pool_change(&mut pool_events) => return changed,
+        }
+    }
+}
+
+/// Wait until a pool is added or removed
+///
+/// Lagging behind the pool events returns too, since such an event may have been skipped.
+async fn wait_for_pool_change(pool_events: &mut broadcast::Receiver<PoolEvent>) -> Result<()> {
+    loop {
+        match pool_events.recv().await {
+            Ok(PoolEvent::Added { .. } | PoolEvent::Removed { .. })
+            | Err(broadcast::error::RecvError::Lagged(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Closed) => bail!("Pool events closed"),
+        }
+    }
+}
+
+/// Swap the state after a `Swap` log into its pool
+///
+/// The logs removed by a reorg are skipped, the next poll of the pool corrects its state.
+/// So are the logs of a block older than the state of the pool.
+fn apply_swap_log(app_state: &AppState, log: &Log) {
+    if log.removed {
+        return;
+    }
+    let Some(block_number) = log.block_number else {
+        return;
+    };
+
+    let address = address_key(&log.address());
+    // Don't hold the DashMap lock while replacing the pool
+    let Some(pool) = app_state
+        .pools
+        .get(&address)
+        .map(|entry| entry.value().clone())
+    else {
+        return;
+    };
+    // Both events match the filter, only the one of the pool's dex decodes
+    if log.topic0() != Some(&swaps::swap_signature(&pool.dex_type)) {
+        return;
+    }
+
+    let (current_tick, liquidity) = match swaps::decode_pool_state(&pool.dex_type, log) {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("Failed to decode a Swap log of pool {}: {:#}", address, e);
+            return;
+        }
+    };
+    let price0 = match tick_to_price(current_tick, pool.token0.decimals, pool.token1.decimals) {
+        Ok(price0) => price0,
+        Err(e) => {
+            warn!("Invalid tick {} of pool {}: {:#}", current_tick, address, e);
+            return;
+        }
+    };
+
+    replace_pool_from_swap(
+        app_state,
+        &address,
+        Pool {
+            current_tick,
+            price0,
+            price1: 1.0 / price0,
+            liquidity,
+            updated_at: unix_timestamp(),
+            block_number,
+            ..pool
+        },
+    );
+}
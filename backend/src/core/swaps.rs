@@ -0,0 +1,455 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{Address, B256, I256},
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    sol,
+    sol_types::SolEvent,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{MAX_LOG_BLOCK_RANGE, MAX_SWAP_LOG_REQUESTS},
+    core::{block_time, metrics::Metrics},
+    types::{DexType, EvmProvider, Pool},
+    utils::amm_math::{MAX_RANGE_WIDTH, MAX_TICK, MIN_TICK},
+};
+
+sol!(
+    interface IUniswapV3Pool {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick
+        );
+    }
+
+    interface IPancakeV3Pool {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint128 protocolFeesToken0,
+            uint128 protocolFeesToken1
+        );
+    }
+);
+
+/// Swap executed on a pool, amounts are signed from the pool point of view
+#[derive(Debug, Clone)]
+pub struct Swap {
+    pub block_number: u64,
+    pub amount0: I256,
+    pub amount1: I256,
+    /// Tick of the pool after the swap
+    pub tick: i32,
+}
+
+/// Swaps of a pool over a trailing window of blocks, oldest first
+#[derive(Debug, Clone)]
+pub struct SwapWindow {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Estimated duration covered by the blocks
+    pub elapsed_secs: u64,
+    pub swaps: Vec<Swap>,
+}
+
+/// Fee APR a new position centered on the current tick would have earned over the window
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FeeAprEstimate {
+    pub address: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub elapsed_secs: u64,
+    pub swaps: usize,
+    /// Swapped amount of token0 over the window, in token units
+    pub volume0: f64,
+    /// Swapped amount of token1 over the window, in token units
+    pub volume1: f64,
+    /// Fees earned by all the in range LPs per day, in token1 units
+    pub fees_per_day: f64,
+    /// Share of the swaps that left the price within the range
+    pub in_range_ratio: Option<f64>,
+    /// Yearly fees over the value of the position, as a fraction. Assumes the price stays
+    /// in range and the position is small compared to the pool liquidity.
+    pub apr: Option<f64>,
+}
+
+impl FeeAprEstimate {
+    /// Estimate the fee APR of a `width_ticks` wide range, rounded up to the tick spacing
+    pub fn new(pool: &Pool, window: &SwapWindow, width_ticks: i32) -> Self {
+        let (tick_lower, tick_upper) =
+            centered_range(pool.current_tick, pool.tick_spacing, width_ticks);
+
+        // Raw amounts, the decimals cancel out in the APR
+        let raw_volume = |amount: fn(&Swap) -> I256| -> f64 {
+            window
+                .swaps
+                .iter()
+                .map(|swap| f64::from(amount(swap).unsigned_abs()))
+                .sum()
+        };
+        let raw_volume0 = raw_volume(|swap| swap.amount0);
+        let raw_volume1 = raw_volume(|swap| swap.amount1);
+
+        // `fee` is a percentage
+        let days = window.elapsed_secs.max(1) as f64 / (24 * 60 * 60) as f64;
+        let raw_fees_per_day = raw_volume1 * pool.fee / 100.0 / days;
+
+        // Value in token1 of one unit of liquidity over the range, at the current price
+        let sqrt_price = |tick: i32| 1.0001f64.powf(tick as f64 / 2.0);
+        let (sqrt_current, sqrt_lower, sqrt_upper) = (
+            sqrt_price(pool.current_tick),
+            sqrt_price(tick_lower),
+            sqrt_price(tick_upper),
+        );
+        let value_per_liquidity =
+            2.0 * sqrt_current - sqrt_current.powi(2) / sqrt_upper - sqrt_lower;
+
+        let apr = (pool.liquidity > 0 && value_per_liquidity > 0.0)
+            .then(|| raw_fees_per_day * 365.0 / (pool.liquidity as f64 * value_per_liquidity));
+
+        let in_range_ratio = (!window.swaps.is_empty()).then(|| {
+            let in_range = window
+                .swaps
+                .iter()
+                .filter(|swap| swap.tick >= tick_lower && swap.tick < tick_upper)
+                .count();
+            in_range as f64 / window.swaps.len() as f64
+        });
+
+        let decimals = |decimals: u8| 10f64.powi(decimals as i32);
+
+        Self {
+            address: pool.address.clone(),
+            tick_lower,
+            tick_upper,
+            from_block: window.from_block,
+            to_block: window.to_block,
+            elapsed_secs: window.elapsed_secs,
+            swaps: window.swaps.len(),
+            volume0: raw_volume0 / decimals(pool.token0.decimals),
+            volume1: raw_volume1 / decimals(pool.token1.decimals),
+            fees_per_day: raw_fees_per_day / decimals(pool.token1.decimals),
+            in_range_ratio,
+            apr,
+        }
+    }
+}
+
+/// Number of times a range of a given width would have gone out of range over the window
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TickCrossings {
+    pub address: String,
+    /// Width of the range in ticks, after rounding up to the tick spacing
+    pub width: i32,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub elapsed_secs: u64,
+    pub swaps: usize,
+    /// Number of swaps that moved the price out of the range
+    pub crossings: usize,
+    pub crossings_per_day: f64,
+}
+
+impl TickCrossings {
+    /// Replay the swaps against a range of the given width, re-centered every time a swap
+    /// moves the price out of it like a rebalance would
+    pub fn new(pool: &Pool, window: &SwapWindow, width_ticks: i32) -> Self {
+        let crossings = count_crossings(
+            window.swaps.iter().map(|swap| swap.tick),
+            pool.tick_spacing,
+            width_ticks,
+        );
+        let days = window.elapsed_secs.max(1) as f64 / (24 * 60 * 60) as f64;
+
+        Self {
+            address: pool.address.clone(),
+            width: round_width(width_ticks, pool.tick_spacing),
+            from_block: window.from_block,
+            to_block: window.to_block,
+            elapsed_secs: window.elapsed_secs,
+            swaps: window.swaps.len(),
+            crossings,
+            crossings_per_day: crossings as f64 / days,
+        }
+    }
+}
+
+/// Number of times a sequence of ticks leaves a range centered on its first tick, the range
+/// being re-centered on the new tick every time
+pub fn count_crossings(
+    ticks: impl IntoIterator<Item = i32>,
+    tick_spacing: i32,
+    width_ticks: i32,
+) -> usize {
+    let mut crossings = 0;
+    let mut range: Option<(i32, i32)> = None;
+    for tick in ticks {
+        match range {
+            Some((tick_lower, tick_upper)) if tick >= tick_lower && tick < tick_upper => {}
+            Some(_) => {
+                crossings += 1;
+                range = Some(centered_range(tick, tick_spacing, width_ticks));
+            }
+            None => range = Some(centered_range(tick, tick_spacing, width_ticks)),
+        }
+    }
+    crossings
+}
+
+/// Round a range width up to a positive multiple of the tick spacing, at most
+/// `MAX_RANGE_WIDTH` rounded up
+pub fn round_width(width_ticks: i32, tick_spacing: i32) -> i32 {
+    let spacing = tick_spacing.max(1) as i64;
+    let width = width_ticks.clamp(1, MAX_RANGE_WIDTH) as i64;
+    i32::try_from((width + spacing - 1) / spacing * spacing).unwrap_or(i32::MAX)
+}
+
+/// `[tick_lower, tick_upper)` range around `tick`, with the width rounded up to the tick
+/// spacing and the bounds aligned on it and kept within the usable ticks
+pub fn centered_range(tick: i32, tick_spacing: i32, width_ticks: i32) -> (i32, i32) {
+    let spacing = tick_spacing.max(1);
+    let width = round_width(width_ticks, spacing);
+    let tick_lower = ((tick.clamp(MIN_TICK, MAX_TICK) - width / 2).div_euclid(spacing) * spacing)
+        .max(MIN_TICK.div_euclid(spacing) * spacing + spacing);
+    let tick_upper = tick_lower
+        .saturating_add(width)
+        .min(MAX_TICK.div_euclid(spacing) * spacing);
+    (tick_lower, tick_upper)
+}
+
+/// Fetch the swaps of a pool over the last `lookback_secs`
+///
+/// The block range is derived from the current block time and queried in chunks of
+/// `MAX_LOG_BLOCK_RANGE` blocks, the range most RPC providers accept for `eth_getLogs`.
+/// It is shortened to `MAX_SWAP_LOG_REQUESTS` chunks, `elapsed_secs` of the window tells
+/// the duration actually covered.
+pub async fn fetch_swaps(
+    evm_provider: &EvmProvider,
+    metrics: &Metrics,
+    pool: &Pool,
+    lookback_secs: u64,
+) -> Result<SwapWindow> {
+    let block_time = block_time::estimate_block_time(evm_provider, metrics).await?;
+
+    let to_block = block_time.latest_block;
+    let blocks = ((lookback_secs as f64 / block_time.avg_block_time_secs).ceil() as u64)
+        .clamp(1, to_block.max(1))
+        .min(MAX_LOG_BLOCK_RANGE * MAX_SWAP_LOG_REQUESTS);
+    let from_block = to_block.saturating_sub(blocks - 1);
+
+    let swaps = fetch_swaps_between(evm_provider, metrics, pool, from_block, to_block).await?;
+
+    Ok(SwapWindow {
+        from_block,
+        to_block,
+        elapsed_secs: (blocks as f64 * block_time.avg_block_time_secs).round() as u64,
+        swaps,
+    })
+}
+
+/// Fetch the swaps of a pool between two blocks (inclusive), oldest first
+pub async fn fetch_swaps_between(
+    evm_provider: &EvmProvider,
+    metrics: &Metrics,
+    pool: &Pool,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<Swap>> {
+    let pool_address = Address::from_str(&pool.address)?;
+    let signature = swap_signature(&pool.dex_type);
+
+    let mut swaps = Vec::new();
+    let mut chunk_start = from_block;
+    while chunk_start <= to_block {
+        let chunk_end = (chunk_start + MAX_LOG_BLOCK_RANGE - 1).min(to_block);
+        let filter = Filter::new()
+            .address(pool_address)
+            .event_signature(signature)
+            .from_block(chunk_start)
+            .to_block(chunk_end);
+
+        let logs = metrics
+            .track_rpc("eth_getLogs", evm_provider.get_logs(&filter))
+            .await?;
+        for log in logs {
+            swaps.push(decode_swap(&pool.dex_type, &log)?);
+        }
+
+        chunk_start = chunk_end + 1;
+    }
+
+    Ok(swaps)
+}
+
+/// Topic of the `Swap` event of the pools of a dex
+pub fn swap_signature(dex_type: &DexType) -> B256 {
+    match dex_type {
+        DexType::UniswapV3 => IUniswapV3Pool::Swap::SIGNATURE_HASH,
+        DexType::PancakeSwapV3 => IPancakeV3Pool::Swap::SIGNATURE_HASH,
+    }
+}
+
+/// Tick and in-range liquidity of a pool after the swap of a `Swap` log
+pub fn decode_pool_state(dex_type: &DexType, log: &Log) -> Result<(i32, u128)> {
+    Ok(match dex_type {
+        DexType::UniswapV3 => {
+            let swap = log.log_decode::<IUniswapV3Pool::Swap>()?.inner.data;
+            (swap.tick.as_i32(), swap.liquidity)
+        }
+        DexType::PancakeSwapV3 => {
+            let swap = log.log_decode::<IPancakeV3Pool::Swap>()?.inner.data;
+            (swap.tick.as_i32(), swap.liquidity)
+        }
+    })
+}
+
+fn decode_swap(dex_type: &DexType, log: &Log) -> Result<Swap> {
+    let block_number = log
+        .block_number
+        .context("Swap log without a block number")?;
+
+    let (amount0, amount1, tick) = match dex_type {
+        DexType::UniswapV3 => {
+            let swap = log.log_decode::<IUniswapV3Pool::Swap>()?.inner.data;
+            (swap.amount0, swap.amount1, swap.tick.as_i32())
+        }
+        DexType::PancakeSwapV3 => {
+            let swap = log.log_decode::<IPancakeV3Pool::Swap>()?.inner.data;
+            (swap.amount0, swap.amount1, swap.tick.as_i32())
+        }
+    };
+
+    Ok(Swap {
+        block_number,
+        amount0,
+        amount1,
+        tick,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Token;
+
+    fn pool(liquidity: u128) -> Pool {
+        let token = |symbol: &str| Token {
+            address: format!("0x{}", symbol),
+            symbol: symbol.to_string(),
+            decimals: 6,
+        };
+        Pool {
+            address: "0xpool".to_string(),
+            dex_type: DexType::UniswapV3,
+            token0: token("A"),
+            token1: token("B"),
+            fee: 0.3,
+            tick_spacing: 10,
+            current_tick: 0,
+            price0: 1.0,
+            price1: 1.0,
+            liquidity,
+            updated_at: 0,
+            block_number: 0,
+            annotation: None,
+        }
+    }
+
+    fn window(swaps: &[(i64, i64, i32)]) -> SwapWindow {
+        SwapWindow {
+            from_block: 1,
+            to_block: 100,
+            elapsed_secs: 24 * 60 * 60,
+            swaps: swaps
+                .iter()
+                .map(|&(amount0, amount1, tick)| Swap {
+                    block_number: 1,
+                    amount0: I256::try_from(amount0).unwrap(),
+                    amount1: I256::try_from(amount1).unwrap(),
+                    tick,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rounds_the_width_up_to_the_tick_spacing() {
+        assert_eq!(round_width(100, 60), 120);
+        assert_eq!(round_width(120, 60), 120);
+        assert_eq!(round_width(0, 60), 60);
+        assert_eq!(round_width(-5, 10), 10);
+        assert_eq!(round_width(7, 0), 7);
+    }
+
+    #[test]
+    fn bounds_the_width_without_overflowing() {
+        assert_eq!(round_width(i32::MAX, 1), MAX_RANGE_WIDTH);
+        assert_eq!(round_width(i32::MAX, 200), 1_774_600);
+        assert_eq!(round_width(i32::MIN, 200), 200);
+    }
+
+    #[test]
+    fn centers_the_range_on_the_tick_spacing() {
+        assert_eq!(centered_range(1_000, 60, 600), (660, 1_260));
+        assert_eq!(centered_range(-1_000, 60, 600), (-1_320, -720));
+        assert_eq!(centered_range(5, 1, 10), (0, 10));
+    }
+
+    #[test]
+    fn keeps_the_range_within_the_usable_ticks() {
+        assert_eq!(centered_range(MIN_TICK, 60, 600), (-887_220, -886_620));
+        assert_eq!(centered_range(MAX_TICK, 60, 600), (886_920, 887_220));
+        assert_eq!(centered_range(0, 60, i32::MAX), (-887_220, 887_220));
+        assert_eq!(centered_range(i32::MAX, 1, i32::MAX), (0, MAX_TICK));
+    }
+
+    #[test]
+    fn estimates_the_fee_apr_from_the_swapped_volume() {
+        let window = window(&[(1_000_000, -1_000_000, 5), (-500_000, 500_000, 50)]);
+        let estimate = FeeAprEstimate::new(&pool(1_000_000_000), &window, 20);
+
+        assert_eq!((estimate.tick_lower, estimate.tick_upper), (-10, 10));
+        assert_eq!(estimate.swaps, 2);
+        assert_eq!((estimate.volume0, estimate.volume1), (1.5, 1.5));
+        assert!((estimate.fees_per_day - 0.0045).abs() < 1e-12);
+        assert_eq!(estimate.in_range_ratio, Some(0.5));
+
+        // One unit of liquidity centered on tick 0 is worth 2 - 2 * sqrt(1.0001^-10) token1
+        let value_per_liquidity = 2.0 - 2.0 * 1.0001f64.powf(-5.0);
+        let expected = 4_500.0 * 365.0 / (1e9 * value_per_liquidity);
+        assert!((estimate.apr.unwrap() / expected - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn has_no_fee_apr_without_liquidity_or_swaps() {
+        let estimate = FeeAprEstimate::new(&pool(0), &window(&[(10, -10, 0)]), 20);
+        assert_eq!(estimate.apr, None);
+
+        let estimate = FeeAprEstimate::new(&pool(1_000), &window(&[]), 20);
+        assert_eq!(estimate.apr, Some(0.0));
+        assert_eq!(estimate.in_range_ratio, None);
+    }
+
+    #[test]
+    fn counts_the_exits_of_the_recentered_range() {
+        assert_eq!(count_crossings([0, 10, -10], 10, 40), 0);
+        assert_eq!(count_crossings([0, 30, 35, -100], 10, 40), 2);
+        assert_eq!(count_crossings([], 10, 40), 0);
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use alloy::{
+    network::Ethereum,
+    primitives::{Address, Bytes, TxKind},
+    providers::{PendingTransactionBuilder, Provider, WalletProvider},
+    rpc::types::TransactionRequest,
+    sol,
+    sol_types::SolCall,
+};
+use anyhow::{Result, bail};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{
+        CONFIG, GasStrategy, TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES, TX_FAILSAFE_PATH,
+        registry::AddressRegistry,
+    },
+    core::metrics::Metrics,
+    types::{DexType, EvmProvider, Pool},
+    utils::{json_file, time::unix_timestamp},
+};
+
+sol!(
+    interface IApproval {
+        function approve(address spender, uint256 amount) external returns (bool);
+
+        function setApprovalForAll(address operator, bool approved) external;
+    }
+);
+
+/// State of the fail-safe switching the signer to read-only after repeated submission
+/// failures, persisted to `TX_FAILSAFE_PATH` when it trips or is reset
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct FailSafeState {
+    /// Submissions failed in a row, whatever their pool
+    pub consecutive_failures: u32,
+    /// Unix timestamp (seconds) the fail-safe tripped at, transactions are refused
+    /// until it is reset
+    pub tripped_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Single entry point for every transaction sent by the signer
+///
+/// Transactions are only sent to allowlisted contracts, so a bug or an injected
+/// address can never make the wallet interact with an arbitrary contract.
+#[derive(Debug)]
+pub struct TxManager {
+    evm_provider: EvmProvider,
+    failsafe: Mutex<FailSafeState>,
+}
+
+impl TxManager {
+    /// Create the tx manager, with the fail-safe state of the previous run so a trip is
+    /// only cleared by a reset
+    pub fn new(evm_provider: EvmProvider) -> Result<Self> {
+        let failsafe: FailSafeState = json_file::load(TX_FAILSAFE_PATH)?;
+        if let Some(tripped_at) = failsafe.tripped_at {
+            error!(
+                "Transaction fail-safe tripped at {}, transactions are disabled until it is reset",
+                tripped_at
+            );
+        }
+
+        Ok(Self {
+            evm_provider,
+            failsafe: Mutex::new(failsafe),
+        })
+    }
+
+    pub fn failsafe(&self) -> FailSafeState {
+        self.failsafe
+            .lock()
+            .expect("Fail-safe lock poisoned")
+            .clone()
+    }
+
+    /// Re-enable the transactions after the fail-safe tripped
+    pub fn reset_failsafe(&self) -> Result<()> {
+        let mut failsafe = self.failsafe.lock().expect("Fail-safe lock poisoned");
+        json_file::save(TX_FAILSAFE_PATH, &FailSafeState::default())?;
+        *failsafe = FailSafeState::default();
+        info!("Transaction fail-safe reset, transactions are enabled again");
+        Ok(())
+    }
+
+    /// Count a submission outcome, tripping the fail-safe after
+    /// `TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES` failures in a row
+    fn record_submission(&self, metrics: &Metrics, result: Result<(), String>) {
+        let mut failsafe = self.failsafe.lock().expect("Fail-safe lock poisoned");
+        match result {
+            Ok(()) => failsafe.consecutive_failures = 0,
+            Err(e) => {
+                failsafe.consecutive_failures += 1;
+                failsafe.last_error = Some(e);
+                if failsafe.tripped_at.is_none()
+                    && failsafe.consecutive_failures >= TX_FAILSAFE_MAX_CONSECUTIVE_FAILURES
+                {
+                    failsafe.tripped_at = Some(unix_timestamp());
+                    metrics.record_failsafe_trip();
+                    // The trip is kept in memory anyway, a restart would clear it
+                    if let Err(e) = json_file::save(TX_FAILSAFE_PATH, &*failsafe) {
+                        error!("Failed to persist the tripped fail-safe: {:#}", e);
+                    }
+                    error!(
+                        "{} transaction submissions failed in a row, switching to read-only \
+                         until the fail-safe is reset: {:?}",
+                        failsafe.consecutive_failures, failsafe.last_error
+                    );
+                }
+            }
+        }
+    }
+
+    /// Contracts the signer is allowed to interact with: the Yield contract and the
+    /// `allowed_contracts` of the chain config
+    ///
+    /// Routers and position managers are called by the Yield contract itself, so they
+    /// only need to be listed if the signer calls them directly. The approvals of the
+    /// Yield contract are also allowed on the tokens of the tracked pools and the
+    /// configured position managers, see `approves_yield_contract`.
+    pub fn allowlist(&self) -> Result<HashSet<Address>> {
+        let config = CONFIG.get();
+        let yield_contract = config.contract_address;
+
+        Ok(std::iter::once(yield_contract)
+            .chain(config.toml.chain.allowed_contracts.iter().copied())
+            .collect())
+    }
+
+    /// Check the target of a transaction against the allowlist of the current config
+    fn check_target(
+        &self,
+        pools: &DashMap<String, Pool>,
+        tx: &TransactionRequest,
+    ) -> Result<Address> {
+        let Some(TxKind::Call(to)) = tx.to else {
+            bail!("Contract deployments are not allowed");
+        };
+
+        let config = CONFIG.get();
+        if !self.allowlist()?.contains(&to)
+            && !approves_yield_contract(
+                tx,
+                config.contract_address,
+                &approval_targets(pools, &config.toml.addresses),
+            )
+        {
+            error!(
+                "Refused to send a transaction to non allowlisted contract {}",
+                to
+            );
+            bail!("Contract {} is not allowlisted", to);
+        }
+
+        Ok(to)
+    }
+
+    /// Set the fee fields of a transaction according to the gas strategy of the chain
+    /// config, instead of leaving them to the provider
+    async fn with_fees(
+        &self,
+        metrics: &Metrics,
+        tx: TransactionRequest,
+    ) -> Result<TransactionRequest> {
+        let gas = CONFIG.get().toml.chain.gas.clone();
+        let scale = |fee: u128, cap: Option<u128>| {
+            let fee = (fee as f64 * gas.multiplier).ceil() as u128;
+            cap.map_or(fee, |cap| fee.min(cap))
+        };
+
+        match gas.strategy {
+            GasStrategy::Legacy => {
+                let gas_price = metrics
+                    .track_rpc("eth_gasPrice", self.evm_provider.get_gas_price())
+                    .await?;
+                let gas_price = scale(gas_price, gas.max_fee_per_gas);
+                debug!("Pricing transaction with legacy gas price {}", gas_price);
+                Ok(tx.gas_price(gas_price))
+            }
+            GasStrategy::Eip1559 => {
+                let fees = metrics
+                    .track_rpc("eth_feeHistory", self.evm_provider.estimate_eip1559_fees())
+                    .await?;
+                let max_fee_per_gas = scale(fees.max_fee_per_gas, gas.max_fee_per_gas);
+                // The priority fee can never exceed the max fee
+                let max_priority_fee_per_gas =
+                    scale(fees.max_priority_fee_per_gas, gas.max_priority_fee_per_gas)
+                        .min(max_fee_per_gas);
+                debug!(
+                    "Pricing transaction with max fee {} and priority fee {}",
+                    max_fee_per_gas, max_priority_fee_per_gas
+                );
+                Ok(tx
+                    .max_fee_per_gas(max_fee_per_gas)
+                    .max_priority_fee_per_gas(max_priority_fee_per_gas))
+            }
+        }
+    }
+
+    /// Simulate a transaction from the signer with `eth_call`, without sending it
+    pub async fn simulate(
+        &self,
+        metrics: &Metrics,
+        pools: &DashMap<String, Pool>,
+        tx: TransactionRequest,
+    ) -> Result<Bytes> {
+        self.check_target(pools, &tx)?;
+        let tx = tx.from(self.evm_provider.default_signer_address());
+        Ok(metrics
+            .track_rpc("eth_call", self.evm_provider.call(tx))
+            .await?)
+    }
+
+    /// Sign and send a transaction to an allowlisted contract
+    ///
+    /// # Returns:
+    /// * The pending transaction, to wait for its receipt
+    pub async fn send(
+        &self,
+        metrics: &Metrics,
+        pools: &DashMap<String, Pool>,
+        tx: TransactionRequest,
+    ) -> Result<PendingTransactionBuilder<Ethereum>> {
+        if CONFIG.get().read_only {
+            bail!("Read-only mode, transactions are disabled");
+        }
+        if self.failsafe().tripped_at.is_some() {
+            bail!("Read-only mode after repeated transaction failures, reset the fail-safe");
+        }
+        let to = self.check_target(pools, &tx)?;
+
+        let result = match self.with_fees(metrics, tx).await {
+            Ok(tx) => metrics
+                .track_rpc(
+                    "eth_sendRawTransaction",
+                    self.evm_provider.send_transaction(tx),
+                )
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        self.record_submission(
+            metrics,
+            result.as_ref().map(|_| ()).map_err(|e| format!("{:#}", e)),
+        );
+        let pending = result?;
+
+        info!("Sent transaction {} to {}", pending.tx_hash(), to);
+
+        Ok(pending)
+    }
+}
+
+/// Contracts the signer may approve the Yield contract on: the tokens of the tracked
+/// pools and the position managers of the address registry
+fn approval_targets(
+    pools: &DashMap<String, Pool>,
+    addresses: &AddressRegistry,
+) -> HashSet<Address> {
+    let tokens: Vec<Address> = pools
+        .iter()
+        .flat_map(|entry| {
+            [&entry.token0.address, &entry.token1.address]
+                .map(|token| Address::from_str(token).ok())
+        })
+        .flatten()
+        .collect();
+
+    [DexType::UniswapV3, DexType::PancakeSwapV3]
+        .iter()
+        .filter_map(|dex_type| addresses.dex(dex_type).position_manager)
+        .chain(tokens)
+        .collect()
+}
+
+/// Whether a transaction only approves the Yield contract, which pulls the tokens of the
+/// signer when minting and its positions when removing or rebalancing them
+///
+/// Any contract can implement the approval selectors, so only the `targets` are trusted
+/// to do nothing else.
+fn approves_yield_contract(
+    tx: &TransactionRequest,
+    yield_contract: Address,
+    targets: &HashSet<Address>,
+) -> bool {
+    let (Some(TxKind::Call(to)), Some(input)) = (tx.to, tx.input.input()) else {
+        return false;
+    };
+    if !targets.contains(&to) || tx.value.is_some_and(|value| !value.is_zero()) {
+        return false;
+    }
+
+    IApproval::approveCall::abi_decode(input).is_ok_and(|call| call.spender == yield_contract)
+        || IApproval::setApprovalForAllCall::abi_decode(input)
+            .is_ok_and(|call| call.operator == yield_contract)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{U256, address};
+
+    use super::*;
+    use crate::types::Token;
+
+    const YIELD: Address = address!("0x1111111111111111111111111111111111111111");
+    const TOKEN: Address = address!("0x2222222222222222222222222222222222222222");
+    const NFPM: Address = address!("0x3333333333333333333333333333333333333333");
+    const UNKNOWN: Address = address!("0x4444444444444444444444444444444444444444");
+
+    fn targets() -> HashSet<Address> {
+        let token = |address: Address| Token {
+            address: address.to_string(),
+            symbol: "T".to_string(),
+            decimals: 18,
+        };
+        let pools = DashMap::new();
+        pools.insert(
+            "0xpool".to_string(),
+            Pool {
+                address: "0xpool".to_string(),
+                dex_type: DexType::UniswapV3,
+                token0: token(TOKEN),
+                // Skipped, not an address
+                token1: Token {
+                    address: "0xinvalid".to_string(),
+                    ..token(TOKEN)
+                },
+                fee: 0.3,
+                tick_spacing: 60,
+                current_tick: 0,
+                price0: 1.0,
+                price1: 1.0,
+                liquidity: 0,
+                updated_at: 0,
+                block_number: 0,
+                annotation: None,
+            },
+        );
+
+        let mut addresses = AddressRegistry::default();
+        addresses.uniswap_v3.position_manager = Some(NFPM);
+        approval_targets(&pools, &addresses)
+    }
+
+    fn approve(to: Address, spender: Address) -> TransactionRequest {
+        TransactionRequest::default().to(to).input(
+            IApproval::approveCall {
+                spender,
+                amount: U256::MAX,
+            }
+            .abi_encode()
+            .into(),
+        )
+    }
+
+    #[test]
+    fn trusts_the_pool_tokens_and_position_managers() {
+        assert_eq!(targets(), HashSet::from([TOKEN, NFPM]));
+    }
+
+    #[test]
+    fn accepts_an_approval_of_the_yield_contract_on_a_pool_token() {
+        assert!(approves_yield_contract(
+            &approve(TOKEN, YIELD),
+            YIELD,
+            &targets()
+        ));
+
+        let tx = TransactionRequest::default().to(NFPM).input(
+            IApproval::setApprovalForAllCall {
+                operator: YIELD,
+                approved: true,
+            }
+            .abi_encode()
+            .into(),
+        );
+        assert!(approves_yield_contract(&tx, YIELD, &targets()));
+    }
+
+    #[test]
+    fn rejects_an_approval_on_an_unknown_contract() {
+        assert!(!approves_yield_contract(
+            &approve(UNKNOWN, YIELD),
+            YIELD,
+            &targets()
+        ));
+    }
+
+    #[test]
+    fn rejects_other_calls_and_spenders() {
+        let targets = targets();
+        assert!(!approves_yield_contract(
+            &approve(TOKEN, UNKNOWN),
+            YIELD,
+            &targets
+        ));
+        assert!(!approves_yield_contract(
+            &approve(TOKEN, YIELD).value(U256::from(1)),
+            YIELD,
+            &targets
+        ));
+        assert!(!approves_yield_contract(
+            &TransactionRequest::default().to(TOKEN),
+            YIELD,
+            &targets
+        ));
+    }
+}
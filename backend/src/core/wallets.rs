@@ -0,0 +1,153 @@
+use alloy::primitives::Address;
+use anyhow::Result;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    core::{positions, strategy, swaps},
+    state::AppState,
+    types::{Pool, Position},
+    utils::amm_math::{MAX_TICK, MIN_TICK},
+};
+
+/// Largest tick spacing of the fee tiers, a range within it of both tick bounds is a full
+/// range one
+const FULL_RANGE_TOLERANCE_TICKS: i32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionClass {
+    /// Covers every price, so its liquidity earns the least fees
+    FullRange,
+    InRange,
+    /// Earns no fees until the price comes back into its range
+    OutOfRange,
+    /// Has no liquidity left, only fees to collect if any
+    Dust,
+    /// Its pool is not tracked, so its current tick is unknown
+    Untracked,
+}
+
+/// Suggested change of an unmanaged position
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Recommendation {
+    pub message: String,
+    /// Range to move the liquidity to, centered on the current tick
+    pub tick_lower: Option<i32>,
+    pub tick_upper: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WalletPosition {
+    pub position: Position,
+    pub class: PositionClass,
+    pub recommendation: Option<Recommendation>,
+}
+
+/// Positions of a wallet the backend doesn't manage, with what to do with them
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WalletAnalysis {
+    pub owner: String,
+    pub positions: Vec<WalletPosition>,
+}
+
+/// Fetch and classify the positions of a wallet, without sending anything
+pub async fn analyze_wallet(app_state: &AppState, owner: Address) -> Result<WalletAnalysis> {
+    let positions = positions::fetch_wallet_positions(app_state, owner).await?;
+
+    Ok(WalletAnalysis {
+        owner: owner.to_string(),
+        positions: positions
+            .into_iter()
+            .map(|position| {
+                let pool = position
+                    .pool
+                    .as_ref()
+                    .and_then(|address| app_state.pools.get(&address.to_lowercase()))
+                    .map(|entry| entry.value().clone());
+                let (class, recommendation) = classify(app_state, &position, pool.as_ref());
+                WalletPosition {
+                    position,
+                    class,
+                    recommendation,
+                }
+            })
+            .collect(),
+    })
+}
+
+fn classify(
+    app_state: &AppState,
+    position: &Position,
+    pool: Option<&Pool>,
+) -> (PositionClass, Option<Recommendation>) {
+    if position.liquidity == 0 {
+        let has_fees = position.uncollected_fees0 != "0" || position.uncollected_fees1 != "0";
+        let message = if has_fees {
+            "Collect the remaining fees and burn the empty position"
+        } else {
+            "Burn the empty position"
+        };
+        return (
+            PositionClass::Dust,
+            Some(Recommendation {
+                message: message.to_string(),
+                tick_lower: None,
+                tick_upper: None,
+            }),
+        );
+    }
+
+    let full_range = position.tick_lower - MIN_TICK < FULL_RANGE_TOLERANCE_TICKS
+        && MAX_TICK - position.tick_upper < FULL_RANGE_TOLERANCE_TICKS;
+    let Some(pool) = pool else {
+        let class = if full_range {
+            PositionClass::FullRange
+        } else {
+            PositionClass::Untracked
+        };
+        return (
+            class,
+            Some(Recommendation {
+                message: "Track the pool of the position to get a managed range suggestion"
+                    .to_string(),
+                tick_lower: None,
+                tick_upper: None,
+            }),
+        );
+    };
+
+    // The strategy width of the pool when it has one, its tuned default width otherwise
+    let address = pool.address.to_lowercase();
+    let width = match app_state.strategies.get(&address) {
+        Some(strategy) => strategy::range_width_ticks(strategy.range_width_percent),
+        None => app_state.range_tuner.width(&address, pool),
+    };
+    let (tick_lower, tick_upper) =
+        swaps::centered_range(pool.current_tick, pool.tick_spacing, width);
+    let suggestion = |message: String| Recommendation {
+        message,
+        tick_lower: Some(tick_lower),
+        tick_upper: Some(tick_upper),
+    };
+
+    if full_range {
+        (
+            PositionClass::FullRange,
+            Some(suggestion(format!(
+                "Migrate this full-range position to a managed band of {} ticks around the current price to earn more fees with the same liquidity",
+                tick_upper - tick_lower
+            ))),
+        )
+    } else if pool.current_tick < position.tick_lower || pool.current_tick >= position.tick_upper {
+        (
+            PositionClass::OutOfRange,
+            Some(suggestion(
+                "The position earns no fees, rebalance it into a range around the current price"
+                    .to_string(),
+            )),
+        )
+    } else {
+        (PositionClass::InRange, None)
+    }
+}
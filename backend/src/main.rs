@@ -1,11 +1,16 @@
-use actix_cors::Cors;
-use actix_web::{App, HttpServer, web};
-use tracing::{error, info};
+use std::time::Duration;
+
+use actix_web::{App, HttpServer, middleware::from_fn, web};
+use once_cell::sync::Lazy;
+use tracing::{info, warn};
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
-use utoipa_actix_web::AppExt;
+
+use crate::utils::log_sampling::{LOG_SAMPLER, SamplingLayer};
+use utoipa::OpenApi;
+use utoipa_actix_web::{AppExt, scope};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::config::CONFIG;
+use crate::config::{CONFIG, cli::CLI_ARGS};
 
 mod api;
 mod config;
@@ -19,6 +24,9 @@ async fn main() -> std::io::Result<()> {
     // Load .env file
     dotenvy::dotenv().ok();
 
+    // Exit early on invalid arguments, before anything is initialized
+    let cli_args = Lazy::force(&CLI_ARGS);
+
     // Initialize the logger logic
     let file_appender = tracing_appender::rolling::daily("./logs", "yieldai.log");
     let (file_writer, _guard) = tracing_appender::non_blocking(file_appender);
@@ -29,46 +37,118 @@ async fn main() -> std::io::Result<()> {
     // File layer
     let file_layer = fmt::layer().with_writer(file_writer).with_ansi(false); // don't add colors to the file logs
 
+    // The remote config and the Vault secrets must be fetched before the config is loaded
+    let timeout = Duration::from_secs(crate::config::DEFAULT_HTTP_TIMEOUT_SECS);
+    if let Err(e) = crate::config::remote::init(timeout).await {
+        eprintln!("Failed to load config: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = crate::config::vault::init(timeout).await {
+        eprintln!("Failed to read the secrets from Vault: {:#}", e);
+        std::process::exit(1);
+    }
+
+    let config = CONFIG.get();
+
     // 🔥 Only accept logs that match your crate
-    let filter = EnvFilter::new("yieldai=trace");
+    let log_level = cli_args
+        .log_level
+        .as_ref()
+        .or(config.toml.app.log_level.as_ref());
+    let filter = match log_level {
+        Some(directives) if directives.contains('=') => EnvFilter::new(directives),
+        Some(level) => EnvFilter::new(format!("yieldai={}", level)),
+        None => EnvFilter::new("yieldai=trace"),
+    };
 
     // Combine both
     tracing_subscriber::registry()
         .with(filter)
+        .with(SamplingLayer)
         .with(console_layer)
         .with(file_layer)
         .init();
 
     info!("Logger initialized Successfully");
 
-    info!("Toml config: {:?}", CONFIG.toml);
+    match (
+        crate::config::toml_config_path(),
+        crate::config::remote::config_url(),
+    ) {
+        (Some(path), _) => info!("Toml config loaded from {}: {:?}", path, config.toml),
+        (None, Some(url)) => info!(
+            "Toml config fetched from {}: {:?}",
+            crate::config::remote::redacted_url(&url),
+            config.toml
+        ),
+        (None, None) => info!("Embedded toml config loaded: {:?}", config.toml),
+    }
+    if let Some(app_env) = config.app_env {
+        match (
+            crate::config::toml_config_path(),
+            crate::config::profile::profile_config_path(app_env),
+        ) {
+            (None, _) => info!("Embedded {} profile applied", app_env.name()),
+            (Some(_), Some(path)) => info!("Profile {} applied", path),
+            (Some(_), None) => warn!(
+                "APP_ENV is {} but there is no {}.toml next to the toml config",
+                app_env.name(),
+                app_env.name()
+            ),
+        }
+    }
+
+    LOG_SAMPLER.set_rates(config.log_sampling.clone());
 
     let app_state = web::Data::new(state::AppState::new().await);
 
-    info!("Starting HTTP server at http://localhost:{}", CONFIG.port);
+    core::config_watch::spawn_config_watcher(app_state.clone().into_inner());
+    core::gas::spawn_gas_sampler(app_state.clone().into_inner());
+    core::pool_refresh::spawn_pool_refresher(app_state.clone().into_inner());
+    core::ingestion::spawn_swap_ingestion(app_state.clone().into_inner());
+    core::swap_subscription::spawn_swap_subscription(app_state.clone().into_inner());
+    core::range_tuning::spawn_range_tuner(app_state.clone().into_inner());
+    core::rate_limit::spawn_rate_limit_sweeper(app_state.clone().into_inner());
+
+    info!(
+        "Starting HTTP server at http://{}:{}",
+        config.host, config.port
+    );
     info!(
-        "Swagger UI available at http://localhost:{}/swagger-ui/",
-        CONFIG.port
+        "API v1 available at http://{}:{}/api/v1",
+        config.host, config.port
+    );
+    info!(
+        "Swagger UI available at http://{}:{}/docs/",
+        config.host, config.port
     );
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header();
+    let server_config = config.clone();
+    let mut server = HttpServer::new(move || {
+        let cors = api::middleware::cors(&server_config.cors);
 
         let (app, app_api) = App::new()
+            .wrap(from_fn(api::middleware::api_key_tier))
+            .wrap(from_fn(api::middleware::rate_limit))
+            .wrap(from_fn(api::middleware::track_metrics))
             .wrap(cors)
+            .wrap(from_fn(api::middleware::request_id))
             .into_utoipa_app()
+            .openapi(api::ApiDoc::openapi())
             .app_data(app_state.clone())
-            .service(api::get_index_service)
-            .service(api::get_health_service)
-            .service(api::get_pools_service)
+            .configure(api::configure_root)
+            .service(scope("/api/v1").configure(api::configure_v1))
             .split_for_parts();
 
-        app.service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", app_api))
-    })
-    .bind(("127.0.0.1", CONFIG.port))?
-    .run()
-    .await
+        app.service(SwaggerUi::new("/docs/{_:.*}").url("/api-docs/openapi.json", app_api))
+    });
+    // Defaults to the number of physical CPU cores
+    if let Some(workers) = config.workers {
+        server = server.workers(workers);
+    }
+
+    server
+        .bind((config.host.as_str(), config.port))?
+        .run()
+        .await
 }
@@ -1,31 +1,198 @@
-use dashmap::DashMap;
-use tracing::info;
+use std::sync::Arc;
+
+use dashmap::{DashMap, Entry};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
 
 use crate::{
-    core,
-    types::{EvmProvider, Pool},
+    config::{
+        CONFIG, Config, POOL_EVENTS_CAPACITY, PoolStrategy, SWAP_HISTORY_RATE_LIMIT_BURST,
+        SWAP_HISTORY_RATE_LIMIT_RPS,
+    },
+    core::{
+        self, analytics::PriceHistory, annotations::Annotations, anomaly::AnomalyDetector,
+        auth::AuthService, executions::ExecutionLedger, gas::GasTracker, ingestion::Checkpoints,
+        metrics::Metrics, range_tuning::RangeTuner, rate_limit::RateLimiter,
+        snapshots::SnapshotStore, tx::TxManager,
+    },
+    types::{EvmProvider, NftMetadata, Pool, PoolEvent},
+    utils::time::unix_timestamp,
 };
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub evm_provider: EvmProvider,
     pub pools: DashMap<String, Pool>,
+    /// Strategy of the pools with a `[pools.strategy]` table, kept in sync with the config
+    pub strategies: DashMap<String, PoolStrategy>,
+    /// Broadcasts every change made to `pools` to the live subscribers (WebSocket clients)
+    pub pool_events: broadcast::Sender<PoolEvent>,
+    pub auth: Arc<AuthService>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Limits the requests fetching swap history, each costs several `eth_getLogs` calls
+    pub swap_history_limiter: Arc<RateLimiter>,
+    pub metrics: Arc<Metrics>,
+    pub anomalies: Arc<AnomalyDetector>,
+    pub price_history: Arc<PriceHistory>,
+    /// Last block whose swaps were ingested into `price_history`, per pool
+    pub checkpoints: Arc<Checkpoints>,
+    /// Labels and notes of the positions and pools
+    pub annotations: Arc<Annotations>,
+    /// Transactions mined for the positions of the tracked pools
+    pub executions: Arc<ExecutionLedger>,
+    /// State of the positions before and after each execution
+    pub snapshots: Arc<SnapshotStore>,
+    /// Position NFT metadata cache, keyed by token ID
+    pub nft_metadata: DashMap<String, NftMetadata>,
+    pub gas: Arc<GasTracker>,
+    /// Default range width of each pool, tuned from the recorded ticks
+    pub range_tuner: Arc<RangeTuner>,
+    /// Sends the signer transactions, restricted to the allowlisted contracts
+    pub tx_manager: Arc<TxManager>,
+    /// Unix timestamp (seconds) of the process start
+    pub started_at: u64,
 }
 
 impl AppState {
     pub async fn new() -> Self {
+        let started_at = unix_timestamp();
+        let metrics = Arc::new(Metrics::new());
+
         let evm_provider = core::init::init_evm_provider()
             .await
             .expect("Failed to initialize EVM provider");
-        let pools = core::init::init_pools_state(&evm_provider)
+        let pools = core::init::init_pools_state(&evm_provider, &metrics)
             .await
             .expect("Failed to initialize pools state");
 
-        info!("Pools state initialized: {:?}", pools);
+        info!("Pools state initialized with {} pools", pools.len());
+
+        let price_history = PriceHistory::new();
+        for entry in pools.iter() {
+            price_history.record(entry.key(), entry.value());
+        }
+
+        let (pool_events, _) = broadcast::channel(POOL_EVENTS_CAPACITY);
+
+        let config = CONFIG.get();
+
+        let jwt_secret = match &config.jwt_secret {
+            Some(secret) => secret.expose().as_bytes().to_vec(),
+            None => {
+                warn!("JWT_SECRET not set, using a random secret: tokens won't survive a restart");
+                core::auth::random_secret()
+            }
+        };
 
         Self {
+            tx_manager: Arc::new(
+                TxManager::new(evm_provider.clone())
+                    .expect("Failed to load the transaction fail-safe state"),
+            ),
             evm_provider,
             pools,
+            strategies: config_strategies(&config).collect(),
+            pool_events,
+            auth: Arc::new(AuthService::new(jwt_secret)),
+            rate_limiter: config.rate_limit.as_ref().map(|rate_limit| {
+                Arc::new(RateLimiter::new(
+                    rate_limit.requests_per_second,
+                    rate_limit.burst,
+                ))
+            }),
+            swap_history_limiter: Arc::new(RateLimiter::new(
+                SWAP_HISTORY_RATE_LIMIT_RPS,
+                SWAP_HISTORY_RATE_LIMIT_BURST,
+            )),
+            metrics,
+            anomalies: Arc::new(AnomalyDetector::new()),
+            price_history: Arc::new(price_history),
+            checkpoints: Arc::new(
+                Checkpoints::load().expect("Failed to load the swap ingestion checkpoints"),
+            ),
+            annotations: Arc::new(
+                Annotations::load().expect("Failed to load the position and pool annotations"),
+            ),
+            executions: Arc::new(
+                ExecutionLedger::load().expect("Failed to load the position executions"),
+            ),
+            snapshots: Arc::new(
+                SnapshotStore::load().expect("Failed to load the position snapshots"),
+            ),
+            nft_metadata: DashMap::new(),
+            gas: Arc::new(GasTracker::new()),
+            range_tuner: Arc::new(RangeTuner::new()),
+            started_at,
         }
     }
+
+    /// Start tracking a pool, unless it is already tracked
+    ///
+    /// # Returns:
+    /// * Whether the pool was added
+    pub fn track_pool(&self, address: String, pool: Pool) -> bool {
+        match self.pools.entry(address) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                info!("Registered new pool: {}", entry.key());
+                self.price_history.record(entry.key(), &pool);
+                entry.insert(pool.clone());
+                self.publish_pool_event(PoolEvent::Added { pool });
+                true
+            }
+        }
+    }
+
+    /// Stop tracking a pool and forget everything recorded about it
+    ///
+    /// Its annotation is kept for when the pool is tracked again, only the annotations
+    /// endpoint deletes it.
+    ///
+    /// # Returns:
+    /// * The removed pool, none if it was not tracked
+    pub fn untrack_pool(&self, address: &str) -> Option<Pool> {
+        let (_, pool) = self.pools.remove(address)?;
+
+        info!("Stopped tracking pool: {}", address);
+        self.anomalies.forget(address);
+        self.price_history.forget(address);
+        self.range_tuner.forget(address);
+        if let Err(e) = self.checkpoints.forget(address) {
+            warn!(
+                "Failed to forget the checkpoint of pool {}: {:#}",
+                address, e
+            );
+        }
+        self.publish_pool_event(PoolEvent::Removed {
+            address: address.to_string(),
+        });
+        Some(pool)
+    }
+
+    /// Replace the pool strategies with the ones of a new config
+    pub fn apply_strategies(&self, config: &Config) {
+        let strategies: Vec<(String, PoolStrategy)> = config_strategies(config).collect();
+        self.strategies
+            .retain(|address, _| strategies.iter().any(|(known, _)| known == address));
+        for (address, strategy) in strategies {
+            if self.strategies.get(&address).as_deref() != Some(&strategy) {
+                info!("Strategy of pool {} set to {:?}", address, strategy);
+                self.strategies.insert(address, strategy);
+            }
+        }
+    }
+
+    /// Notify the subscribers of a pool change
+    pub fn publish_pool_event(&self, event: PoolEvent) {
+        // Sending only fails when nobody is subscribed, which is fine
+        let _ = self.pool_events.send(event);
+    }
+}
+
+fn config_strategies(config: &Config) -> impl Iterator<Item = (String, PoolStrategy)> + '_ {
+    config
+        .toml
+        .pools
+        .iter()
+        .filter_map(|pool| pool.strategy.clone().map(|strategy| (pool.key(), strategy)))
 }
@@ -1,13 +1,58 @@
+use std::str::FromStr;
+
+use alloy::primitives::Address;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Clone, Serialize, ToSchema)]
+use crate::core::{analytics::Candle, annotations::Annotation, anomaly::PoolAnomaly};
+
+#[derive(Debug, Deserialize, Clone, Serialize, ToSchema, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub enum DexType {
     UniswapV3,
     PancakeSwapV3,
 }
 
+impl DexType {
+    /// Value of the `DexType` enum of the Yield contract
+    pub fn contract_id(&self) -> u8 {
+        match self {
+            DexType::UniswapV3 => 0,
+            DexType::PancakeSwapV3 => 1,
+        }
+    }
+}
+
+/// Parse an address, checking its EIP-55 checksum when it is mixed-case: an all lowercase
+/// or all uppercase address carries no checksum
+pub fn parse_checksummed_address(address: &str) -> anyhow::Result<Address> {
+    let digits = address.strip_prefix("0x").unwrap_or(address);
+    let mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+
+    if mixed_case {
+        Address::parse_checksummed(address, None)
+            .map_err(|e| anyhow::anyhow!("{} is not a valid EIP-55 address: {}", address, e))
+    } else {
+        Address::from_str(address)
+            .map_err(|e| anyhow::anyhow!("{} is not a valid address: {}", address, e))
+    }
+}
+
+/// Deserializer of the configured addresses, see `parse_checksummed_address`
+pub fn checksummed_address<'de, D>(deserializer: D) -> Result<Address, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let address: String = Deserialize::deserialize(deserializer)?;
+    parse_checksummed_address(&address).map_err(serde::de::Error::custom)
+}
+
+/// Lowercase hex of an address, the key the pools are tracked under
+pub fn address_key(address: &Address) -> String {
+    format!("{:#x}", address)
+}
+
 /// Custom deserializer that converts to lowercase
 /// 'de is rust lifetime standard for deserialization
 pub fn lowercase_address<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -52,6 +97,168 @@ pub struct Pool {
     pub current_tick: i32,
     pub price0: f64,
     pub price1: f64,
+    /// In-range liquidity, as a string since it doesn't fit in a JSON number
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+    /// Unix timestamp (seconds) of the last fetch of the pool from the blockchain
+    pub updated_at: u64,
+    /// Block the state of the pool was read at, older states are not applied
+    #[serde(default)]
+    pub block_number: u64,
+    /// Labels and note of the operators, only returned by the pools list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+}
+
+/// Liquidity position NFT of a NonfungiblePositionManager
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Position {
+    /// Token ID of the position NFT, as a string since it is a uint256
+    pub token_id: String,
+    pub dex_type: DexType,
+    /// Holder of the position NFT, the Yield contract or the signer wallet
+    pub owner: String,
+    pub token0: String,
+    pub token1: String,
+    pub fee: f64,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+    /// Fees collectable right now, in the smallest unit of token0
+    pub uncollected_fees0: String,
+    /// Fees collectable right now, in the smallest unit of token1
+    pub uncollected_fees1: String,
+    /// Address of the tracked pool of the position, if any
+    pub pool: Option<String>,
+    /// Price0 at the lower tick, only known for tracked pools
+    pub price_lower: Option<f64>,
+    /// Price0 at the upper tick, only known for tracked pools
+    pub price_upper: Option<f64>,
+    /// Whether the current tick of the tracked pool is within the range
+    pub in_range: Option<bool>,
+    /// Labels and note of the operators
+    pub annotation: Option<Annotation>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct MintPositionRequest {
+    /// Address of the tracked pool to provide liquidity to
+    #[serde(deserialize_with = "lowercase_address")]
+    pub pool: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Amount of token0 to deposit, in its smallest unit
+    pub amount0_desired: String,
+    /// Amount of token1 to deposit, in its smallest unit
+    pub amount1_desired: String,
+    /// Minimum amount of token0 to deposit, defaults to 0
+    pub amount0_min: Option<String>,
+    /// Minimum amount of token1 to deposit, defaults to 0
+    pub amount1_min: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MintedPosition {
+    /// Hashes of the token approvals sent before the mint, in order
+    pub approval_tx_hashes: Vec<String>,
+    pub tx_hash: String,
+    pub token_id: String,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+    /// Deposited amount of token0, in its smallest unit
+    pub amount0: String,
+    /// Deposited amount of token1, in its smallest unit
+    pub amount1: String,
+}
+
+/// Swap done between closing and reopening a position, to rebalance the token amounts
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RebalanceSwap {
+    /// Address of the pool token to sell
+    pub token_in: String,
+    /// Amount of `token_in` to sell, in its smallest unit
+    pub amount: String,
+    /// Minimum amount of the other token to receive, defaults to 0
+    pub amount_out_min: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RebalancePositionRequest {
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub swap: Option<RebalanceSwap>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RebalancedPosition {
+    /// Hashes of the submitted transactions, in order
+    pub tx_hashes: Vec<String>,
+    pub old_token_id: String,
+    pub new_token_id: String,
+    #[schema(value_type = String)]
+    #[serde(with = "u128_string")]
+    pub liquidity: u128,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CollectedFees {
+    pub tx_hash: String,
+    pub token_id: String,
+    /// Collected amount of token0, in its smallest unit
+    pub amount0: String,
+    /// Collected amount of token1, in its smallest unit
+    pub amount1: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ClosedPosition {
+    pub token_id: String,
+    /// Whether the close was only simulated
+    pub dry_run: bool,
+    /// Hash of the approval of the Yield contract on the position manager, absent when it
+    /// was already approved
+    pub approval_tx_hash: Option<String>,
+    /// Hash of the transaction, absent for a dry run
+    pub tx_hash: Option<String>,
+    /// Withdrawn amount of token0 including the fees, in its smallest unit
+    pub amount0: String,
+    /// Withdrawn amount of token1 including the fees, in its smallest unit
+    pub amount1: String,
+    /// Whether the position NFT was burned
+    pub burned: bool,
+}
+
+/// Metadata of a position NFT, as returned by its `tokenURI`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NftMetadata {
+    pub token_id: String,
+    pub dex_type: DexType,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Image URI of the metadata, usually a base64 SVG data URI
+    pub image: Option<String>,
+    /// Decoded SVG image, when the image is an SVG data URI
+    pub svg: Option<String>,
+    /// Unix timestamp (seconds) of the fetch of the metadata
+    pub fetched_at: u64,
+}
+
+/// (De)serialize a u128 as a decimal string, JSON numbers lose precision above 2^53
+pub mod u128_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize, ToSchema)]
@@ -60,3 +267,172 @@ pub struct Token {
     pub symbol: String,
     pub decimals: u8,
 }
+
+impl Token {
+    /// Check if this token has the given symbol or address, case insensitive
+    pub fn matches(&self, symbol_or_address: &str) -> bool {
+        self.symbol.eq_ignore_ascii_case(symbol_or_address)
+            || self.address.eq_ignore_ascii_case(symbol_or_address)
+    }
+}
+
+/// Tracked pools containing a given token
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TokenPools {
+    pub token: Token,
+    pub pools: Vec<Pool>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolSortField {
+    Fee,
+    Price0,
+    Price1,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, ToSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Token whose price a chart shows
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, ToSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartSide {
+    /// Price of token0 in token1 (`price0`)
+    #[default]
+    Token0,
+    /// Price of token1 in token0 (`price1`)
+    Token1,
+}
+
+/// Currency the chart prices are expressed in
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, ToSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartQuote {
+    /// The other token of the pool
+    #[default]
+    Token,
+    Usd,
+}
+
+/// Tick and prices of a pool at a given point in time
+#[derive(Debug, Deserialize, Clone, Serialize, ToSchema)]
+pub struct PoolPriceState {
+    pub current_tick: i32,
+    pub price0: f64,
+    pub price1: f64,
+}
+
+impl From<&Pool> for PoolPriceState {
+    fn from(pool: &Pool) -> Self {
+        Self {
+            current_tick: pool.current_tick,
+            price0: pool.price0,
+            price1: pool.price1,
+        }
+    }
+}
+
+/// Result of a forced pool resync
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolRefresh {
+    pub address: String,
+    pub previous: PoolPriceState,
+    pub current: PoolPriceState,
+}
+
+/// Change notification emitted whenever the tracked pools are modified
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PoolEvent {
+    /// A pool started being tracked
+    Added { pool: Pool },
+    /// The tick or prices of a tracked pool changed
+    Updated {
+        address: String,
+        #[serde(flatten)]
+        state: PoolPriceState,
+    },
+    /// A pool stopped being tracked
+    Removed { address: String },
+    /// Two consecutive snapshots of a pool differ in a suspicious way
+    Anomaly {
+        #[serde(flatten)]
+        anomaly: PoolAnomaly,
+    },
+}
+
+/// Message sent by a client of the candle WebSocket feed
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum CandleFeedRequest {
+    /// Receive a snapshot of the recent candles of a pool, then every change of its
+    /// latest candle
+    Subscribe {
+        #[serde(deserialize_with = "lowercase_address")]
+        pool: String,
+        #[serde(default)]
+        side: ChartSide,
+        /// Duration of a candle, defaults to 1 hour
+        interval_secs: Option<u64>,
+        /// Number of candles of the snapshot, defaults to 100
+        limit: Option<usize>,
+    },
+    Unsubscribe {
+        #[serde(deserialize_with = "lowercase_address")]
+        pool: String,
+        #[serde(default)]
+        side: ChartSide,
+        interval_secs: Option<u64>,
+    },
+}
+
+/// Message sent to a client of the candle WebSocket feed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CandleFeedMessage {
+    /// Recent candles of a new subscription, oldest first
+    Snapshot {
+        pool: String,
+        side: ChartSide,
+        interval_secs: u64,
+        candles: Vec<Candle>,
+    },
+    /// The latest candle of a subscription changed, or a new one started
+    Candle {
+        pool: String,
+        side: ChartSide,
+        interval_secs: u64,
+        candle: Candle,
+    },
+    Unsubscribed {
+        pool: String,
+        side: ChartSide,
+        interval_secs: u64,
+    },
+    /// A request of the client was rejected
+    Error { message: String },
+}
+
+/// Request body used to start tracking a new pool at runtime
+#[derive(Debug, Deserialize, Clone, Serialize, ToSchema)]
+pub struct RegisterPoolRequest {
+    #[serde(deserialize_with = "lowercase_address")]
+    pub address: String,
+    pub dex_type: DexType,
+}
+
+/// Paginated response envelope
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Total number of items matching the filters, before pagination
+    pub total: usize,
+    pub page: usize,
+    pub limit: usize,
+}
@@ -1,10 +1,19 @@
 use anyhow::Result;
 
+/// Lowest tick of a Uniswap V3 pool
+pub const MIN_TICK: i32 = -887_272;
+
+/// Highest tick of a Uniswap V3 pool
+pub const MAX_TICK: i32 = 887_272;
+
+/// Widest range of a Uniswap V3 pool, in ticks
+pub const MAX_RANGE_WIDTH: i32 = MAX_TICK - MIN_TICK;
+
 /// Convert a tick to a price0.
 /// It caclulate the price of token0 in terms of token1.
 /// 1 token0 = price * token1
 pub fn tick_to_price(tick: i32, token0_decimals: u8, token1_decimals: u8) -> Result<f64> {
-    let price_tick = 1.0001f64.powi(tick as i32);
+    let price_tick = 1.0001f64.powi(tick);
 
     let diff_decimals = token1_decimals as i8 - token0_decimals as i8;
 
@@ -12,3 +21,88 @@ pub fn tick_to_price(tick: i32, token0_decimals: u8, token1_decimals: u8) -> Res
 
     Ok(price)
 }
+
+/// Token amounts, in their smallest unit, held by `liquidity` over a tick range when the
+/// pool is at `current_tick`
+pub fn liquidity_to_amounts(
+    liquidity: u128,
+    current_tick: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> (f64, f64) {
+    let sqrt_price = |tick: i32| 1.0001f64.powf(tick as f64 / 2.0);
+    let liquidity = liquidity as f64;
+    let (sqrt_lower, sqrt_upper) = (sqrt_price(tick_lower), sqrt_price(tick_upper));
+
+    if current_tick < tick_lower {
+        let amount0 = liquidity * (sqrt_upper - sqrt_lower) / (sqrt_lower * sqrt_upper);
+        (amount0, 0.0)
+    } else if current_tick >= tick_upper {
+        (0.0, liquidity * (sqrt_upper - sqrt_lower))
+    } else {
+        let sqrt_current = sqrt_price(current_tick);
+        let amount0 = liquidity * (sqrt_upper - sqrt_current) / (sqrt_current * sqrt_upper);
+        (amount0, liquidity * (sqrt_current - sqrt_lower))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqrt_price(tick: i32) -> f64 {
+        1.0001f64.powf(tick as f64 / 2.0)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-12,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn holds_only_token0_below_the_range() {
+        let (amount0, amount1) = liquidity_to_amounts(1_000_000, -20, -10, 10);
+
+        let (sqrt_lower, sqrt_upper) = (sqrt_price(-10), sqrt_price(10));
+        assert_close(
+            amount0,
+            1e6 * (sqrt_upper - sqrt_lower) / (sqrt_lower * sqrt_upper),
+        );
+        assert_eq!(amount1, 0.0);
+    }
+
+    #[test]
+    fn holds_only_token1_above_the_range() {
+        let expected = 1e6 * (sqrt_price(10) - sqrt_price(-10));
+        assert_eq!(
+            liquidity_to_amounts(1_000_000, 20, -10, 10),
+            (0.0, expected)
+        );
+        // The upper tick is out of the range
+        assert_eq!(
+            liquidity_to_amounts(1_000_000, 10, -10, 10),
+            (0.0, expected)
+        );
+    }
+
+    #[test]
+    fn holds_both_tokens_within_the_range() {
+        let (amount0, amount1) = liquidity_to_amounts(1_000_000, 0, -10, 10);
+
+        assert_close(amount0, 1e6 * (1.0 - 1.0 / sqrt_price(10)));
+        assert_close(amount1, 1e6 * (1.0 - sqrt_price(-10)));
+        // A range centered on tick 0 holds as much of both tokens
+        assert_close(amount0, amount1);
+    }
+
+    #[test]
+    fn converts_a_tick_to_a_price_with_the_decimals() {
+        assert_eq!(tick_to_price(0, 18, 18).unwrap(), 1.0);
+        assert_close(tick_to_price(0, 18, 6).unwrap(), 1e12);
+        assert_close(tick_to_price(100, 6, 6).unwrap(), 1.0001f64.powi(100));
+    }
+}
@@ -0,0 +1,31 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Read a JSON file, the default value when the file does not exist yet
+pub fn load<T: DeserializeOwned + Default>(path: &str) -> Result<T> {
+    match fs::read_to_string(path) {
+        Ok(data) => {
+            serde_json::from_str(&data).with_context(|| format!("Unable to parse {}", path))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("Unable to read {}", path)),
+    }
+}
+
+/// Write a JSON file through a temporary file, so a crash never leaves a truncated file
+pub fn save<T: Serialize>(path: &str, value: &T) -> Result<()> {
+    let path = Path::new(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Unable to create the {} directory", dir.display()))?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("Unable to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Unable to replace {}", path.display()))?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, RwLock};
+
+use once_cell::sync::Lazy;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Sampling rates of the log events per module, shared by the tracing layer and the admin
+/// endpoints so the rates can be changed without restarting
+pub static LOG_SAMPLER: Lazy<LogSampler> = Lazy::new(LogSampler::default);
+
+/// Keep 1 in N log events of the configured modules
+///
+/// Warnings and errors are always kept. A rate applies to a target and all its
+/// submodules, the longest matching target wins.
+#[derive(Debug, Default)]
+pub struct LogSampler {
+    /// Target prefix → N
+    rates: RwLock<BTreeMap<String, u64>>,
+    /// Number of events seen per target
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl LogSampler {
+    pub fn rates(&self) -> BTreeMap<String, u64> {
+        self.rates
+            .read()
+            .expect("Log sampling lock poisoned")
+            .clone()
+    }
+
+    /// Replace all the sampling rates, a rate of 1 keeps every event
+    pub fn set_rates(&self, rates: BTreeMap<String, u64>) {
+        *self.rates.write().expect("Log sampling lock poisoned") = rates;
+        self.counts
+            .lock()
+            .expect("Log sampling lock poisoned")
+            .clear();
+    }
+
+    fn keep(&self, metadata: &Metadata<'_>) -> bool {
+        if *metadata.level() <= Level::WARN {
+            return true;
+        }
+
+        let target = metadata.target();
+        let rate = {
+            let rates = self.rates.read().expect("Log sampling lock poisoned");
+            rates
+                .iter()
+                .filter(|(prefix, _)| {
+                    target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+                })
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(_, rate)| *rate)
+        };
+        let Some(rate) = rate.filter(|rate| *rate > 1) else {
+            return true;
+        };
+
+        let mut counts = self.counts.lock().expect("Log sampling lock poisoned");
+        let count = match counts.get_mut(target) {
+            Some(count) => count,
+            None => counts.entry(target.to_string()).or_default(),
+        };
+        *count += 1;
+        *count % rate == 1
+    }
+}
+
+/// Tracing layer dropping the events not kept by `LOG_SAMPLER`
+///
+/// The sampling runs in `event_enabled`, once every layer enabled the event, so the events
+/// filtered out by the level filter don't count. The callsites keep the default interest
+/// since the rates can change at runtime.
+pub struct SamplingLayer;
+
+impl<S: Subscriber> Layer<S> for SamplingLayer {
+    fn event_enabled(&self, event: &Event<'_>, _: Context<'_, S>) -> bool {
+        LOG_SAMPLER.keep(event.metadata())
+    }
+}
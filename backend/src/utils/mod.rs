@@ -1 +1,5 @@
 pub mod amm_math;
+pub mod json_file;
+pub mod log_sampling;
+pub mod secret;
+pub mod time;